@@ -0,0 +1,86 @@
+use mcstream::{CompressionType, McStreamError, McsEncoder};
+use std::fs;
+use std::path::Path;
+
+// 比较Zstandard默认设置与长距离匹配在平铺城市建筑上的体积
+fn main() -> Result<(), McStreamError> {
+    let default = file_size(tiled_city()?, "long_distance_default.mcs")?;
+    println!("默认: {} 字节", default);
+
+    for window_log in [24, 27] {
+        let long = file_size(
+            tiled_city()?.with_long_distance_matching(window_log),
+            "long_distance_long.mcs",
+        )?;
+        println!(
+            "长距离匹配（窗口 2^{}）: {} 字节（{:.1}%）",
+            window_log,
+            long,
+            long as f64 * 100.0 / default as f64
+        );
+    }
+
+    Ok(())
+}
+
+// 同一栋楼在每个区块中竖直堆叠多层：楼层内的方块与箱子内容各不相同，
+// 层与层之间完全重复，重复的距离超过默认压缩窗口
+fn tiled_city() -> Result<McsEncoder, McStreamError> {
+    let mut encoder = McsEncoder::new(CompressionType::Zstandard);
+    let mut seed = 0x2545_f491_u32;
+
+    // 生成一层楼：方块ID与箱子内容
+    let mut floor = Vec::new();
+    for y in 0..16 {
+        for z in 0..16 {
+            for x in 0..16 {
+                let id = match next(&mut seed) % 4 {
+                    0 => "minecraft:stone_bricks",
+                    1 => "minecraft:glass",
+                    2 => "minecraft:oak_planks",
+                    _ => "minecraft:chest[facing=north]",
+                };
+                let nbt = id.starts_with("minecraft:chest").then(|| {
+                    (0..2048)
+                        .map(|_| next(&mut seed) as u8)
+                        .collect::<Vec<u8>>()
+                });
+                floor.push((x, y, z, id, nbt));
+            }
+        }
+    }
+
+    for cx in 0..2 {
+        for cz in 0..2 {
+            for level in 0..3 {
+                for (x, y, z, id, nbt) in &floor {
+                    encoder.add_block(
+                        id.to_string(),
+                        cx * 16 + x,
+                        level * 16 + y,
+                        cz * 16 + z,
+                        nbt.clone(),
+                    )?;
+                }
+            }
+        }
+    }
+
+    Ok(encoder)
+}
+
+// 简单的伪随机数（xorshift），保证每次运行结果相同
+fn next(seed: &mut u32) -> u32 {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 17;
+    *seed ^= *seed << 5;
+    *seed
+}
+
+fn file_size(encoder: McsEncoder, name: &str) -> Result<u64, McStreamError> {
+    let path = Path::new(name);
+    encoder.write_to_file(path)?;
+    let size = fs::metadata(path)?.len();
+    fs::remove_file(path)?;
+    Ok(size)
+}
@@ -40,6 +40,8 @@ fn main() -> Result<(), McStreamError> {
         1 => "Zstandard",
         2 => "LZ4",
         3 => "Brotli",
+        4 => "LZ4（块格式）",
+        0x80..=0xFF => "自定义",
         _ => "未知",
     };
 
@@ -67,7 +69,7 @@ fn main() -> Result<(), McStreamError> {
 
         // 输出每个方块的信息
         for block in &chunk.blocks {
-            let block_id = &chunk.palette[block.palette_index as usize];
+            let block_id = &chunk.palette[block.palette_index];
             let global_x = (pos.x * 16) + block.pos.x as i32;
             let global_y = block.pos.actual_y();
             let global_z = (pos.z * 16) + block.pos.z as i32;
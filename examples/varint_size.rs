@@ -0,0 +1,85 @@
+use mcstream::{
+    chunk::{serialize_chunk, serialize_chunk_as, CHUNK_FORMAT_VARINT},
+    CompressionType, McStreamError, McsEncoder,
+};
+use std::fs;
+use std::path::Path;
+
+// 比较定长与变长整数区块格式在稀疏红石建筑上的体积
+fn main() -> Result<(), McStreamError> {
+    let compressions = [
+        ("无压缩", CompressionType::None),
+        ("Zstandard", CompressionType::Zstandard),
+    ];
+
+    // 解压后的区块数据体积
+    let encoder = sparse_redstone_build(CompressionType::None)?;
+    let (mut fixed, mut varint) = (0, 0);
+    for chunk in encoder.get_chunks().values() {
+        fixed += serialize_chunk(chunk)?.len();
+        varint += serialize_chunk_as(chunk, CHUNK_FORMAT_VARINT)?.len();
+    }
+    println!(
+        "区块数: {}，解压后: 定长 {} 字节，变长 {} 字节（{:.1}%）",
+        encoder.get_chunks().len(),
+        fixed,
+        varint,
+        varint as f64 * 100.0 / fixed as f64
+    );
+
+    // 完整文件体积
+    for (name, compression) in compressions {
+        let fixed = file_size(sparse_redstone_build(compression)?, "varint_fixed.mcs")?;
+        let varint = file_size(
+            sparse_redstone_build(compression)?.with_varint_encoding(),
+            "varint_varint.mcs",
+        )?;
+        println!(
+            "{}: 定长 {} 字节，变长 {} 字节（{:.1}%）",
+            name,
+            fixed,
+            varint,
+            varint as f64 * 100.0 / fixed as f64
+        );
+    }
+
+    Ok(())
+}
+
+// 分散在多个区块中的红石线路：每个区块只有一条短线路和少量元件
+fn sparse_redstone_build(compression: CompressionType) -> Result<McsEncoder, McStreamError> {
+    let mut encoder = McsEncoder::new(compression);
+
+    for cx in 0..8 {
+        for cz in 0..8 {
+            let (bx, bz) = (cx * 16, cz * 16);
+            for i in 0..12 {
+                encoder.add_block("minecraft:stone".to_string(), bx + i, 0, bz + 4, None)?;
+                let id = match i {
+                    3 => "minecraft:repeater[delay=2,facing=east]",
+                    7 => "minecraft:comparator[facing=east,mode=compare]",
+                    11 => "minecraft:redstone_lamp[lit=false]",
+                    _ => "minecraft:redstone_wire[east=side,west=side]",
+                };
+                encoder.add_block(id.to_string(), bx + i, 1, bz + 4, None)?;
+            }
+            encoder.add_block(
+                "minecraft:lever[face=floor]".to_string(),
+                bx,
+                1,
+                bz + 5,
+                None,
+            )?;
+        }
+    }
+
+    Ok(encoder)
+}
+
+fn file_size(encoder: McsEncoder, name: &str) -> Result<u64, McStreamError> {
+    let path = Path::new(name);
+    encoder.write_to_file(path)?;
+    let size = fs::metadata(path)?.len();
+    fs::remove_file(path)?;
+    Ok(size)
+}
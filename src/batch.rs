@@ -0,0 +1,172 @@
+// 批量处理目录中的MCS文件
+// 每个文件独立处理并单独记录结果，一个文件出错不会中断其余文件
+
+use crate::{
+    error::McStreamError, export::json, integrity::verify_tree_hash, interop::structure,
+    packer::McsEncoder, unpacker::McsDecoder, CompressionType,
+};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// 转换的目标格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// 扁平JSON（与 `unpack` 命令输出一致，含编辑日志时为重放后的状态）
+    Json,
+    /// 原版结构文件（.nbt）
+    StructureNbt,
+}
+
+impl Format {
+    /// 输出文件的扩展名
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::StructureNbt => "nbt",
+        }
+    }
+}
+
+/// 对每个文件执行的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchOp {
+    /// 完整解码，含树形哈希时同时校验
+    Validate,
+    /// 以指定压缩算法重新压缩，原地替换（签名不保留）
+    Recompress(CompressionType),
+    /// 转换为其他格式，输出与原文件同名、扩展名不同的文件
+    ConvertTo(Format),
+}
+
+/// 单个文件的处理结果
+#[derive(Debug)]
+pub struct BatchEntry {
+    pub path: PathBuf,
+    pub output: Option<PathBuf>, // 转换得到的新文件
+    pub result: Result<(), McStreamError>,
+}
+
+/// 批量处理的结果，按文件路径排序
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub entries: Vec<BatchEntry>,
+}
+
+impl BatchReport {
+    /// 全部文件处理成功
+    pub fn is_success(&self) -> bool {
+        self.entries.iter().all(|e| e.result.is_ok())
+    }
+
+    /// 处理成功的文件
+    pub fn succeeded(&self) -> impl Iterator<Item = &BatchEntry> {
+        self.entries.iter().filter(|e| e.result.is_ok())
+    }
+
+    /// 处理失败的文件
+    pub fn failed(&self) -> impl Iterator<Item = &BatchEntry> {
+        self.entries.iter().filter(|e| e.result.is_err())
+    }
+}
+
+/// 对目录中所有 `.mcs` 文件（不含子目录）并行执行 `op`
+///
+/// 只有读取目录本身失败时返回错误，单个文件的错误记录在报告中。
+pub fn process_dir<P: AsRef<Path>>(dir: P, op: BatchOp) -> Result<BatchReport, McStreamError> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_mcs = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("mcs"));
+        if is_mcs && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    Ok(process_files(&paths, op))
+}
+
+/// 对给定的文件执行 `op`，启用 `parallel` 特性时并行处理
+pub fn process_files<P: AsRef<Path> + Sync>(paths: &[P], op: BatchOp) -> BatchReport {
+    #[cfg(feature = "parallel")]
+    let iter = paths.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let iter = paths.iter();
+    let entries = iter
+        .map(|path| {
+            let path = path.as_ref();
+            let (output, result) = match process_file(path, op) {
+                Ok(output) => (output, Ok(())),
+                Err(e) => {
+                    log::warn!("处理 {} 失败: {}", path.display(), e);
+                    (None, Err(e))
+                }
+            };
+            BatchEntry {
+                path: path.to_path_buf(),
+                output,
+                result,
+            }
+        })
+        .collect();
+
+    BatchReport { entries }
+}
+
+// 处理单个文件，转换时返回输出文件的路径
+fn process_file(path: &Path, op: BatchOp) -> Result<Option<PathBuf>, McStreamError> {
+    match op {
+        BatchOp::Validate => {
+            let decoder = McsDecoder::from_file(path)?;
+            if decoder.header().has_tree_hash() {
+                let verification = verify_tree_hash(path)?;
+                if !verification.is_valid() {
+                    return Err(McStreamError::ValidationError(format!(
+                        "树形哈希校验失败，损坏的区块: {:?}",
+                        verification.corrupt_chunks
+                    )));
+                }
+            }
+            Ok(None)
+        }
+        BatchOp::Recompress(compression) => {
+            let decoder = McsDecoder::builder().keep_nbt_compressed(true).open(path)?;
+            let encoder = McsEncoder::from_decoder(decoder, compression)?;
+
+            // 先写入同目录的临时文件再替换，失败时原文件保持不变
+            let dir = path.parent().unwrap_or(Path::new("."));
+            let mut temp = NamedTempFile::new_in(dir)?;
+            let mut writer = BufWriter::new(temp.as_file_mut());
+            encoder.write_to_writer(&mut writer)?;
+            writer.flush()?;
+            drop(writer);
+            temp.persist(path).map_err(|e| McStreamError::Io(e.error))?;
+            Ok(None)
+        }
+        BatchOp::ConvertTo(format) => {
+            let output = path.with_extension(format.extension());
+            let decoder = McsDecoder::from_file(path)?;
+            match format {
+                Format::Json => {
+                    let data = if decoder.journal().is_empty() {
+                        decoder.into_mcs_data()
+                    } else {
+                        decoder.replay()?
+                    };
+                    let mut writer = BufWriter::new(File::create(&output)?);
+                    json::write_flat_json(&data, json::BlockOrder::None, &mut writer)?;
+                    writer.flush()?;
+                }
+                Format::StructureNbt => structure::write_structure_nbt(&decoder, &output)?,
+            }
+            Ok(Some(output))
+        }
+    }
+}
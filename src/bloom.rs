@@ -0,0 +1,81 @@
+// 区块调色板的布隆过滤器
+// 每个区块的调色板ID写入一个固定大小的位图，查询时只需读取索引表与尾部，
+// 即可判断区块"可能含有"某个方块ID而无需解压区块数据
+
+use crate::error::McStreamError;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Write};
+
+/// 单个布隆过滤器的字节数
+pub const BLOOM_SIZE: usize = 32;
+
+// 每个ID设置的位数
+const BLOOM_HASHES: u64 = 4;
+
+/// 单个区块调色板的布隆过滤器
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PaletteBloom(pub [u8; BLOOM_SIZE]);
+
+impl PaletteBloom {
+    /// 由调色板构建
+    pub fn from_palette(palette: &[String]) -> Self {
+        let mut bloom = Self::default();
+        for id in palette {
+            bloom.insert(id);
+        }
+        bloom
+    }
+
+    /// 加入一个方块ID
+    pub fn insert(&mut self, id: &str) {
+        for bit in bit_positions(id) {
+            self.0[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    /// 是否可能含有该方块ID；返回false时一定不含
+    pub fn may_contain(&self, id: &str) -> bool {
+        bit_positions(id).all(|bit| self.0[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+}
+
+// FNV-1a 64位哈希，结果与平台无关
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// 双重哈希：第i位为 h1 + i·h2
+fn bit_positions(id: &str) -> impl Iterator<Item = usize> {
+    let hash = fnv1a(id.as_bytes());
+    let (h1, h2) = (hash & 0xFFFF_FFFF, (hash >> 32) | 1);
+    (0..BLOOM_HASHES)
+        .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % (BLOOM_SIZE as u64 * 8)) as usize)
+}
+
+/// 写入布隆过滤器段
+pub fn write_palette_blooms<W: Write>(
+    writer: &mut W,
+    blooms: &[PaletteBloom],
+) -> Result<(), McStreamError> {
+    writer.write_u32::<LittleEndian>(blooms.len() as u32)?;
+    for bloom in blooms {
+        writer.write_all(&bloom.0)?;
+    }
+    Ok(())
+}
+
+/// 读取布隆过滤器段
+pub fn read_palette_blooms<R: Read>(reader: &mut R) -> Result<Vec<PaletteBloom>, McStreamError> {
+    let count = reader.read_u32::<LittleEndian>()?;
+
+    let mut blooms = Vec::with_capacity((count as usize).min(4096));
+    for _ in 0..count {
+        let mut bloom = [0u8; BLOOM_SIZE];
+        reader.read_exact(&mut bloom)?;
+        blooms.push(PaletteBloom(bloom));
+    }
+
+    Ok(blooms)
+}
@@ -1,13 +1,101 @@
 use crate::{
     compression::{compress_data, compression_type_from_u8, decompress_data},
     error::McStreamError,
-    palette,
-    types::{Block, ChunkData, ChunkIndexEntry, ChunkPos, LocalBlockPos},
-    CompressionType,
+    format::{index_entry_size, INDEX_ENTRY_SIZE},
+    palette::{self, IdTable, Palette, PaletteId},
+    types::{Block, BlockPos, ChunkData, ChunkIndexEntry, ChunkPos, LocalBlockPos, CHUNK_VOLUME},
+    utils::{read_u16_field, read_u32_field, write_u16_field, write_u32_field},
+    CompressionType, MCS_VERSION,
 };
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::collections::HashMap;
 use std::io::{Cursor, Read, Write};
 
+/// 方块标志位：含NBT数据
+pub const BLOCK_FLAG_NBT: u8 = 0x01;
+
+/// 方块标志位：NBT数据单独压缩，数据前带有1字节压缩算法
+pub const BLOCK_FLAG_NBT_COMPRESSED: u8 = 0x02;
+
+/// 方块标志位：含覆盖层，方块记录后带有2字节覆盖层调色板索引
+pub const BLOCK_FLAG_OVERLAY: u8 = 0x04;
+
+/// 方块标志位：含编辑器标签，覆盖层（如有）之后带有2字节标签位
+pub const BLOCK_FLAG_TAGS: u8 = 0x08;
+
+/// 区块格式位：计数、长度与调色板索引使用LEB128变长整数
+///
+/// 仅当头部设置了 [`FLAG_CHUNK_FORMAT`](crate::header::FLAG_CHUNK_FORMAT) 时，
+/// 区块数据才以1字节区块格式开头。
+pub const CHUNK_FORMAT_VARINT: u8 = 0x01;
+
+/// 区块格式位：调色板按方块ID表编码（见 [`IdTable`]），格式字节后带有4字节表ID
+pub const CHUNK_FORMAT_ID_TABLE: u8 = 0x02;
+
+/// 区块格式位：调色板索引与覆盖层索引为单字节，仅适用于不超过256项的调色板
+pub const CHUNK_FORMAT_NARROW_INDEX: u8 = 0x04;
+
+/// 单字节索引可表示的调色板条目数
+pub const NARROW_INDEX_PALETTE_LIMIT: usize = 256;
+
+/// 区块格式位：NBT数据区之后附有按坐标排序的空间索引（见 [`SpatialIndex`]）
+pub const CHUNK_FORMAT_SPATIAL_INDEX: u8 = 0x08;
+
+/// 区块格式位：调色板大小、调色板索引与覆盖层索引为4字节，用于超过
+/// [`MAX_PALETTE_LEN`](crate::palette::MAX_PALETTE_LEN) 项的调色板
+///
+/// 不能与 [`CHUNK_FORMAT_NARROW_INDEX`] 同时使用。
+pub const CHUNK_FORMAT_WIDE_INDEX: u8 = 0x10;
+
+/// 区块格式位：格式字节（及方块ID表的表ID）之后带有int32小端的压缩级别，只供查看，解码时不使用
+pub const CHUNK_FORMAT_COMPRESSION_LEVEL: u8 = 0x20;
+
+/// 区块内按局部坐标排序的方块下标，可按坐标二分查找方块
+///
+/// 坐标按 Y → Z → X 的顺序比较；同一坐标有多个方块时靠后的排在后面，查找时取最后一个。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpatialIndex(Vec<u32>);
+
+impl SpatialIndex {
+    /// 为区块的方块列表建立索引
+    pub fn build(blocks: &[Block]) -> Self {
+        let mut order: Vec<u32> = (0..blocks.len() as u32).collect();
+        order.sort_by_key(|&i| pack_local_pos(blocks[i as usize].pos));
+        Self(order)
+    }
+
+    /// 坐标处的方块在 `blocks` 中的下标，`blocks` 须为建立索引时的列表
+    pub fn find(&self, blocks: &[Block], pos: LocalBlockPos) -> Option<usize> {
+        let key_of = |i: u32| {
+            blocks
+                .get(i as usize)
+                .map_or(u32::MAX, |b| pack_local_pos(b.pos))
+        };
+        let key = pack_local_pos(pos);
+        let end = self.0.partition_point(|&i| key_of(i) <= key);
+        let i = *self.0.get(end.checked_sub(1)?)? as usize;
+        blocks.get(i).is_some_and(|b| b.pos == pos).then_some(i)
+    }
+
+    // 读取的索引须恰好是方块下标的一个排列，且与 `build` 的顺序一致
+    fn is_valid_for(&self, blocks: &[Block]) -> bool {
+        let mut seen = vec![false; blocks.len()];
+        for &i in &self.0 {
+            match seen.get_mut(i as usize) {
+                Some(seen) if !*seen => *seen = true,
+                _ => return false,
+            }
+        }
+        let sort_key = |i: u32| (pack_local_pos(blocks[i as usize].pos), i);
+        self.0.len() == blocks.len() && self.0.windows(2).all(|w| sort_key(w[0]) < sort_key(w[1]))
+    }
+}
+
+// 局部坐标压缩为可排序的整数：Y占高位，其后为Z与X各4位
+fn pack_local_pos(pos: LocalBlockPos) -> u32 {
+    (pos.y as u32) << 8 | (pos.z as u32) << 4 | pos.x as u32
+}
+
 /// 验证局部坐标是否在有效范围内
 pub fn validate_local_pos(pos: &LocalBlockPos) -> Result<(), McStreamError> {
     if pos.x > 15 || pos.z > 15 || pos.y > 383 {
@@ -16,11 +104,80 @@ pub fn validate_local_pos(pos: &LocalBlockPos) -> Result<(), McStreamError> {
     Ok(())
 }
 
-/// 写入区块索引表
+// 区块内坐标、方块ID与NBT
+type LocalBlock = (LocalBlockPos, String, Option<Vec<u8>>);
+
+/// 把全局坐标的方块重新分组为区块，为每个区块建立新的调色板
+///
+/// 空气方块被跳过，同一坐标出现多次时以最后一次为准；NBT按原样保存（不单独压缩）。
+/// Y坐标超出 -64～319 时返回 `CoordinateOutOfRange`。
+pub fn rechunk(
+    blocks: impl IntoIterator<Item = (BlockPos, String, Option<Vec<u8>>)>,
+) -> Result<HashMap<ChunkPos, ChunkData>, McStreamError> {
+    // 先按区块收集最终的方块，调色板只包含实际留下的方块ID
+    let mut grouped: HashMap<ChunkPos, Vec<LocalBlock>> = HashMap::new();
+    let mut slots: HashMap<BlockPos, usize> = HashMap::new(); // 坐标 → 在所属区块列表中的位置
+
+    for (pos, block_id, nbt) in blocks {
+        if block_id.contains("minecraft:air") {
+            continue;
+        }
+
+        let local_pos = pos.local_pos();
+        validate_local_pos(&local_pos)?;
+
+        let entries = grouped.entry(pos.chunk_pos()).or_default();
+        match slots.get(&pos) {
+            Some(&slot) => entries[slot] = (local_pos, block_id, nbt),
+            None => {
+                slots.insert(pos, entries.len());
+                entries.push((local_pos, block_id, nbt));
+            }
+        }
+    }
+
+    grouped
+        .into_iter()
+        .map(|(chunk_pos, entries)| {
+            let mut palette = Palette::new();
+            let blocks = entries
+                .into_iter()
+                .map(|(local_pos, block_id, nbt)| {
+                    Ok(Block {
+                        palette_index: palette.get_or_insert(block_id)?,
+                        pos: local_pos,
+                        nbt,
+                        nbt_compression: None,
+                        overlay: None,
+                        tags: 0,
+                    })
+                })
+                .collect::<Result<_, McStreamError>>()?;
+            let chunk = ChunkData {
+                pos: chunk_pos,
+                palette,
+                blocks,
+            };
+            Ok((chunk_pos, chunk))
+        })
+        .collect()
+}
+
+/// 写入区块索引表（当前版本格式）
 pub fn write_chunk_index<W: Write>(
     writer: &mut W,
     entries: &[ChunkIndexEntry],
 ) -> Result<(), McStreamError> {
+    write_chunk_index_as(writer, entries, MCS_VERSION)
+}
+
+/// 按指定版本的条目布局写入区块索引表，1.0版本不写方块数
+pub fn write_chunk_index_as<W: Write>(
+    writer: &mut W,
+    entries: &[ChunkIndexEntry],
+    version: u16,
+) -> Result<(), McStreamError> {
+    let has_block_count = index_entry_size(version) >= INDEX_ENTRY_SIZE;
     writer.write_u32::<LittleEndian>(entries.len() as u32)?;
 
     for entry in entries {
@@ -28,22 +185,34 @@ pub fn write_chunk_index<W: Write>(
         writer.write_i32::<LittleEndian>(entry.chunk_z)?;
         writer.write_u32::<LittleEndian>(entry.data_offset)?;
         writer.write_u32::<LittleEndian>(entry.compressed_size)?;
+        if has_block_count {
+            writer.write_u32::<LittleEndian>(entry.block_count.unwrap_or(0))?;
+        }
     }
 
     Ok(())
 }
 
-/// 读取区块索引表
-pub fn read_chunk_index<R: Read>(reader: &mut R) -> Result<Vec<ChunkIndexEntry>, McStreamError> {
+/// 读取区块索引表，`version` 为文件头部中的版本号
+pub fn read_chunk_index<R: Read>(
+    reader: &mut R,
+    version: u16,
+) -> Result<Vec<ChunkIndexEntry>, McStreamError> {
     let entry_count = reader.read_u32::<LittleEndian>()?;
+    let has_block_count = index_entry_size(version) >= INDEX_ENTRY_SIZE;
 
-    let mut entries = Vec::with_capacity(entry_count as usize);
+    let mut entries = Vec::with_capacity((entry_count as usize).min(4096));
     for _ in 0..entry_count {
         entries.push(ChunkIndexEntry {
             chunk_x: reader.read_i32::<LittleEndian>()?,
             chunk_z: reader.read_i32::<LittleEndian>()?,
             data_offset: reader.read_u32::<LittleEndian>()?,
             compressed_size: reader.read_u32::<LittleEndian>()?,
+            block_count: if has_block_count {
+                Some(reader.read_u32::<LittleEndian>()?)
+            } else {
+                None
+            },
         });
     }
 
@@ -53,9 +222,91 @@ pub fn read_chunk_index<R: Read>(reader: &mut R) -> Result<Vec<ChunkIndexEntry>,
 /// 序列化单个区块为二进制数据
 pub fn serialize_chunk(chunk: &ChunkData) -> Result<Vec<u8>, McStreamError> {
     let mut buffer = Vec::new();
+    write_chunk_body(&mut buffer, chunk, 0, None)?;
+    Ok(buffer)
+}
+
+/// 按指定区块格式序列化，数据以1字节区块格式开头
+pub fn serialize_chunk_as(chunk: &ChunkData, format: u8) -> Result<Vec<u8>, McStreamError> {
+    serialize_chunk_with(chunk, format, None)
+}
+
+/// 按指定区块格式序列化，格式含 [`CHUNK_FORMAT_ID_TABLE`] 时调色板按 `id_table` 编码
+pub fn serialize_chunk_with(
+    chunk: &ChunkData,
+    format: u8,
+    id_table: Option<&IdTable>,
+) -> Result<Vec<u8>, McStreamError> {
+    serialize_chunk_with_level(chunk, format, id_table, None)
+}
+
+/// 同 [`serialize_chunk_with`]，格式含 [`CHUNK_FORMAT_COMPRESSION_LEVEL`] 时记录压缩级别 `level`
+pub fn serialize_chunk_with_level(
+    chunk: &ChunkData,
+    format: u8,
+    id_table: Option<&IdTable>,
+    level: Option<i32>,
+) -> Result<Vec<u8>, McStreamError> {
+    validate_chunk_format(format)?;
+
+    let mut buffer = vec![format];
+    let table = if format & CHUNK_FORMAT_ID_TABLE != 0 {
+        let table = id_table.ok_or_else(|| {
+            McStreamError::ValidationError("区块格式要求方块ID表，但未提供".to_string())
+        })?;
+        buffer.write_u32::<LittleEndian>(table.table_id())?;
+        Some(table)
+    } else {
+        None
+    };
+    if format & CHUNK_FORMAT_COMPRESSION_LEVEL != 0 {
+        let level = level.ok_or_else(|| {
+            McStreamError::ValidationError("区块格式要求记录压缩级别，但未提供".to_string())
+        })?;
+        buffer.write_i32::<LittleEndian>(level)?;
+    }
+    write_chunk_body(&mut buffer, chunk, format, table)?;
+    Ok(buffer)
+}
+
+fn write_chunk_body(
+    buffer: &mut Vec<u8>,
+    chunk: &ChunkData,
+    format: u8,
+    id_table: Option<&IdTable>,
+) -> Result<(), McStreamError> {
+    let varint = format & CHUNK_FORMAT_VARINT != 0;
+    let width = IndexWidth::of(format);
+    if width == IndexWidth::Narrow && chunk.palette.len() > NARROW_INDEX_PALETTE_LIMIT {
+        return Err(McStreamError::PaletteError(format!(
+            "调色板有 {} 个条目，无法使用单字节索引",
+            chunk.palette.len()
+        )));
+    }
+
+    let wide = width == IndexWidth::Wide;
+    match id_table {
+        Some(table) => palette::write_palette_with_table_sized(
+            buffer,
+            chunk.palette.as_slice(),
+            varint,
+            wide,
+            table,
+        )?,
+        None => palette::write_palette_sized(buffer, chunk.palette.as_slice(), varint, wide)?,
+    }
 
-    palette::write_palette(&mut buffer, &chunk.palette)?;
-    buffer.write_u32::<LittleEndian>(chunk.blocks.len() as u32)?;
+    // 一个区块最多容纳 CHUNK_VOLUME 个方块，更多说明有重复坐标（如错误地合并了区块）
+    if chunk.blocks.len() > CHUNK_VOLUME {
+        return Err(McStreamError::ValidationError(format!(
+            "区块 ({}, {}) 有 {} 个方块，超过区块容量 {}，可能含有重复坐标",
+            chunk.pos.x,
+            chunk.pos.z,
+            chunk.blocks.len(),
+            CHUNK_VOLUME
+        )));
+    }
+    write_u32_field(buffer, chunk.blocks.len() as u32, varint)?;
 
     let nbt_blocks: Vec<&Block> = chunk
         .blocks
@@ -64,57 +315,260 @@ pub fn serialize_chunk(chunk: &ChunkData) -> Result<Vec<u8>, McStreamError> {
         .collect();
 
     for block in &chunk.blocks {
-        buffer.write_u16::<LittleEndian>(block.palette_index)?;
+        let mut flags = 0x00;
+        if block.nbt.is_some() {
+            flags |= BLOCK_FLAG_NBT;
+            if block.nbt_compression.is_some() {
+                flags |= BLOCK_FLAG_NBT_COMPRESSED;
+            }
+        }
+        if block.overlay.is_some() {
+            flags |= BLOCK_FLAG_OVERLAY;
+        }
+        if block.tags != 0 {
+            flags |= BLOCK_FLAG_TAGS;
+        }
+
+        write_palette_index(buffer, block.palette_index, varint, width)?;
         buffer.write_u8(block.pos.x)?;
         buffer.write_u16::<LittleEndian>(block.pos.y)?;
         buffer.write_u8(block.pos.z)?;
-        buffer.write_u8(if block.nbt.is_some() { 0x01 } else { 0x00 })?;
+        buffer.write_u8(flags)?;
+
+        if let Some(overlay) = block.overlay {
+            write_palette_index(buffer, overlay, varint, width)?;
+        }
+        if block.tags != 0 {
+            write_u16_field(buffer, block.tags, varint)?;
+        }
     }
 
-    buffer.write_u32::<LittleEndian>(nbt_blocks.len() as u32)?;
+    write_u32_field(buffer, nbt_blocks.len() as u32, varint)?;
 
     for block in nbt_blocks {
         if let Some(nbt_data) = &block.nbt {
-            buffer.write_u32::<LittleEndian>(nbt_data.len() as u32)?;
+            write_u32_field(buffer, nbt_data.len() as u32, varint)?;
+            if let Some(compression) = block.nbt_compression {
+                buffer.write_u8(compression.to_u8())?;
+            }
             buffer.write_all(nbt_data)?;
         }
     }
 
-    Ok(buffer)
+    if format & CHUNK_FORMAT_SPATIAL_INDEX != 0 {
+        for i in SpatialIndex::build(&chunk.blocks).0 {
+            write_u32_field(buffer, i, varint)?;
+        }
+    }
+
+    Ok(())
 }
 
+// 区块格式字节及其后记录的信息
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ChunkPrefix {
+    pub format: u8,
+    pub compression_level: Option<i32>,
+}
+
+// 解码得到的区块、区块数据中附带的空间索引与区块格式前缀
+pub(crate) type DecodedChunk<S> = (ChunkData<S>, Option<SpatialIndex>, ChunkPrefix);
+
 /// 反序列化二进制数据为区块
+///
+/// 单独压缩的NBT数据保持压缩状态，可通过 [`Block::nbt_decompressed`] 按需解压。
 pub fn deserialize_chunk(data: &[u8], pos: ChunkPos) -> Result<ChunkData, McStreamError> {
+    deserialize_chunk_with(data, pos, false, false, None, &mut str::to_owned)
+        .map(|(chunk, _, _)| chunk)
+}
+
+/// 反序列化以区块格式字节开头的数据（见 [`serialize_chunk_as`]）
+pub fn deserialize_chunk_prefixed(data: &[u8], pos: ChunkPos) -> Result<ChunkData, McStreamError> {
+    deserialize_chunk_with(data, pos, true, false, None, &mut str::to_owned)
+        .map(|(chunk, _, _)| chunk)
+}
+
+/// 反序列化区块数据，`skip_nbt` 为真时按长度跳过NBT数据，所有方块的 `nbt` 均为 `None`
+///
+/// 调色板条目经 `intern` 转换为调色板元素；区块按方块ID表编码时须提供表ID一致的 `id_table`。
+/// 区块带有空间索引时校验后一并返回，同时返回区块格式字节（无前缀时为0）与记录的压缩级别。
+pub(crate) fn deserialize_chunk_with<S: PaletteId>(
+    data: &[u8],
+    pos: ChunkPos,
+    prefixed: bool,
+    skip_nbt: bool,
+    id_table: Option<&IdTable>,
+    intern: &mut impl FnMut(&str) -> S,
+) -> Result<DecodedChunk<S>, McStreamError> {
     let mut cursor = Cursor::new(data);
+    let (format, table) = if prefixed {
+        let format = cursor.read_u8()?;
+        validate_chunk_format(format)?;
+        let table = if format & CHUNK_FORMAT_ID_TABLE != 0 {
+            Some(check_id_table(
+                cursor.read_u32::<LittleEndian>()?,
+                id_table,
+            )?)
+        } else {
+            None
+        };
+        (format, table)
+    } else {
+        (0, None)
+    };
+    let compression_level = if format & CHUNK_FORMAT_COMPRESSION_LEVEL != 0 {
+        Some(cursor.read_i32::<LittleEndian>()?)
+    } else {
+        None
+    };
+
+    let (chunk, spatial_index) =
+        read_chunk_body(&mut cursor, pos, format, skip_nbt, table, intern)?;
+    let prefix = ChunkPrefix {
+        format,
+        compression_level,
+    };
+    Ok((chunk, spatial_index, prefix))
+}
+
+// 调色板索引的宽度，由区块格式字节决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IndexWidth {
+    Narrow,
+    Default,
+    Wide,
+}
+
+impl IndexWidth {
+    fn of(format: u8) -> Self {
+        if format & CHUNK_FORMAT_NARROW_INDEX != 0 {
+            IndexWidth::Narrow
+        } else if format & CHUNK_FORMAT_WIDE_INDEX != 0 {
+            IndexWidth::Wide
+        } else {
+            IndexWidth::Default
+        }
+    }
+}
+
+// 写入调色板索引：单字节、2字节或4字节小端（非单字节时可为变长整数）
+fn write_palette_index<W: Write>(
+    writer: &mut W,
+    index: u32,
+    varint: bool,
+    width: IndexWidth,
+) -> Result<(), McStreamError> {
+    match width {
+        IndexWidth::Narrow => {
+            let index = u8::try_from(index).map_err(|_| {
+                McStreamError::PaletteError(format!("调色板索引 {} 超出单字节范围", index))
+            })?;
+            writer.write_u8(index)?;
+            Ok(())
+        }
+        IndexWidth::Default => {
+            let index = u16::try_from(index).map_err(|_| {
+                McStreamError::PaletteError(format!(
+                    "调色板索引 {} 超出2字节范围，需使用4字节索引",
+                    index
+                ))
+            })?;
+            write_u16_field(writer, index, varint)
+        }
+        IndexWidth::Wide => write_u32_field(writer, index, varint),
+    }
+}
+
+fn read_palette_index<R: Read>(
+    reader: &mut R,
+    varint: bool,
+    width: IndexWidth,
+) -> Result<u32, McStreamError> {
+    match width {
+        IndexWidth::Narrow => Ok(reader.read_u8()? as u32),
+        IndexWidth::Default => Ok(read_u16_field(reader, varint)? as u32),
+        IndexWidth::Wide => read_u32_field(reader, varint),
+    }
+}
+
+// 区块记录的表ID必须与提供的方块ID表一致
+fn check_id_table(table_id: u32, id_table: Option<&IdTable>) -> Result<&IdTable, McStreamError> {
+    match id_table {
+        Some(table) if table.table_id() == table_id => Ok(table),
+        Some(table) => Err(McStreamError::ValidationError(format!(
+            "方块ID表不匹配：区块需要ID为 {:08x} 的表，提供的表ID为 {:08x}",
+            table_id,
+            table.table_id()
+        ))),
+        None => Err(McStreamError::ValidationError(format!(
+            "区块使用了ID为 {:08x} 的方块ID表，解码时需要提供该表",
+            table_id
+        ))),
+    }
+}
+
+fn read_chunk_body<S: PaletteId>(
+    cursor: &mut Cursor<&[u8]>,
+    pos: ChunkPos,
+    format: u8,
+    skip_nbt: bool,
+    id_table: Option<&IdTable>,
+    intern: &mut impl FnMut(&str) -> S,
+) -> Result<(ChunkData<S>, Option<SpatialIndex>), McStreamError> {
+    let varint = format & CHUNK_FORMAT_VARINT != 0;
+    let width = IndexWidth::of(format);
 
-    let palette = palette::read_palette(&mut cursor)?;
-    let block_count = cursor.read_u32::<LittleEndian>()?;
+    let wide = width == IndexWidth::Wide;
+    let palette = match id_table {
+        Some(table) => palette::read_palette_with_table_sized(cursor, varint, wide, table, intern)?,
+        None => palette::read_palette_sized(cursor, varint, wide, intern)?,
+    };
+    let block_count = read_u32_field(cursor, varint)?;
 
     let mut blocks = Vec::with_capacity(block_count as usize);
     let mut nbt_blocks = Vec::new();
 
     for _ in 0..block_count {
-        let palette_index = cursor.read_u16::<LittleEndian>()?;
+        let palette_index = read_palette_index(cursor, varint, width)?;
         let x = cursor.read_u8()?;
         let y = cursor.read_u16::<LittleEndian>()?;
         let z = cursor.read_u8()?;
         let local_pos = LocalBlockPos::new(x, y, z);
 
         let flags = cursor.read_u8()?;
-        let has_nbt = (flags & 0x01) != 0;
+        let has_nbt = (flags & BLOCK_FLAG_NBT) != 0;
+
+        let overlay = if (flags & BLOCK_FLAG_OVERLAY) != 0 {
+            Some(read_palette_index(cursor, varint, width)?)
+        } else {
+            None
+        };
+        let tags = if (flags & BLOCK_FLAG_TAGS) != 0 {
+            read_u16_field(cursor, varint)?
+        } else {
+            0
+        };
 
         blocks.push(Block {
             palette_index,
             pos: local_pos,
-            nbt: if has_nbt { Some(Vec::new()) } else { None },
+            nbt: if has_nbt && !skip_nbt {
+                Some(Vec::new())
+            } else {
+                None
+            },
+            nbt_compression: None,
+            overlay,
+            tags,
         });
 
         if has_nbt {
-            nbt_blocks.push(blocks.len() - 1);
+            let nbt_compressed = (flags & BLOCK_FLAG_NBT_COMPRESSED) != 0;
+            nbt_blocks.push((blocks.len() - 1, nbt_compressed));
         }
     }
 
-    let nbt_count = cursor.read_u32::<LittleEndian>()?;
+    let nbt_count = read_u32_field(cursor, varint)?;
 
     if nbt_count as usize != nbt_blocks.len() {
         return Err(McStreamError::NbtError(
@@ -122,21 +576,76 @@ pub fn deserialize_chunk(data: &[u8], pos: ChunkPos) -> Result<ChunkData, McStre
         ));
     }
 
-    for block_index in nbt_blocks {
-        let nbt_len = cursor.read_u32::<LittleEndian>()?;
+    for (block_index, nbt_compressed) in nbt_blocks {
+        let nbt_len = read_u32_field(cursor, varint)?;
+        let nbt_compression = if nbt_compressed {
+            Some(compression_type_from_u8(cursor.read_u8()?)?)
+        } else {
+            None
+        };
+
+        if skip_nbt {
+            let end = cursor.position() + nbt_len as u64;
+            if end > cursor.get_ref().len() as u64 {
+                return Err(McStreamError::NbtError("NBT数据不完整".to_string()));
+            }
+            cursor.set_position(end);
+            continue;
+        }
+
         let mut nbt_data = vec![0u8; nbt_len as usize];
         cursor.read_exact(&mut nbt_data)?;
 
         if let Some(block) = blocks.get_mut(block_index) {
             block.nbt = Some(nbt_data);
+            block.nbt_compression = nbt_compression;
         }
     }
 
-    Ok(ChunkData {
+    let spatial_index = if format & CHUNK_FORMAT_SPATIAL_INDEX != 0 {
+        let index = (0..block_count)
+            .map(|_| read_u32_field(cursor, varint))
+            .collect::<Result<_, _>>()?;
+        let index = SpatialIndex(index);
+        if !index.is_valid_for(&blocks) {
+            return Err(McStreamError::ValidationError(format!(
+                "区块 ({}, {}) 的空间索引与方块不一致",
+                pos.x, pos.z
+            )));
+        }
+        Some(index)
+    } else {
+        None
+    };
+
+    let chunk = ChunkData {
         pos,
-        palette,
+        palette: Palette::from(palette),
         blocks,
-    })
+    };
+    Ok((chunk, spatial_index))
+}
+
+fn validate_chunk_format(format: u8) -> Result<(), McStreamError> {
+    let known = CHUNK_FORMAT_VARINT
+        | CHUNK_FORMAT_ID_TABLE
+        | CHUNK_FORMAT_NARROW_INDEX
+        | CHUNK_FORMAT_SPATIAL_INDEX
+        | CHUNK_FORMAT_WIDE_INDEX
+        | CHUNK_FORMAT_COMPRESSION_LEVEL;
+    if format & !known != 0 {
+        return Err(McStreamError::ValidationError(format!(
+            "不支持的区块格式: {:#04x}",
+            format
+        )));
+    }
+    let both = CHUNK_FORMAT_NARROW_INDEX | CHUNK_FORMAT_WIDE_INDEX;
+    if format & both == both {
+        return Err(McStreamError::ValidationError(
+            "区块格式不能同时使用单字节与4字节索引".to_string(),
+        ));
+    }
+    Ok(())
 }
 
 /// 压缩区块数据
@@ -147,6 +656,15 @@ pub fn compress_chunk(
     compress_data(&serialize_chunk(chunk)?, compression_type)
 }
 
+/// 按指定区块格式序列化并压缩区块数据
+pub fn compress_chunk_as(
+    chunk: &ChunkData,
+    compression_type: CompressionType,
+    format: u8,
+) -> Result<Vec<u8>, McStreamError> {
+    compress_data(&serialize_chunk_as(chunk, format)?, compression_type)
+}
+
 /// 解压并反序列化区块数据
 pub fn decompress_chunk(
     compressed_data: &[u8],
@@ -157,3 +675,14 @@ pub fn decompress_chunk(
     let decompressed = decompress_data(compressed_data, compression)?;
     deserialize_chunk(&decompressed, pos)
 }
+
+/// 解压并反序列化以区块格式字节开头的区块数据
+pub fn decompress_chunk_prefixed(
+    compressed_data: &[u8],
+    compression_type: u8,
+    pos: ChunkPos,
+) -> Result<ChunkData, McStreamError> {
+    let compression = compression_type_from_u8(compression_type)?;
+    let decompressed = decompress_data(compressed_data, compression)?;
+    deserialize_chunk_prefixed(&decompressed, pos)
+}
@@ -1,40 +1,228 @@
+#[cfg(feature = "zstd")]
+use crate::{chunk::serialize_chunk, types::McsData};
 use crate::{error::McStreamError, CompressionType};
-use std::io::{Read, Write};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+#[cfg(any(feature = "zstd", feature = "lz4", feature = "brotli"))]
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::sync::RwLock;
 
+#[cfg(feature = "brotli")]
 const BROTLI_BUFFER_SIZE: usize = 4096;
+#[cfg(feature = "brotli")]
 const BROTLI_QUALITY: u32 = 4;
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+#[cfg(feature = "brotli")]
 const BROTLI_LGWIN: u32 = 22;
 
+/// 压缩算法的实现是否已编译进来（由 `zstd`、`lz4`、`brotli` 特性控制）
+pub fn compression_available(compression_type: CompressionType) -> bool {
+    match compression_type {
+        CompressionType::None => true,
+        CompressionType::Zstandard => cfg!(feature = "zstd"),
+        CompressionType::LZ4 | CompressionType::LZ4Block => cfg!(feature = "lz4"),
+        CompressionType::Brotli => cfg!(feature = "brotli"),
+        CompressionType::Custom(id) => custom_codecs().contains_key(&id),
+    }
+}
+
+/// 自定义压缩算法可用的压缩类型值，更小的值保留给内置算法
+pub const CUSTOM_COMPRESSION_RANGE: RangeInclusive<u8> = 0x80..=0xFF;
+
+/// 自定义压缩算法的压缩或解压函数
+pub type CodecFn = Box<dyn Fn(&[u8]) -> std::io::Result<Vec<u8>> + Send + Sync>;
+
+struct CustomCodec {
+    compress: CodecFn,
+    decompress: CodecFn,
+}
+
+// 进程内共享，注册后所有编码器与解码器都可使用
+static CUSTOM_CODECS: RwLock<BTreeMap<u8, CustomCodec>> = RwLock::new(BTreeMap::new());
+
+fn custom_codecs() -> std::sync::RwLockReadGuard<'static, BTreeMap<u8, CustomCodec>> {
+    // 函数内panic不会破坏映射本身，忽略中毒状态
+    CUSTOM_CODECS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// 注册自定义压缩算法，之后以 `CompressionType::Custom(id)` 压缩与解压
+///
+/// `id` 写入文件头，须在 [`CUSTOM_COMPRESSION_RANGE`] 内；重复注册同一ID时替换原有的函数。
+/// 读取这类文件的一方须注册相同的算法，否则解码时返回 [`McStreamError::UnsupportedCompression`]。
+pub fn register_custom_compression<C, D>(
+    id: u8,
+    compress: C,
+    decompress: D,
+) -> Result<(), McStreamError>
+where
+    C: Fn(&[u8]) -> std::io::Result<Vec<u8>> + Send + Sync + 'static,
+    D: Fn(&[u8]) -> std::io::Result<Vec<u8>> + Send + Sync + 'static,
+{
+    check_custom_id(id)?;
+    let codec = CustomCodec {
+        compress: Box::new(compress),
+        decompress: Box::new(decompress),
+    };
+    CUSTOM_CODECS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .insert(id, codec);
+    Ok(())
+}
+
+/// 注销自定义压缩算法，返回此前是否已注册
+pub fn unregister_custom_compression(id: u8) -> bool {
+    CUSTOM_CODECS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .remove(&id)
+        .is_some()
+}
+
+fn check_custom_id(id: u8) -> Result<(), McStreamError> {
+    if !CUSTOM_COMPRESSION_RANGE.contains(&id) {
+        return Err(McStreamError::CompressionError(format!(
+            "自定义压缩算法的ID 0x{:02X} 超出允许范围 0x{:02X}～0x{:02X}",
+            id,
+            CUSTOM_COMPRESSION_RANGE.start(),
+            CUSTOM_COMPRESSION_RANGE.end()
+        )));
+    }
+    Ok(())
+}
+
+/// Zstandard长距离匹配允许的窗口大小（以2为底的对数）
+///
+/// 不超过27时解码端使用默认设置即可解压，无需额外配置。
+pub const LONG_WINDOW_LOG_RANGE: RangeInclusive<u32> = 10..=27;
+
+/// 可以设置压缩级别的算法及其级别范围，其他算法返回 `None`
+pub fn compression_level_range(compression_type: CompressionType) -> Option<RangeInclusive<i32>> {
+    match compression_type {
+        CompressionType::Zstandard => Some(1..=22),
+        CompressionType::Brotli => Some(0..=11),
+        _ => None,
+    }
+}
+
+/// 附加压缩选项，字典与长距离匹配只适用于Zstandard，其他算法只接受默认值
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompressOptions<'a> {
+    /// 压缩字典（见 [`train_dictionary`]）
+    pub dictionary: Option<&'a [u8]>,
+    /// 启用长距离匹配并使用 `2^n` 字节的窗口，适合有大范围重复的大区块
+    pub long_window_log: Option<u32>,
+    /// 压缩级别（见 [`compression_level_range`]），`None` 时Zstandard为3、Brotli为4
+    pub level: Option<i32>,
+}
+
 /// 压缩数据
 pub fn compress_data(
     data: &[u8],
     compression_type: CompressionType,
 ) -> Result<Vec<u8>, McStreamError> {
+    compress_data_with(data, compression_type, &CompressOptions::default())
+}
+
+/// 按附加选项压缩数据
+pub fn compress_data_with(
+    data: &[u8],
+    compression_type: CompressionType,
+    options: &CompressOptions,
+) -> Result<Vec<u8>, McStreamError> {
+    if options.dictionary.is_some() && compression_type != CompressionType::Zstandard {
+        return Err(dictionary_unsupported());
+    }
+    if let Some(window_log) = options.long_window_log {
+        if compression_type != CompressionType::Zstandard {
+            return Err(McStreamError::CompressionError(
+                "长距离匹配只适用于Zstandard".to_string(),
+            ));
+        }
+        if !LONG_WINDOW_LOG_RANGE.contains(&window_log) {
+            return Err(McStreamError::CompressionError(format!(
+                "长距离匹配窗口 2^{} 超出允许范围 2^{}～2^{}",
+                window_log,
+                LONG_WINDOW_LOG_RANGE.start(),
+                LONG_WINDOW_LOG_RANGE.end()
+            )));
+        }
+    }
+
+    if let Some(level) = options.level {
+        match compression_level_range(compression_type) {
+            Some(range) if range.contains(&level) => {}
+            Some(range) => {
+                return Err(McStreamError::CompressionError(format!(
+                    "压缩级别 {} 超出允许范围 {}～{}",
+                    level,
+                    range.start(),
+                    range.end()
+                )))
+            }
+            None => {
+                return Err(McStreamError::CompressionError(
+                    "压缩级别只适用于Zstandard与Brotli".to_string(),
+                ))
+            }
+        }
+    }
+
     match compression_type {
         CompressionType::None => Ok(data.to_vec()),
 
+        #[cfg(feature = "zstd")]
         CompressionType::Zstandard => {
             let mut compressed = Vec::new();
-            let mut encoder = zstd::Encoder::new(&mut compressed, 3)?;
+            let dictionary = options.dictionary.unwrap_or(&[]);
+            let level = options.level.unwrap_or(ZSTD_LEVEL);
+            let mut encoder = zstd::Encoder::with_dictionary(&mut compressed, level, dictionary)?;
+            if let Some(window_log) = options.long_window_log {
+                encoder.long_distance_matching(true)?;
+                encoder.window_log(window_log)?;
+            }
             encoder.write_all(data)?;
             encoder.finish()?;
             Ok(compressed)
         }
 
+        #[cfg(feature = "lz4")]
         CompressionType::LZ4 => {
             let mut compressed = Vec::new();
-            lz4::EncoderBuilder::new()
-                .build(&mut compressed)?
-                .write_all(data)?;
+            let mut encoder = lz4::EncoderBuilder::new().build(&mut compressed)?;
+            encoder.write_all(data)?;
+            // 必须显式结束帧，否则尾部数据不会写出
+            let (_, result) = encoder.finish();
+            result?;
             Ok(compressed)
         }
 
+        #[cfg(feature = "lz4")]
+        CompressionType::LZ4Block => Ok(lz4::block::compress(data, None, true)?),
+
+        CompressionType::Custom(id) => {
+            check_custom_id(id)?;
+            let codecs = custom_codecs();
+            let codec = codecs
+                .get(&id)
+                .ok_or(McStreamError::UnsupportedCompression(id))?;
+            (codec.compress)(data).map_err(|e| {
+                McStreamError::CompressionError(format!("自定义压缩算法 0x{:02X} 失败: {}", id, e))
+            })
+        }
+
+        #[cfg(feature = "brotli")]
         CompressionType::Brotli => {
             let mut compressed = Vec::new();
             let mut encoder = brotli::CompressorWriter::new(
                 &mut compressed,
                 BROTLI_BUFFER_SIZE,
-                BROTLI_QUALITY,
+                options.level.map_or(BROTLI_QUALITY, |level| level as u32),
                 BROTLI_LGWIN,
             );
             encoder.write_all(data)?;
@@ -42,6 +230,10 @@ pub fn compress_data(
             drop(encoder);
             Ok(compressed)
         }
+
+        // 对应的特性未启用
+        #[allow(unreachable_patterns)]
+        disabled => Err(McStreamError::UnsupportedCompression(disabled.to_u8())),
     }
 }
 
@@ -50,33 +242,153 @@ pub fn decompress_data(
     compressed_data: &[u8],
     compression_type: CompressionType,
 ) -> Result<Vec<u8>, McStreamError> {
-    match compression_type {
-        CompressionType::None => Ok(compressed_data.to_vec()),
+    decompress_data_limited(compressed_data, compression_type, None)
+}
 
-        CompressionType::Zstandard => {
-            let mut decompressed = Vec::new();
-            let mut decoder = zstd::Decoder::new(compressed_data)?;
+/// 解压数据，解压后超过 `max_size` 字节时返回错误而不继续分配内存
+pub fn decompress_data_limited(
+    compressed_data: &[u8],
+    compression_type: CompressionType,
+    max_size: Option<u64>,
+) -> Result<Vec<u8>, McStreamError> {
+    decompress_data_limited_with(compressed_data, compression_type, max_size, None)
+}
+
+/// 使用压缩字典解压数据，字典须与压缩时相同
+pub fn decompress_data_limited_with(
+    compressed_data: &[u8],
+    compression_type: CompressionType,
+    max_size: Option<u64>,
+    dictionary: Option<&[u8]>,
+) -> Result<Vec<u8>, McStreamError> {
+    if dictionary.is_some() && compression_type != CompressionType::Zstandard {
+        return Err(dictionary_unsupported());
+    }
+
+    #[cfg(feature = "lz4")]
+    if compression_type == CompressionType::LZ4Block {
+        return decompress_lz4_block(compressed_data, max_size);
+    }
+    if let CompressionType::Custom(id) = compression_type {
+        return decompress_custom(compressed_data, id, max_size);
+    }
+
+    let mut decoder: Box<dyn Read + '_> = match compression_type {
+        CompressionType::None => Box::new(compressed_data),
+        #[cfg(feature = "zstd")]
+        CompressionType::Zstandard => Box::new(zstd::Decoder::with_dictionary(
+            compressed_data,
+            dictionary.unwrap_or(&[]),
+        )?),
+        #[cfg(feature = "lz4")]
+        CompressionType::LZ4 => Box::new(lz4::Decoder::new(compressed_data)?),
+        #[cfg(feature = "brotli")]
+        CompressionType::Brotli => Box::new(brotli::Decompressor::new(
+            compressed_data,
+            BROTLI_BUFFER_SIZE,
+        )),
+        // 对应的特性未启用
+        #[allow(unreachable_patterns)]
+        disabled => return Err(McStreamError::UnsupportedCompression(disabled.to_u8())),
+    };
+
+    let mut decompressed = Vec::new();
+    match max_size {
+        Some(max_size) => {
+            (&mut decoder)
+                .take(max_size.saturating_add(1))
+                .read_to_end(&mut decompressed)?;
+            if decompressed.len() as u64 > max_size {
+                return Err(McStreamError::DecompressionError(format!(
+                    "解压后的数据超过 {} 字节上限",
+                    max_size
+                )));
+            }
+        }
+        None => {
             decoder.read_to_end(&mut decompressed)?;
-            Ok(decompressed)
         }
+    }
 
-        CompressionType::LZ4 => {
-            let mut decompressed = Vec::new();
-            let mut decoder = lz4::Decoder::new(compressed_data)?;
-            decoder.read_to_end(&mut decompressed)?;
-            Ok(decompressed)
+    Ok(decompressed)
+}
+
+// 块格式记录了原始长度，先按长度检查上限再分配内存
+#[cfg(feature = "lz4")]
+fn decompress_lz4_block(
+    compressed_data: &[u8],
+    max_size: Option<u64>,
+) -> Result<Vec<u8>, McStreamError> {
+    let size = match compressed_data.get(..4) {
+        Some(size) => i32::from_le_bytes([size[0], size[1], size[2], size[3]]),
+        None => {
+            return Err(McStreamError::DecompressionError(
+                "LZ4块数据缺少长度".to_string(),
+            ))
         }
+    };
+    if size < 0 {
+        return Err(McStreamError::DecompressionError(format!(
+            "LZ4块数据的长度无效: {}",
+            size
+        )));
+    }
+    if let Some(max_size) = max_size.filter(|max| size as u64 > *max) {
+        return Err(McStreamError::DecompressionError(format!(
+            "解压后的数据超过 {} 字节上限",
+            max_size
+        )));
+    }
+    Ok(lz4::block::decompress(compressed_data, None)?)
+}
 
-        CompressionType::Brotli => {
-            let mut decompressed = Vec::new();
-            let mut decoder = brotli::Decompressor::new(
-                compressed_data,
-                4096, // buffer size
-            );
-            decoder.read_to_end(&mut decompressed)?;
-            Ok(decompressed)
+// 自定义算法一次性返回解压结果，只能在解压后检查上限
+fn decompress_custom(
+    compressed_data: &[u8],
+    id: u8,
+    max_size: Option<u64>,
+) -> Result<Vec<u8>, McStreamError> {
+    check_custom_id(id)?;
+    let codecs = custom_codecs();
+    let codec = codecs
+        .get(&id)
+        .ok_or(McStreamError::UnsupportedCompression(id))?;
+    let decompressed = (codec.decompress)(compressed_data).map_err(|e| {
+        McStreamError::DecompressionError(format!("自定义压缩算法 0x{:02X} 解压失败: {}", id, e))
+    })?;
+    if let Some(max_size) = max_size.filter(|max| decompressed.len() as u64 > *max) {
+        return Err(McStreamError::DecompressionError(format!(
+            "解压后的数据超过 {} 字节上限",
+            max_size
+        )));
+    }
+    Ok(decompressed)
+}
+
+fn dictionary_unsupported() -> McStreamError {
+    McStreamError::CompressionError("压缩字典只适用于Zstandard".to_string())
+}
+
+/// 由一组建筑训练Zstandard压缩字典，字典最多 `dict_size` 字节
+///
+/// 每个区块序列化后的数据作为一个样本；样本过少时训练可能失败。
+#[cfg(feature = "zstd")]
+pub fn train_dictionary(samples: &[McsData], dict_size: usize) -> Result<Vec<u8>, McStreamError> {
+    let mut chunks = Vec::new();
+    for data in samples {
+        for chunk in data.chunks.values() {
+            chunks.push(serialize_chunk(chunk)?);
         }
     }
+
+    zstd::dict::from_samples(&chunks, dict_size)
+        .map_err(|e| McStreamError::CompressionError(format!("训练压缩字典失败: {}", e)))
+}
+
+/// 压缩字典的ID：字典SHA-256的前4字节（小端），不会为0
+pub fn dictionary_id(dictionary: &[u8]) -> u32 {
+    let hash = Sha256::digest(dictionary);
+    u32::from_le_bytes([hash[0], hash[1], hash[2], hash[3]]).max(1)
 }
 
 /// 将压缩类型值转换为枚举
@@ -86,6 +398,8 @@ pub fn compression_type_from_u8(value: u8) -> Result<CompressionType, McStreamEr
         1 => Ok(CompressionType::Zstandard),
         2 => Ok(CompressionType::LZ4),
         3 => Ok(CompressionType::Brotli),
+        4 => Ok(CompressionType::LZ4Block),
+        id if CUSTOM_COMPRESSION_RANGE.contains(&id) => Ok(CompressionType::Custom(id)),
         _ => Err(McStreamError::UnsupportedCompression(value)),
     }
 }
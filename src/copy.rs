@@ -0,0 +1,157 @@
+// 在文件之间直接复制区块的压缩数据，不解压也不重新编码
+// 适用于拆分、合并大型存档：只读取索引表与尾部，区块数据逐个从源文件搬到目标
+
+use crate::{
+    bloom::write_palette_blooms,
+    chunk::write_chunk_index,
+    compression::compression_type_from_u8,
+    error::McStreamError,
+    format::{HEADER_SIZE, INDEX_COUNT_SIZE, INDEX_ENTRY_SIZE},
+    header::{update_dictionary_id, update_index_table_offset, write_header, McsFlags},
+    integrity::{leaf_hash, write_tree_hash, TreeHash},
+    journal::write_event,
+    packer::{HashingWriter, WriteSummary},
+    types::{ChunkIndexEntry, ChunkPos},
+    unpacker::{footer_offset, read_footer, read_validated_index},
+};
+use byteorder::{LittleEndian, WriteBytesExt};
+use sha2::Digest;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// 把 `src` 中 `filter` 返回 `true` 的区块原样复制为一个新的MCS文件，写入 `dst`
+///
+/// 区块的压缩数据直接复制，压缩算法、区块格式与压缩字典沿用源文件；树形哈希按复制的数据重新计算，
+/// 调色板布隆过滤器、采集原点与尾部扩展数据随之保留，编辑日志只保留落在所选区块内的事件。
+/// 源文件的签名复制后不再有效，不会写入。
+///
+/// 源文件为1.0版本时索引不含方块数，输出中记为0。没有区块被选中时返回错误。
+pub fn copy_chunks<P, W, F>(src: P, dst: &mut W, filter: F) -> Result<WriteSummary, McStreamError>
+where
+    P: AsRef<Path>,
+    W: Write,
+    F: Fn(ChunkPos) -> bool,
+{
+    let src = src.as_ref();
+    let (header, entries, _) = read_validated_index(src)?;
+    let footer = read_footer(src, &header, footer_offset(&header, &entries)?)?;
+
+    let selected: Vec<usize> = (0..entries.len())
+        .filter(|&i| filter(ChunkPos::new(entries[i].chunk_x, entries[i].chunk_z)))
+        .collect();
+    if selected.is_empty() {
+        return Err(McStreamError::ValidationError(
+            "没有区块数据可写入".to_string(),
+        ));
+    }
+
+    if header.has_palette_bloom() && footer.palette_blooms.len() != entries.len() {
+        return Err(McStreamError::ValidationError(
+            "布隆过滤器数量与区块索引不一致".to_string(),
+        ));
+    }
+    if header.has_signature() {
+        log::warn!("{} 的签名在复制区块后失效，输出不含签名", src.display());
+    }
+
+    let journal: Vec<_> = footer
+        .journal
+        .iter()
+        .filter(|event| filter(event.op.pos().chunk_pos()))
+        .collect();
+
+    // 区块数据紧跟索引表，各区块的偏移由源索引中的大小直接算出
+    let index_size = INDEX_COUNT_SIZE + selected.len() * INDEX_ENTRY_SIZE;
+    let mut offset = (HEADER_SIZE + index_size) as u64;
+    let mut index = Vec::with_capacity(selected.len());
+    for &i in &selected {
+        let entry = &entries[i];
+        index.push(ChunkIndexEntry {
+            data_offset: u32::try_from(offset).map_err(|_| McStreamError::FileTooLarge)?,
+            block_count: Some(entry.block_count.unwrap_or(0)),
+            ..*entry
+        });
+        offset += entry.compressed_size as u64;
+    }
+
+    let mut flags = header.flags();
+    flags.remove(McsFlags::SIGNATURE);
+    if journal.is_empty() {
+        flags.remove(McsFlags::JOURNAL);
+    }
+
+    let mut head = Vec::with_capacity(HEADER_SIZE);
+    write_header(
+        &mut head,
+        compression_type_from_u8(header.compression)?,
+        flags.bits(),
+    )?;
+    update_index_table_offset(&mut Cursor::new(&mut head), HEADER_SIZE as u32)?;
+    update_dictionary_id(&mut Cursor::new(&mut head), header.dictionary_id)?;
+
+    let mut writer = HashingWriter::new(dst);
+    writer.write_all(&head)?;
+    write_chunk_index(&mut writer, &index)?;
+
+    // 逐个区块复制，内存中只保留一个区块的数据
+    let mut reader = BufReader::new(File::open(src)?);
+    let mut leaves = Vec::with_capacity(selected.len());
+    let mut data = Vec::new();
+    for &i in &selected {
+        let entry = &entries[i];
+        reader.seek(SeekFrom::Start(entry.data_offset as u64))?;
+        data.resize(entry.compressed_size as usize, 0);
+        reader.read_exact(&mut data)?;
+        if header.has_tree_hash() {
+            leaves.push(leaf_hash(&data));
+        }
+        writer.write_all(&data)?;
+    }
+
+    if header.has_tree_hash() {
+        write_tree_hash(&mut writer, &TreeHash::from_leaves(leaves))?;
+    }
+    if header.has_palette_bloom() {
+        let blooms: Vec<_> = selected.iter().map(|&i| footer.palette_blooms[i]).collect();
+        write_palette_blooms(&mut writer, &blooms)?;
+    }
+    if let Some(origin) = footer.origin {
+        for coord in [origin.x, origin.y, origin.z] {
+            writer.write_i32::<LittleEndian>(coord)?;
+        }
+    }
+    if header.has_framed_footer() {
+        let extension = footer.extension.unwrap_or_default();
+        let extension_len =
+            u32::try_from(extension.len()).map_err(|_| McStreamError::FileTooLarge)?;
+        writer.write_u32::<LittleEndian>(0)?;
+        writer.write_u32::<LittleEndian>(extension_len)?;
+        writer.write_all(&extension)?;
+    }
+    for event in journal {
+        write_event(&mut writer, event)?;
+    }
+    writer.flush()?;
+
+    let chunk_count = index
+        .iter()
+        .map(|e| (e.chunk_x, e.chunk_z))
+        .collect::<HashSet<_>>()
+        .len();
+    log::debug!(
+        "从 {} 复制 {} 个区块（{} 个索引条目），共 {} 字节",
+        src.display(),
+        chunk_count,
+        index.len(),
+        writer.bytes_written
+    );
+
+    Ok(WriteSummary {
+        bytes_written: writer.bytes_written,
+        index_table_offset: HEADER_SIZE as u32,
+        chunk_count,
+        data_hash: writer.hasher.finalize().into(),
+    })
+}
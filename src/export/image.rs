@@ -0,0 +1,64 @@
+// 导出俯视缩略图：每一列取最高的着色方块，按方块ID的颜色绘制一个像素
+// 颜色由调用方提供，适合建筑图库的预览图，不做光照与纹理
+
+use crate::{error::McStreamError, unpacker::McsDecoder};
+use image::{Rgb, RgbImage};
+use std::collections::HashMap;
+
+/// 俯视图的最大像素数，防止分散的建筑分配过大的图像
+pub const MAX_TOPDOWN_PIXELS: u64 = 64 * 1024 * 1024;
+
+/// 将建筑从上往下投影为图像，每个像素为该列最高方块的颜色
+///
+/// 图像覆盖所有方块的水平包围盒，左上角为最小的 `(x, z)`，向右为 +X（东）、向下为 +Z（南）。
+/// 颜色先按完整的方块ID查找，找不到时按去掉方块状态（`[...]`）后的名称查找；
+/// 两者都没有的方块视为透明，显示其下方的方块，整列都没有颜色时为黑色。
+/// 没有方块时返回空图像，包围盒超过 [`MAX_TOPDOWN_PIXELS`] 时返回错误。
+pub fn render_topdown(
+    decoder: &McsDecoder,
+    block_colors: &HashMap<String, [u8; 3]>,
+) -> Result<RgbImage, McStreamError> {
+    let Some((min, max)) = decoder.occupancy().bounds else {
+        return Ok(RgbImage::new(0, 0));
+    };
+    let width = (max.x as i64 - min.x as i64 + 1) as u64;
+    let height = (max.z as i64 - min.z as i64 + 1) as u64;
+    if width.saturating_mul(height) > MAX_TOPDOWN_PIXELS {
+        return Err(McStreamError::ValidationError(format!(
+            "俯视图过大，最多允许 {} 个像素",
+            MAX_TOPDOWN_PIXELS
+        )));
+    }
+
+    // 每列当前最高的着色方块的Y坐标，同一高度以后出现的方块为准
+    let mut tops = vec![i32::MIN; (width * height) as usize];
+    let mut image = RgbImage::new(width as u32, height as u32);
+    for chunk in decoder.get_chunks().values() {
+        let colors: Vec<Option<[u8; 3]>> = chunk
+            .palette
+            .iter()
+            .map(|block_id| block_color(block_colors, block_id))
+            .collect();
+        for block in &chunk.blocks {
+            let Some(color) = colors.get(block.palette_index as usize).copied().flatten() else {
+                continue;
+            };
+            let pos = block.pos.to_global(chunk.pos);
+            let (px, pz) = ((pos.x - min.x) as u32, (pos.z - min.z) as u32);
+            let top = &mut tops[pz as usize * width as usize + px as usize];
+            if pos.y >= *top {
+                *top = pos.y;
+                image.put_pixel(px, pz, Rgb(color));
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+fn block_color(block_colors: &HashMap<String, [u8; 3]>, block_id: &str) -> Option<[u8; 3]> {
+    block_colors.get(block_id).copied().or_else(|| {
+        let name = block_id.split('[').next().unwrap_or_default();
+        block_colors.get(name).copied()
+    })
+}
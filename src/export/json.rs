@@ -0,0 +1,290 @@
+use crate::{
+    error::McStreamError,
+    packer::McsEncoder,
+    types::{Block, BlockPos, ChunkData, McsData},
+    CompressionType, MCS_MIN_VERSION, MCS_VERSION,
+};
+use serde::ser::{Error as _, SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+use std::io::Write;
+
+/// 扁平JSON格式的标识
+pub const FLAT_JSON_FORMAT: &str = "mcs";
+
+/// 当前格式版本对应的JSON版本字符串
+pub fn flat_json_version() -> String {
+    format!("{}.{}", MCS_VERSION >> 8, MCS_VERSION & 0xFF)
+}
+
+/// 扁平JSON中方块的输出顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlockOrder {
+    /// 不排序，区块之间的顺序不固定
+    #[default]
+    None,
+    /// 按全局坐标 `(y, z, x)` 排序
+    Coords,
+    /// 区块按 `(x, z)` 顺序，区块内保持方块的添加顺序
+    Original,
+}
+
+/// 将MCS数据转换为扁平JSON（与 `unpack` 命令输出一致）
+pub fn to_flat_json(data: &McsData) -> Result<Value, McStreamError> {
+    to_flat_json_ordered(data, BlockOrder::None)
+}
+
+/// 按指定顺序将MCS数据转换为扁平JSON，固定的顺序便于对输出做文本比较
+pub fn to_flat_json_ordered(data: &McsData, order: BlockOrder) -> Result<Value, McStreamError> {
+    let blocks = ordered_blocks(data, order)
+        .into_iter()
+        .map(|(chunk, block)| block_to_json(chunk, block))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(json!({
+        "format": FLAT_JSON_FORMAT,
+        "version": flat_json_version(),
+        "blocks": blocks
+    }))
+}
+
+/// 将扁平JSON逐个方块写入 `writer`，输出与 `to_flat_json_ordered` 的格式化结果一致
+///
+/// 不构建完整的JSON树，内存占用与方块数量基本无关，适用于超大建筑。
+pub fn write_flat_json<W: Write>(
+    data: &McsData,
+    order: BlockOrder,
+    writer: W,
+) -> Result<(), McStreamError> {
+    let document = FlatJsonDocument {
+        blocks: ordered_blocks(data, order),
+    };
+    let mut serializer = serde_json::Serializer::pretty(writer);
+    document
+        .serialize(&mut serializer)
+        .map_err(|e| McStreamError::ValidationError(format!("JSON写入错误: {}", e)))
+}
+
+// 流式写出的扁平JSON文档，字段顺序与 `Value` 的键排序一致
+struct FlatJsonDocument<'a> {
+    blocks: Vec<(&'a ChunkData, &'a Block)>,
+}
+
+impl Serialize for FlatJsonDocument<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("blocks", &FlatJsonBlocks(&self.blocks))?;
+        map.serialize_entry("format", FLAT_JSON_FORMAT)?;
+        map.serialize_entry("version", &flat_json_version())?;
+        map.end()
+    }
+}
+
+struct FlatJsonBlocks<'a, 'b>(&'b [(&'a ChunkData, &'a Block)]);
+
+impl Serialize for FlatJsonBlocks<'_, '_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for (chunk, block) in self.0 {
+            let block_obj = block_to_json(chunk, block).map_err(S::Error::custom)?;
+            seq.serialize_element(&block_obj)?;
+        }
+        seq.end()
+    }
+}
+
+// 按输出顺序排列所有方块
+fn ordered_blocks(data: &McsData, order: BlockOrder) -> Vec<(&ChunkData, &Block)> {
+    let mut chunks: Vec<_> = data.chunks.values().collect();
+    if order != BlockOrder::None {
+        chunks.sort_unstable_by_key(|chunk| (chunk.pos.x, chunk.pos.z));
+    }
+
+    let mut ordered: Vec<_> = chunks
+        .into_iter()
+        .flat_map(|chunk| chunk.blocks.iter().map(move |block| (chunk, block)))
+        .collect();
+    if order == BlockOrder::Coords {
+        ordered.sort_by_key(|(chunk, block)| {
+            let pos = block.pos.to_global(chunk.pos);
+            (pos.y, pos.z, pos.x)
+        });
+    }
+    ordered
+}
+
+// 单个方块的JSON对象
+fn block_to_json(chunk: &ChunkData, block: &Block) -> Result<Value, McStreamError> {
+    // 获取方块ID
+    let block_id = chunk
+        .palette
+        .get(block.palette_index)
+        .ok_or_else(|| McStreamError::ValidationError("无效的调色板索引".to_string()))?;
+
+    // 计算全局坐标
+    let pos = block.pos.to_global(chunk.pos);
+
+    // 转换NBT数据：JSON格式的NBT直接嵌入，其余（如二进制NBT）以十六进制保存
+    let nbt_data = block.nbt_decompressed()?;
+    let (nbt, nbt_binary) = match &nbt_data {
+        Some(nbt_data) => match serde_json::from_slice::<Value>(nbt_data) {
+            Ok(nbt) => (nbt, None),
+            Err(_) => (Value::Null, Some(to_hex(nbt_data))),
+        },
+        None => (Value::Null, None),
+    };
+
+    let mut block_obj = json!({
+        "id": block_id,
+        "pos": [pos.x, pos.y, pos.z],
+        "nbt": nbt
+    });
+
+    if let Some(nbt_binary) = nbt_binary {
+        block_obj["nbt_binary"] = json!(nbt_binary);
+    }
+
+    // 覆盖层只在存在时输出
+    if let Some(overlay) = block.overlay {
+        let overlay_id = chunk
+            .palette
+            .get(overlay)
+            .ok_or_else(|| McStreamError::ValidationError("无效的覆盖层调色板索引".to_string()))?;
+        block_obj["overlay"] = json!(overlay_id);
+    }
+
+    // 编辑器标签只在非零时输出
+    if block.tags != 0 {
+        block_obj["tags"] = json!(block.tags);
+    }
+
+    Ok(block_obj)
+}
+
+/// 从扁平JSON构建MCS数据（接受 `unpack` 命令的输出）
+///
+/// `format`/`version` 字段可省略；若存在则必须是当前或仍可读取的旧版本。
+/// `nbt` 为 `null` 或缺失时视为无NBT数据；`nbt_binary` 存在时优先使用其中的原始字节。
+pub fn from_flat_json(
+    json: &Value,
+    compression: CompressionType,
+) -> Result<McsData, McStreamError> {
+    if let Some(format) = json.get("format") {
+        if format.as_str() != Some(FLAT_JSON_FORMAT) {
+            return Err(McStreamError::ValidationError(format!(
+                "不支持的JSON格式: {}",
+                format
+            )));
+        }
+    }
+
+    if let Some(version) = json.get("version") {
+        let supported = version
+            .as_str()
+            .and_then(parse_version)
+            .is_some_and(|v| (MCS_MIN_VERSION..=MCS_VERSION).contains(&v));
+        if !supported {
+            return Err(McStreamError::ValidationError(format!(
+                "不支持的JSON版本: {}",
+                version
+            )));
+        }
+    }
+
+    let mut encoder = McsEncoder::new(compression);
+
+    // 处理方块数据
+    if let Some(blocks) = json.get("blocks").and_then(|b| b.as_array()) {
+        for block in blocks {
+            let block_id = block
+                .get("id")
+                .and_then(|id| id.as_str())
+                .ok_or_else(|| McStreamError::ValidationError("方块缺少id字段".to_string()))?
+                .to_string();
+
+            let pos = block
+                .get("pos")
+                .and_then(|p| p.as_array())
+                .ok_or_else(|| McStreamError::ValidationError("方块缺少pos字段".to_string()))?;
+
+            if pos.len() != 3 {
+                return Err(McStreamError::ValidationError(
+                    "方块坐标格式错误".to_string(),
+                ));
+            }
+
+            let mut coords = [0i32; 3];
+            for (coord, value) in coords.iter_mut().zip(pos) {
+                *coord = value
+                    .as_i64()
+                    .and_then(|v| i32::try_from(v).ok())
+                    .ok_or_else(|| {
+                        McStreamError::ValidationError("方块坐标格式错误".to_string())
+                    })?;
+            }
+
+            // 处理NBT数据
+            let nbt = match (block.get("nbt"), block.get("nbt_binary")) {
+                (_, Some(Value::String(hex))) => Some(from_hex(hex)?),
+                (None | Some(Value::Null), _) => None,
+                (Some(n), _) => Some(serde_json::to_vec(n).map_err(|e| {
+                    McStreamError::ValidationError(format!("无法序列化NBT: {}", e))
+                })?),
+            };
+
+            let overlay_id = match block.get("overlay") {
+                None | Some(Value::Null) => None,
+                Some(overlay) => Some(
+                    overlay
+                        .as_str()
+                        .ok_or_else(|| {
+                            McStreamError::ValidationError("方块overlay字段格式错误".to_string())
+                        })?
+                        .to_string(),
+                ),
+            };
+
+            let tags = match block.get("tags") {
+                None | Some(Value::Null) => 0,
+                Some(tags) => tags
+                    .as_u64()
+                    .and_then(|v| u16::try_from(v).ok())
+                    .ok_or_else(|| {
+                        McStreamError::ValidationError("方块tags字段格式错误".to_string())
+                    })?,
+            };
+
+            let pos = BlockPos::new(coords[0], coords[1], coords[2]);
+            encoder.insert_block(block_id, overlay_id, pos, nbt, tags)?;
+        }
+    }
+
+    Ok(encoder.to_mcs_data())
+}
+
+fn parse_version(version: &str) -> Option<u16> {
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse::<u8>().ok()? as u16) << 8 | minor.parse::<u8>().ok()? as u16)
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, McStreamError> {
+    let invalid =
+        || McStreamError::ValidationError("nbt_binary不是有效的十六进制字符串".to_string());
+
+    if !hex.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
@@ -0,0 +1,6 @@
+// 与其他数据格式之间的转换
+
+#[cfg(feature = "image")]
+pub mod image;
+pub mod json;
+pub mod obj;
@@ -0,0 +1,116 @@
+// 导出为Wavefront OBJ网格，每个方块为一个单位立方体，只输出外露的面
+// 材质由调用方按方块ID提供，不依赖Minecraft的纹理
+
+use crate::{
+    error::McStreamError,
+    types::{BlockPos, FaceMask},
+    unpacker::McsDecoder,
+};
+use std::collections::HashMap;
+use std::io::Write;
+
+/// 网格材质，`name` 对应OBJ中的 `usemtl` 与MTL中的 `newmtl`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub color: [f32; 3], // 漫反射颜色（RGB，0～1）
+}
+
+impl Material {
+    pub fn new(name: impl Into<String>, color: [f32; 3]) -> Self {
+        Self {
+            name: name.into(),
+            color,
+        }
+    }
+}
+
+// 各面四个角相对方块最小角的偏移，从外侧看为逆时针，顺序与 `FaceMask::DIRECTIONS` 一致
+const FACE_CORNERS: [[(i32, i32, i32); 4]; 6] = [
+    [(0, 0, 0), (1, 0, 0), (1, 0, 1), (0, 0, 1)], // 下
+    [(0, 1, 0), (0, 1, 1), (1, 1, 1), (1, 1, 0)], // 上
+    [(0, 0, 0), (0, 1, 0), (1, 1, 0), (1, 0, 0)], // 北
+    [(0, 0, 1), (1, 0, 1), (1, 1, 1), (0, 1, 1)], // 南
+    [(0, 0, 0), (0, 0, 1), (0, 1, 1), (0, 1, 0)], // 西
+    [(1, 0, 0), (1, 1, 0), (1, 1, 1), (1, 0, 1)], // 东
+];
+
+/// 将建筑导出为OBJ网格，返回用到的材质（按首次出现的顺序），可交给 `write_mtl` 写出材质库
+///
+/// `materials` 为方块ID选择材质，返回 `None` 的方块不输出（但仍会遮挡相邻方块的面）；
+/// 按材质名分组，同名材质以第一次返回的颜色为准。相邻方块之间的面被剔除，顶点在方块之间共享。
+pub fn to_obj<W, F>(
+    decoder: &McsDecoder,
+    materials: F,
+    mut writer: W,
+) -> Result<Vec<Material>, McStreamError>
+where
+    W: Write,
+    F: Fn(&str) -> Option<Material>,
+{
+    // 固定遍历顺序，使相同的输入得到相同的输出
+    let mut blocks: Vec<_> = decoder.exposed_blocks().collect();
+    blocks.sort_unstable_by_key(|(pos, _, _)| (pos.y, pos.z, pos.x));
+
+    let mut used: Vec<Material> = Vec::new();
+    let mut groups: Vec<Vec<(usize, [usize; 4])>> = Vec::new();
+    let mut group_of: HashMap<String, usize> = HashMap::new();
+    let mut vertices: Vec<BlockPos> = Vec::new();
+    let mut vertex_of: HashMap<BlockPos, usize> = HashMap::new();
+
+    for (pos, block_id, faces) in blocks {
+        let Some(material) = materials(block_id) else {
+            continue;
+        };
+        let group = *group_of.entry(material.name.clone()).or_insert_with(|| {
+            used.push(material);
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+
+        let directions = FaceMask::DIRECTIONS.iter().zip(FACE_CORNERS);
+        for (normal, ((face, _), corners)) in directions.enumerate() {
+            if !faces.contains(*face) {
+                continue;
+            }
+            let indices = corners.map(|(dx, dy, dz)| {
+                let corner = BlockPos::new(pos.x + dx, pos.y + dy, pos.z + dz);
+                *vertex_of.entry(corner).or_insert_with(|| {
+                    vertices.push(corner);
+                    vertices.len()
+                })
+            });
+            groups[group].push((normal + 1, indices));
+        }
+    }
+
+    writeln!(writer, "# MCStream OBJ导出")?;
+    for vertex in &vertices {
+        writeln!(writer, "v {} {} {}", vertex.x, vertex.y, vertex.z)?;
+    }
+    // 法线即各面指向的相邻方块偏移
+    for (_, (x, y, z)) in FaceMask::DIRECTIONS {
+        writeln!(writer, "vn {} {} {}", x, y, z)?;
+    }
+    for (material, faces) in used.iter().zip(&groups) {
+        writeln!(writer, "usemtl {}", material.name)?;
+        for (normal, [a, b, c, d]) in faces {
+            writeln!(writer, "f {a}//{n} {b}//{n} {c}//{n} {d}//{n}", n = normal)?;
+        }
+    }
+    writer.flush()?;
+
+    Ok(used)
+}
+
+/// 写出与 `to_obj` 配套的MTL材质库
+pub fn write_mtl<W: Write>(materials: &[Material], mut writer: W) -> Result<(), McStreamError> {
+    for material in materials {
+        let [r, g, b] = material.color;
+        writeln!(writer, "newmtl {}", material.name)?;
+        writeln!(writer, "Kd {} {} {}", r, g, b)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
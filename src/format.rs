@@ -0,0 +1,230 @@
+// 磁盘格式的字段布局描述
+// 头部的魔数之后的版本号为大端，其余所有多字节字段均为小端，
+// 修改读写代码时请以此处的描述为准
+
+/// 字节序
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    /// 单字节或字节数组，与字节序无关
+    None,
+    Big,
+    Little,
+}
+
+/// 单个字段的布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub name: &'static str,
+    pub offset: usize, // 相对于所在结构起始位置的偏移
+    pub size: usize,
+    pub endian: Endian,
+}
+
+const fn field(name: &'static str, offset: usize, size: usize, endian: Endian) -> FieldLayout {
+    FieldLayout {
+        name,
+        offset,
+        size,
+        endian,
+    }
+}
+
+/// 文件头部布局
+pub const LAYOUT: &[FieldLayout] = &[
+    field("Magic", 0x00, 8, Endian::None),
+    field("Version", 0x08, 2, Endian::Big),
+    field("Compression", 0x0A, 1, Endian::None),
+    field("Flags", 0x0B, 1, Endian::None),
+    field("IndexTableOffset", 0x0C, 4, Endian::Little),
+    field("DictionaryId", 0x10, 4, Endian::Little),
+];
+
+/// 区块索引表条目布局（紧跟在 uint32 小端的条目数之后）
+pub const INDEX_ENTRY_LAYOUT: &[FieldLayout] = &[
+    field("ChunkX", 0x00, 4, Endian::Little),
+    field("ChunkZ", 0x04, 4, Endian::Little),
+    field("DataOffset", 0x08, 4, Endian::Little),
+    field("CompressedSize", 0x0C, 4, Endian::Little),
+    field("BlockCount", 0x10, 4, Endian::Little),
+];
+
+/// 1.0版本的区块索引表条目布局（不含方块数）
+pub const INDEX_ENTRY_LAYOUT_V1_0: &[FieldLayout] = &[
+    field("ChunkX", 0x00, 4, Endian::Little),
+    field("ChunkZ", 0x04, 4, Endian::Little),
+    field("DataOffset", 0x08, 4, Endian::Little),
+    field("CompressedSize", 0x0C, 4, Endian::Little),
+];
+
+/// 解压后区块数据中单个方块记录的布局（不含可选的覆盖层字段）
+pub const BLOCK_RECORD_LAYOUT: &[FieldLayout] = &[
+    field("PaletteIndex", 0x00, 2, Endian::Little),
+    field("X", 0x02, 1, Endian::None),
+    field("Y", 0x03, 2, Endian::Little),
+    field("Z", 0x05, 1, Endian::None),
+    field("Flags", 0x06, 1, Endian::None),
+];
+
+/// 计算布局的总字节数，要求字段按顺序紧密排列
+pub const fn layout_size(layout: &[FieldLayout]) -> usize {
+    let mut offset = 0;
+    let mut i = 0;
+    while i < layout.len() {
+        assert!(layout[i].offset == offset, "字段布局不连续");
+        offset += layout[i].size;
+        i += 1;
+    }
+    offset
+}
+
+/// 按名称查找字段
+pub fn find_field(layout: &[FieldLayout], name: &str) -> Option<FieldLayout> {
+    layout.iter().copied().find(|f| f.name == name)
+}
+
+/// 编译期按名称取字段偏移，字段不存在时编译失败
+pub const fn field_offset(layout: &[FieldLayout], name: &str) -> usize {
+    let mut i = 0;
+    while i < layout.len() {
+        if str_eq(layout[i].name, name) {
+            return layout[i].offset;
+        }
+        i += 1;
+    }
+    panic!("字段不存在");
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// 文件头部字节数
+pub const HEADER_SIZE: usize = layout_size(LAYOUT);
+
+/// 头部中标志位字段的位置
+pub const FLAGS_FIELD_POS: usize = field_offset(LAYOUT, "Flags");
+
+/// 头部中索引表偏移字段的位置
+pub const INDEX_OFFSET_FIELD_POS: usize = field_offset(LAYOUT, "IndexTableOffset");
+
+/// 头部中压缩字典ID字段的位置
+pub const DICTIONARY_ID_FIELD_POS: usize = field_offset(LAYOUT, "DictionaryId");
+
+/// 索引表开头条目数字段的字节数
+pub const INDEX_COUNT_SIZE: usize = 4;
+
+/// 当前版本单个索引条目的字节数
+pub const INDEX_ENTRY_SIZE: usize = layout_size(INDEX_ENTRY_LAYOUT);
+
+/// 单个方块记录的字节数（不含可选的覆盖层字段）
+pub const BLOCK_RECORD_SIZE: usize = layout_size(BLOCK_RECORD_LAYOUT);
+
+/// 指定版本下单个索引条目的字节数
+pub const fn index_entry_size(version: u16) -> usize {
+    if version >= 0x0101 {
+        INDEX_ENTRY_SIZE
+    } else {
+        layout_size(INDEX_ENTRY_LAYOUT_V1_0)
+    }
+}
+
+// 布局描述与读写代码的字段宽度在编译期保持一致
+const _: () = assert!(HEADER_SIZE == 20);
+const _: () = assert!(FLAGS_FIELD_POS == 0x0B);
+const _: () = assert!(INDEX_OFFSET_FIELD_POS == 0x0C);
+const _: () = assert!(DICTIONARY_ID_FIELD_POS == 0x10);
+const _: () = assert!(INDEX_ENTRY_SIZE == 20);
+const _: () = assert!(index_entry_size(0x0100) == 16);
+const _: () = assert!(BLOCK_RECORD_SIZE == 7);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{packer::McsEncoder, CompressionType};
+
+    // 一个方块 (17, 70, -3) 不压缩写出的完整文件
+    const GOLDEN_HEADER: [u8; HEADER_SIZE] = [
+        b'M', b'C', b'S', b'T', b'R', b'M', 0, 0, // 魔数
+        0x01, 0x01, // 版本，大端
+        0x00, // 压缩算法
+        0x00, // 标志位
+        0x14, 0x00, 0x00, 0x00, // 索引表偏移，小端
+        0x00, 0x00, 0x00, 0x00, // 压缩字典ID
+    ];
+    const GOLDEN_INDEX: [u8; INDEX_COUNT_SIZE + INDEX_ENTRY_SIZE] = [
+        0x01, 0x00, 0x00, 0x00, // 条目数
+        0x01, 0x00, 0x00, 0x00, // ChunkX = 1
+        0xFF, 0xFF, 0xFF, 0xFF, // ChunkZ = -1
+        0x2C, 0x00, 0x00, 0x00, // DataOffset = 44
+        0x22, 0x00, 0x00, 0x00, // CompressedSize = 34
+        0x01, 0x00, 0x00, 0x00, // BlockCount = 1
+    ];
+    const GOLDEN_CHUNK: [u8; 34] = [
+        0x01, 0x00, // 调色板条目数
+        0x0F, 0x00, b'm', b'i', b'n', b'e', b'c', b'r', b'a', b'f', b't', b':', b's', b't', b'o',
+        b'n', b'e', // 方块ID
+        0x01, 0x00, 0x00, 0x00, // 方块数
+        0x00, 0x00, // PaletteIndex
+        0x01, // X
+        0x86, 0x00, // Y = 70 + 64
+        0x0D, // Z
+        0x00, // Flags
+        0x00, 0x00, 0x00, 0x00, // 带NBT的方块数
+    ];
+
+    fn field_bytes<'a>(bytes: &'a [u8], layout: &[FieldLayout], name: &str) -> &'a [u8] {
+        let field = find_field(layout, name).unwrap();
+        &bytes[field.offset..field.offset + field.size]
+    }
+
+    #[test]
+    fn golden_bytes() {
+        let mut encoder = McsEncoder::new(CompressionType::None);
+        encoder
+            .add_block("minecraft:stone".to_string(), 17, 70, -3, None)
+            .unwrap();
+        let mut bytes = Vec::new();
+        encoder.write_to_writer(&mut bytes).unwrap();
+
+        let (header, rest) = bytes.split_at(HEADER_SIZE);
+        let (index, chunk) = rest.split_at(GOLDEN_INDEX.len());
+        assert_eq!(header, GOLDEN_HEADER);
+        assert_eq!(index, GOLDEN_INDEX);
+        assert_eq!(chunk, GOLDEN_CHUNK);
+
+        // 字节序与布局描述一致
+        assert_eq!(
+            field_bytes(header, LAYOUT, "Version"),
+            0x0101u16.to_be_bytes()
+        );
+        assert_eq!(
+            field_bytes(header, LAYOUT, "IndexTableOffset"),
+            (HEADER_SIZE as u32).to_le_bytes()
+        );
+        let entry = &index[INDEX_COUNT_SIZE..];
+        assert_eq!(
+            field_bytes(entry, INDEX_ENTRY_LAYOUT, "ChunkZ"),
+            (-1i32).to_le_bytes()
+        );
+        assert_eq!(
+            field_bytes(entry, INDEX_ENTRY_LAYOUT, "DataOffset"),
+            ((HEADER_SIZE + GOLDEN_INDEX.len()) as u32).to_le_bytes()
+        );
+        let record = &chunk[chunk.len() - 4 - BLOCK_RECORD_SIZE..chunk.len() - 4];
+        assert_eq!(
+            field_bytes(record, BLOCK_RECORD_LAYOUT, "Y"),
+            134u16.to_le_bytes()
+        );
+    }
+}
@@ -1,25 +1,192 @@
-use crate::{error::McStreamError, types::McsHeader, CompressionType, MCS_MAGIC, MCS_VERSION};
+use crate::{
+    compression::compression_type_from_u8,
+    error::McStreamError,
+    format::{DICTIONARY_ID_FIELD_POS, FLAGS_FIELD_POS, INDEX_OFFSET_FIELD_POS},
+    types::McsHeader,
+    CompressionType, MCS_MAGIC, MCS_MIN_VERSION, MCS_VERSION,
+};
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::io::{Read, Seek, SeekFrom, Write};
 
-/// 写入MCS文件头部
+/// 头部标志位：文件尾部含数字签名
+pub const FLAG_SIGNATURE: u8 = 0x01;
+
+/// 头部标志位：区块数据之后含追加式编辑日志
+pub const FLAG_JOURNAL: u8 = 0x02;
+
+/// 头部标志位：区块数据之后含树形哈希（Merkle树）段
+pub const FLAG_TREE_HASH: u8 = 0x04;
+
+/// 头部标志位：每个区块解压后的数据以1字节区块格式开头
+pub const FLAG_CHUNK_FORMAT: u8 = 0x08;
+
+/// 头部标志位：尾部含每个区块调色板的布隆过滤器
+pub const FLAG_PALETTE_BLOOM: u8 = 0x10;
+
+/// 头部标志位：尾部记录了采集建筑时的世界坐标原点
+pub const FLAG_ORIGIN: u8 = 0x20;
+
+/// 头部标志位：区块数据使用Zstandard压缩字典，字典ID见头部 `DictionaryId` 字段
+pub const FLAG_DICTIONARY: u8 = 0x40;
+
+/// 头部标志位：签名与扩展数据带长度前缀；未签名的文件尾部之后的未知数据可被安全忽略，带签名时不允许有多余数据
+pub const FLAG_FRAMED_FOOTER: u8 = 0x80;
+
+/// 头部标志位集合
+///
+/// 未知的位会原样保留，旧版本读取新版本写入的文件时不会丢失这些位。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct McsFlags(u8);
+
+impl McsFlags {
+    pub const SIGNATURE: Self = Self(FLAG_SIGNATURE);
+    pub const JOURNAL: Self = Self(FLAG_JOURNAL);
+    pub const TREE_HASH: Self = Self(FLAG_TREE_HASH);
+    pub const CHUNK_FORMAT: Self = Self(FLAG_CHUNK_FORMAT);
+    pub const PALETTE_BLOOM: Self = Self(FLAG_PALETTE_BLOOM);
+    pub const ORIGIN: Self = Self(FLAG_ORIGIN);
+    pub const DICTIONARY: Self = Self(FLAG_DICTIONARY);
+    pub const FRAMED_FOOTER: Self = Self(FLAG_FRAMED_FOOTER);
+
+    /// 当前版本已定义的全部标志位
+    pub const KNOWN: Self = Self(
+        FLAG_SIGNATURE
+            | FLAG_JOURNAL
+            | FLAG_TREE_HASH
+            | FLAG_CHUNK_FORMAT
+            | FLAG_PALETTE_BLOOM
+            | FLAG_ORIGIN
+            | FLAG_DICTIONARY
+            | FLAG_FRAMED_FOOTER,
+    );
+
+    /// 空集合
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// 由原始字节构建，保留所有位（包括未知位）
+    pub const fn from_bits_retain(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    /// 原始字节
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// 是否包含 `other` 中的全部位
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// 设置 `other` 中的位
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// 清除 `other` 中的位
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+
+    /// 当前版本未定义的位
+    pub const fn unknown(self) -> Self {
+        Self(self.0 & !Self::KNOWN.0)
+    }
+}
+
+impl std::ops::BitOr for McsFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for McsFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl McsHeader {
+    /// 头部标志位集合
+    pub fn flags(&self) -> McsFlags {
+        McsFlags::from_bits_retain(self.flags)
+    }
+
+    /// 文件尾部含数字签名
+    pub fn has_signature(&self) -> bool {
+        self.flags().contains(McsFlags::SIGNATURE)
+    }
+
+    /// 文件含追加式编辑日志
+    pub fn has_journal(&self) -> bool {
+        self.flags().contains(McsFlags::JOURNAL)
+    }
+
+    /// 文件含树形哈希段
+    pub fn has_tree_hash(&self) -> bool {
+        self.flags().contains(McsFlags::TREE_HASH)
+    }
+
+    /// 区块数据以区块格式字节开头
+    pub fn has_chunk_format(&self) -> bool {
+        self.flags().contains(McsFlags::CHUNK_FORMAT)
+    }
+
+    /// 文件含调色板布隆过滤器段
+    pub fn has_palette_bloom(&self) -> bool {
+        self.flags().contains(McsFlags::PALETTE_BLOOM)
+    }
+
+    /// 文件记录了采集原点
+    pub fn has_origin(&self) -> bool {
+        self.flags().contains(McsFlags::ORIGIN)
+    }
+
+    /// 区块数据使用压缩字典
+    pub fn has_dictionary(&self) -> bool {
+        self.flags().contains(McsFlags::DICTIONARY)
+    }
+
+    /// 尾部的签名与扩展数据带长度前缀
+    pub fn has_framed_footer(&self) -> bool {
+        self.flags().contains(McsFlags::FRAMED_FOOTER)
+    }
+}
+
+/// 写入MCS文件头部（当前版本）
 pub fn write_header<W: Write>(
     writer: &mut W,
     compression: CompressionType,
-    has_signature: bool,
+    flags: u8,
 ) -> Result<(), McStreamError> {
-    writer.write_all(MCS_MAGIC)?;
-    writer.write_u16::<BigEndian>(MCS_VERSION)?;
-    writer.write_u8(compression as u8)?;
+    write_header_as(writer, MCS_VERSION, compression, flags)
+}
 
-    let flags = if has_signature { 0x01 } else { 0x00 };
+/// 写入指定版本号的MCS文件头部，各版本的头部布局相同
+pub fn write_header_as<W: Write>(
+    writer: &mut W,
+    version: u16,
+    compression: CompressionType,
+    flags: u8,
+) -> Result<(), McStreamError> {
+    if !(MCS_MIN_VERSION..=MCS_VERSION).contains(&version) {
+        return Err(McStreamError::UnsupportedVersion(version));
+    }
+
+    writer.write_all(MCS_MAGIC)?;
+    writer.write_u16::<BigEndian>(version)?;
+    writer.write_u8(compression.to_u8())?;
     writer.write_u8(flags)?;
 
     // 区块索引表偏移，临时写入0
     writer.write_u32::<LittleEndian>(0)?;
 
-    // 预留字段
-    writer.write_all(&[0; 4])?;
+    // 压缩字典ID，未使用字典时为0
+    writer.write_u32::<LittleEndian>(0)?;
 
     Ok(())
 }
@@ -34,35 +201,52 @@ pub fn read_header<R: Read>(reader: &mut R) -> Result<McsHeader, McStreamError>
     }
 
     let version = reader.read_u16::<BigEndian>()?;
-    if version != MCS_VERSION {
+    if !(MCS_MIN_VERSION..=MCS_VERSION).contains(&version) {
         return Err(McStreamError::UnsupportedVersion(version));
     }
 
     let compression = reader.read_u8()?;
-    if compression > 3 {
-        return Err(McStreamError::UnsupportedCompression(compression));
-    }
+    compression_type_from_u8(compression)?;
 
     let flags = reader.read_u8()?;
     let index_table_offset = reader.read_u32::<LittleEndian>()?;
 
-    let mut reserved = [0u8; 4];
-    reader.read_exact(&mut reserved)?;
+    let dictionary_id = reader.read_u32::<LittleEndian>()?;
 
     Ok(McsHeader {
         version,
         compression,
         flags,
         index_table_offset,
+        dictionary_id,
     })
 }
 
+/// 更新头部中的压缩字典ID
+pub fn update_dictionary_id<W: Write + Seek>(
+    writer: &mut W,
+    dictionary_id: u32,
+) -> Result<(), McStreamError> {
+    writer.seek(SeekFrom::Start(DICTIONARY_ID_FIELD_POS as u64))?;
+    writer.write_u32::<LittleEndian>(dictionary_id)?;
+    Ok(())
+}
+
+/// 更新头部标志位
+///
+/// 写入的是完整字节，调用方应在读取到的标志位上修改，以保留未知位。
+pub fn update_flags<W: Write + Seek>(writer: &mut W, flags: u8) -> Result<(), McStreamError> {
+    writer.seek(SeekFrom::Start(FLAGS_FIELD_POS as u64))?;
+    writer.write_u8(flags)?;
+    Ok(())
+}
+
 /// 更新区块索引表偏移值
 pub fn update_index_table_offset<W: Write + Seek>(
     writer: &mut W,
     offset: u32,
 ) -> Result<(), McStreamError> {
-    writer.seek(SeekFrom::Start(0x0C))?;
+    writer.seek(SeekFrom::Start(INDEX_OFFSET_FIELD_POS as u64))?;
     writer.write_u32::<LittleEndian>(offset)?;
     Ok(())
 }
@@ -0,0 +1,50 @@
+use crate::{error::McStreamError, types::McsHeader, unpacker::read_validated_index};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// 无需解压区块即可获得的文件概要信息
+#[derive(Debug, Clone)]
+pub struct McsInfo {
+    pub header: McsHeader,
+    pub file_size: u64,
+    pub chunk_count: usize,         // 不同区块坐标的数量
+    pub entry_count: usize,         // 索引条目数，区块被拆分时多于区块数
+    pub total_compressed_size: u64, // 所有区块压缩数据的总字节数
+}
+
+impl McsInfo {
+    /// 仅读取头部与区块索引表获取文件信息
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, McStreamError> {
+        let (header, entries, file_size) = read_validated_index(path)?;
+
+        let total_compressed_size = entries.iter().map(|e| e.compressed_size as u64).sum();
+        let chunk_count = entries
+            .iter()
+            .map(|e| (e.chunk_x, e.chunk_z))
+            .collect::<HashSet<_>>()
+            .len();
+
+        Ok(Self {
+            header,
+            file_size,
+            chunk_count,
+            entry_count: entries.len(),
+            total_compressed_size,
+        })
+    }
+
+    /// 格式版本
+    pub fn version(&self) -> u16 {
+        self.header.version
+    }
+
+    /// 压缩算法类型值
+    pub fn compression(&self) -> u8 {
+        self.header.compression
+    }
+
+    /// 头部标志位
+    pub fn flags(&self) -> u8 {
+        self.header.flags
+    }
+}
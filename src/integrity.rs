@@ -0,0 +1,182 @@
+// 基于区块的树形哈希（Merkle树）
+// 每个区块的压缩数据作为一个叶子并行计算SHA-256，签名只需覆盖根哈希，
+// 校验时可以定位到具体损坏的区块
+
+use crate::{
+    error::McStreamError,
+    types::ChunkPos,
+    unpacker::{footer_offset, read_footer, read_raw_chunk, read_validated_index},
+};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// SHA-256哈希值
+pub type Hash = [u8; 32];
+
+// 叶子与内部节点使用不同的前缀，避免二者被互相伪造
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// 文件中保存的树形哈希
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeHash {
+    pub leaves: Vec<Hash>, // 与区块索引表顺序一致
+    pub root: Hash,
+}
+
+impl TreeHash {
+    /// 由叶子哈希构建
+    pub fn from_leaves(leaves: Vec<Hash>) -> Self {
+        let root = merkle_root(&leaves);
+        Self { leaves, root }
+    }
+}
+
+/// 计算单个叶子的哈希
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+/// 计算一组区块数据的叶子哈希，启用 `parallel` 特性时并行计算
+pub fn chunk_hashes<T: AsRef<[u8]> + Sync>(chunks: &[T]) -> Vec<Hash> {
+    #[cfg(feature = "parallel")]
+    let iter = chunks.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let iter = chunks.iter();
+    iter.map(|c| leaf_hash(c.as_ref())).collect()
+}
+
+/// 计算Merkle根，奇数个节点时最后一个直接提升到上一层
+pub fn merkle_root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return Sha256::digest([]).into();
+    }
+
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    let mut hasher = Sha256::new();
+                    hasher.update([NODE_PREFIX]);
+                    hasher.update(left);
+                    hasher.update(right);
+                    hasher.finalize().into()
+                }
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+
+    level[0]
+}
+
+/// 写入树形哈希段
+pub fn write_tree_hash<W: Write>(writer: &mut W, tree: &TreeHash) -> Result<(), McStreamError> {
+    writer.write_u32::<LittleEndian>(tree.leaves.len() as u32)?;
+    for leaf in &tree.leaves {
+        writer.write_all(leaf)?;
+    }
+    writer.write_all(&tree.root)?;
+    Ok(())
+}
+
+/// 读取树形哈希段
+pub fn read_tree_hash<R: Read>(reader: &mut R) -> Result<TreeHash, McStreamError> {
+    let count = reader.read_u32::<LittleEndian>()?;
+
+    let mut leaves = Vec::with_capacity((count as usize).min(4096));
+    for _ in 0..count {
+        let mut leaf = [0u8; 32];
+        reader.read_exact(&mut leaf)?;
+        leaves.push(leaf);
+    }
+
+    let mut root = [0u8; 32];
+    reader.read_exact(&mut root)?;
+
+    Ok(TreeHash { leaves, root })
+}
+
+/// 树形哈希校验结果
+#[derive(Debug, Clone)]
+pub struct TreeVerification {
+    pub root: Hash,                    // 重新计算得到的根哈希
+    pub root_matches: bool,            // 与文件中保存的根哈希一致
+    pub corrupt_chunks: Vec<ChunkPos>, // 叶子哈希不一致的区块
+}
+
+impl TreeVerification {
+    /// 所有区块与根哈希均一致
+    pub fn is_valid(&self) -> bool {
+        self.root_matches && self.corrupt_chunks.is_empty()
+    }
+}
+
+/// 重新计算文件的树形哈希并与保存的值比较
+pub fn verify_tree_hash<P: AsRef<Path> + Sync>(path: P) -> Result<TreeVerification, McStreamError> {
+    let (header, entries, _) = read_validated_index(&path)?;
+    if !header.has_tree_hash() {
+        return Err(McStreamError::ValidationError(
+            "文件不包含树形哈希".to_string(),
+        ));
+    }
+
+    let footer = read_footer(&path, &header, footer_offset(&header, &entries)?)?;
+    let stored = footer.tree_hash.ok_or(McStreamError::ChunkIndexError)?;
+
+    #[cfg(feature = "parallel")]
+    let iter = entries.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let iter = entries.iter();
+    let leaves: Vec<Hash> = iter
+        .map(|entry| Ok(leaf_hash(&read_raw_chunk(&path, entry)?)))
+        .collect::<Result<_, McStreamError>>()?;
+
+    let corrupt_chunks = entries
+        .iter()
+        .zip(&leaves)
+        .enumerate()
+        .filter(|(i, (_, leaf))| stored.leaves.get(*i) != Some(leaf))
+        .map(|(_, (entry, _))| ChunkPos::new(entry.chunk_x, entry.chunk_z))
+        .collect();
+
+    let root = merkle_root(&leaves);
+
+    Ok(TreeVerification {
+        root,
+        root_matches: root == stored.root,
+        corrupt_chunks,
+    })
+}
+
+/// 校验签名：先校验树形哈希，再由调用方验证签名是否覆盖根哈希
+///
+/// `verifier` 接收根哈希与文件中的签名数据，返回签名是否有效。
+pub fn verify_signature<P, F>(path: P, verifier: F) -> Result<bool, McStreamError>
+where
+    P: AsRef<Path> + Sync,
+    F: FnOnce(&Hash, &[u8]) -> bool,
+{
+    let verification = verify_tree_hash(&path)?;
+    if !verification.is_valid() {
+        return Ok(false);
+    }
+
+    let (header, entries, _) = read_validated_index(&path)?;
+    let footer = read_footer(&path, &header, footer_offset(&header, &entries)?)?;
+
+    match footer.signature {
+        Some(signature) => Ok(verifier(&verification.root, &signature)),
+        None => Ok(false),
+    }
+}
@@ -0,0 +1,297 @@
+// Minecraft存档的Anvil区域文件（.mca）
+// 文件开头为1024个区块位置（3字节扇区偏移 + 1字节扇区数）与时间戳表，
+// 每个区块为单独压缩的大端NBT，方块按16×16×16的段存储为调色板 + 紧凑long数组
+
+use crate::{
+    error::McStreamError,
+    interop::structure::block_state_from_nbt,
+    nbt::{self, NbtValue},
+    packer::McsEncoder,
+    types::BlockPos,
+    CompressionType,
+};
+use byteorder::BigEndian;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// 区域文件的扇区大小
+pub const SECTOR_SIZE: usize = 4096;
+
+// 区块数据的压缩方式
+const CHUNK_GZIP: u8 = 1;
+const CHUNK_ZLIB: u8 = 2;
+const CHUNK_UNCOMPRESSED: u8 = 3;
+
+fn invalid(message: &str) -> McStreamError {
+    McStreamError::ValidationError(format!("区域文件格式错误: {}", message))
+}
+
+// 各种空气方块都不存储
+fn is_air(state: &str) -> bool {
+    matches!(
+        state,
+        "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air"
+    )
+}
+
+// 区块或段坐标对应的方块坐标范围（起点与终点），超出i32范围时返回错误
+fn block_span(coord: i32, what: &str) -> Result<(i32, i32), McStreamError> {
+    coord
+        .checked_mul(16)
+        .and_then(|base| Some((base, base.checked_add(15)?)))
+        .ok_or_else(|| invalid(&format!("{}超出范围", what)))
+}
+
+// 由文件名 `r.<x>.<z>.mca` 得到区域坐标
+fn region_coords(path: &Path) -> Option<(i32, i32)> {
+    let name = path.file_name()?.to_str()?;
+    let mut parts = name.strip_prefix("r.")?.strip_suffix(".mca")?.split('.');
+    let x = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+    parts.next().is_none().then_some((x, z))
+}
+
+/// 从Anvil区域文件中读取指定区域内的方块
+///
+/// 区域包含 `min` 与 `max` 两个端点，坐标为世界坐标。支持1.16及以上版本的区块格式，
+/// 只转换方块与方块实体，实体与生物群系会被忽略。区域的最小角记录为采集原点。
+pub fn read_region<P: AsRef<Path>>(
+    path: P,
+    min: BlockPos,
+    max: BlockPos,
+) -> Result<McsEncoder, McStreamError> {
+    let path = path.as_ref();
+    let data = fs::read(path)?;
+    if data.len() < SECTOR_SIZE * 2 {
+        return Err(invalid("文件缺少区块位置表"));
+    }
+
+    let lo = BlockPos::new(min.x.min(max.x), min.y.min(max.y), min.z.min(max.z));
+    let hi = BlockPos::new(min.x.max(max.x), min.y.max(max.y), min.z.max(max.z));
+    let (lo_chunk, hi_chunk) = (lo.chunk_pos(), hi.chunk_pos());
+    let region = region_coords(path);
+
+    let mut encoder = McsEncoder::new(CompressionType::Zstandard).with_origin(lo);
+
+    for i in 0..1024 {
+        let location = u32::from_be_bytes([
+            data[i * 4],
+            data[i * 4 + 1],
+            data[i * 4 + 2],
+            data[i * 4 + 3],
+        ]);
+        let sector = (location >> 8) as usize;
+        if sector == 0 {
+            continue;
+        }
+
+        // 文件名可用时先按坐标跳过区域外的区块，避免解压；坐标溢出的区块不可能在区域内
+        if let Some((rx, rz)) = region {
+            let chunk_coord = |r: i32, offset: usize| r.checked_mul(32)?.checked_add(offset as i32);
+            let (Some(cx), Some(cz)) = (chunk_coord(rx, i % 32), chunk_coord(rz, i / 32)) else {
+                continue;
+            };
+            if cx < lo_chunk.x || cx > hi_chunk.x || cz < lo_chunk.z || cz > hi_chunk.z {
+                continue;
+            }
+        }
+
+        let root = read_chunk_nbt(&data, sector)?;
+        read_chunk_blocks(&root, lo, hi, &mut encoder)?;
+    }
+
+    Ok(encoder)
+}
+
+fn read_chunk_nbt(data: &[u8], sector: usize) -> Result<NbtValue, McStreamError> {
+    let start = sector * SECTOR_SIZE;
+    let header = data
+        .get(start..start + 5)
+        .ok_or_else(|| invalid("区块位置超出文件范围"))?;
+    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let compression = header[4];
+
+    let payload = length
+        .checked_sub(1)
+        .and_then(|len| data.get(start + 5..start + 5 + len))
+        .ok_or_else(|| invalid("区块数据超出文件范围"))?;
+
+    let mut decompressed = Vec::new();
+    match compression {
+        CHUNK_GZIP => {
+            GzDecoder::new(payload).read_to_end(&mut decompressed)?;
+        }
+        CHUNK_ZLIB => {
+            ZlibDecoder::new(payload).read_to_end(&mut decompressed)?;
+        }
+        CHUNK_UNCOMPRESSED => decompressed.extend_from_slice(payload),
+        _ => {
+            return Err(invalid(&format!(
+                "不支持的区块压缩方式: {}（外部 .mcc 数据与LZ4均不支持）",
+                compression
+            )))
+        }
+    }
+
+    let (_, root) = nbt::read_named::<BigEndian, _>(&mut decompressed.as_slice())?;
+    Ok(root)
+}
+
+fn read_chunk_blocks(
+    root: &NbtValue,
+    lo: BlockPos,
+    hi: BlockPos,
+    encoder: &mut McsEncoder,
+) -> Result<(), McStreamError> {
+    // 1.18起字段位于根标签，之前位于 Level 之下
+    let (level, modern) = match root.get("Level") {
+        Some(level) => (level, false),
+        None => (root, true),
+    };
+
+    let chunk_x = level
+        .get("xPos")
+        .and_then(|v| v.as_i32())
+        .ok_or_else(|| invalid("区块缺少xPos"))?;
+    let chunk_z = level
+        .get("zPos")
+        .and_then(|v| v.as_i32())
+        .ok_or_else(|| invalid("区块缺少zPos"))?;
+    let (base_x, end_x) = block_span(chunk_x, "区块xPos")?;
+    let (base_z, end_z) = block_span(chunk_z, "区块zPos")?;
+    if base_x > hi.x || end_x < lo.x || base_z > hi.z || end_z < lo.z {
+        return Ok(());
+    }
+
+    // 方块实体按坐标索引，作为对应方块的NBT
+    let block_entities_key = if modern {
+        "block_entities"
+    } else {
+        "TileEntities"
+    };
+    let mut block_entities = HashMap::new();
+    for entity in level
+        .get(block_entities_key)
+        .and_then(|v| v.as_list())
+        .unwrap_or(&[])
+    {
+        let pos = ["x", "y", "z"].map(|k| entity.get(k).and_then(|v| v.as_i32()));
+        if let [Some(x), Some(y), Some(z)] = pos {
+            block_entities.insert((x, y, z), entity);
+        }
+    }
+
+    let sections_key = if modern { "sections" } else { "Sections" };
+    let sections = level
+        .get(sections_key)
+        .and_then(|v| v.as_list())
+        .unwrap_or(&[]);
+
+    for section in sections {
+        let section_y = section
+            .get("Y")
+            .and_then(|v| v.as_i32())
+            .ok_or_else(|| invalid("段缺少Y"))?;
+        let (base_y, end_y) = block_span(section_y, "段Y")?;
+        if base_y > hi.y || end_y < lo.y {
+            continue;
+        }
+
+        let (palette, states) = if modern {
+            match section.get("block_states") {
+                Some(block_states) => (block_states.get("palette"), block_states.get("data")),
+                None => continue,
+            }
+        } else {
+            (section.get("Palette"), section.get("BlockStates"))
+        };
+
+        let palette = match palette.and_then(|p| p.as_list()) {
+            Some(palette) if !palette.is_empty() => palette,
+            _ => continue,
+        };
+        let palette: Vec<String> = palette
+            .iter()
+            .map(|entry| block_state_from_nbt(entry).ok_or_else(|| invalid("调色板条目格式错误")))
+            .collect::<Result<_, _>>()?;
+
+        let indices = match states {
+            Some(NbtValue::LongArray(longs)) => unpack_indices(longs, palette.len())?,
+            // 调色板只有一项时省略数据，整段均为该方块
+            None => vec![0; 4096],
+            Some(_) => return Err(invalid("方块数据不是long数组")),
+        };
+
+        for (i, &index) in indices.iter().enumerate() {
+            let state = palette
+                .get(index as usize)
+                .ok_or_else(|| invalid("方块索引超出调色板范围"))?;
+            if is_air(state) {
+                continue;
+            }
+
+            // 段内索引按 y → z → x 排列（x 变化最快）
+            let x = base_x + (i & 0xF) as i32;
+            let z = base_z + ((i >> 4) & 0xF) as i32;
+            let y = base_y + (i >> 8) as i32;
+            if x < lo.x || x > hi.x || y < lo.y || y > hi.y || z < lo.z || z > hi.z {
+                continue;
+            }
+
+            let nbt = match block_entities.get(&(x, y, z)) {
+                Some(entity) => Some(nbt::to_bytes("", entity)?),
+                None => None,
+            };
+
+            encoder.add_block(state.clone(), x, y, z, nbt)?;
+        }
+    }
+
+    Ok(())
+}
+
+// 解出4096个调色板索引；自1.16起单个值不跨越两个long
+fn unpack_indices(longs: &[i64], palette_len: usize) -> Result<Vec<u16>, McStreamError> {
+    // 一段只有4096个方块，原版的段调色板不会更长
+    if palette_len > 4096 {
+        return Err(invalid("段调色板过大"));
+    }
+    let bits = (usize::BITS - (palette_len - 1).leading_zeros()).max(4) as usize;
+    let per_long = 64 / bits;
+    if longs.len() < 4096_usize.div_ceil(per_long) {
+        return Err(invalid("方块数据长度不足"));
+    }
+
+    let mask = (1u64 << bits) - 1;
+    Ok((0..4096)
+        .map(|i| ((longs[i / per_long] as u64 >> ((i % per_long) * bits)) & mask) as u16)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflowing_chunk_position_rejected() {
+        let root = NbtValue::Compound(vec![
+            ("xPos".to_string(), NbtValue::Int(i32::MAX)),
+            ("zPos".to_string(), NbtValue::Int(0)),
+        ]);
+        let mut encoder = McsEncoder::new(CompressionType::None);
+        let (lo, hi) = (BlockPos::new(0, 0, 0), BlockPos::new(15, 15, 15));
+        assert!(read_chunk_blocks(&root, lo, hi, &mut encoder).is_err());
+        assert!(block_span(i32::MAX >> 4, "区块xPos").is_ok());
+        assert!(block_span(i32::MIN >> 4, "区块xPos").is_ok());
+    }
+
+    #[test]
+    fn oversized_section_palette_rejected() {
+        let longs = vec![0i64; 4096];
+        assert!(unpack_indices(&longs, 4096).is_ok());
+        assert!(unpack_indices(&longs, 4097).is_err());
+    }
+}
@@ -0,0 +1,360 @@
+// 基岩版结构方块导出的 .mcstructure 文件（未压缩的小端NBT）
+// 方块以两层调色板索引数组表示：第0层为主方块，第1层为含水等附加方块，对应MCS的覆盖层；
+// 索引按X、Y、Z的顺序排列（Z变化最快），-1 表示该位置没有方块
+
+use crate::{
+    error::McStreamError,
+    interop::structure::{join_block_state, read_int_triple, split_block_state},
+    nbt::{self, NbtValue},
+    packer::McsEncoder,
+    types::{BlockPos, ChunkData, ChunkPos, McsData},
+    unpacker::McsDecoder,
+    CompressionType,
+};
+use byteorder::LittleEndian;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// .mcstructure 文件的格式版本
+pub const MCSTRUCTURE_FORMAT_VERSION: i32 = 1;
+
+/// 写出调色板条目时使用的方块状态版本（1.18.10）
+pub const MCSTRUCTURE_BLOCK_VERSION: i32 = 17_959_425;
+
+/// 结构包围盒的最大体积，防止分散的建筑分配过大的索引数组
+pub const MAX_MCSTRUCTURE_VOLUME: u64 = 64 * 1024 * 1024;
+
+fn invalid(message: &str) -> McStreamError {
+    McStreamError::ValidationError(format!("mcstructure文件格式错误: {}", message))
+}
+
+// 没有命名空间的旧版方块名属于 minecraft
+fn namespaced(name: &str) -> String {
+    if name.contains(':') {
+        name.to_string()
+    } else {
+        format!("minecraft:{}", name)
+    }
+}
+
+// 布尔状态以Byte存储，其余为Int或String
+fn state_value_to_string(value: &NbtValue) -> Option<String> {
+    match value {
+        NbtValue::Byte(0) => Some("false".to_string()),
+        NbtValue::Byte(1) => Some("true".to_string()),
+        NbtValue::String(value) => Some(value.clone()),
+        other => other.as_i32().map(|value| value.to_string()),
+    }
+}
+
+fn state_value_from_str(value: &str) -> NbtValue {
+    match value {
+        "false" => NbtValue::Byte(0),
+        "true" => NbtValue::Byte(1),
+        _ => match value.parse() {
+            Ok(value) => NbtValue::Int(value),
+            Err(_) => NbtValue::String(value.to_string()),
+        },
+    }
+}
+
+fn block_state_from_entry(entry: &NbtValue) -> Option<String> {
+    let name = namespaced(entry.get("name")?.as_str()?);
+
+    let mut states = Vec::new();
+    if let Some(NbtValue::Compound(fields)) = entry.get("states") {
+        for (key, value) in fields {
+            states.push((key.as_str(), state_value_to_string(value)?));
+        }
+    }
+
+    let states: Vec<(&str, &str)> = states.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    Some(join_block_state(&name, &states))
+}
+
+fn read_layer(layer: &NbtValue, volume: usize) -> Result<Vec<i32>, McStreamError> {
+    let layer = layer
+        .as_list()
+        .filter(|l| l.len() == volume)
+        .ok_or_else(|| invalid("block_indices的长度与size不符"))?;
+    layer
+        .iter()
+        .map(|index| {
+            index
+                .as_i32()
+                .ok_or_else(|| invalid("block_indices格式错误"))
+        })
+        .collect()
+}
+
+/// 读取基岩版 .mcstructure 文件
+///
+/// 坐标以结构的最小角为原点，只转换方块：第0层为主方块，第1层（如含水方块中的水）为覆盖层，
+/// 方块实体数据转换为Java版（大端）NBT，内容不做改动。方块状态写成 `name[key=value,...]`，
+/// 布尔状态写成 `true`/`false`，没有命名空间的旧版方块名补上 `minecraft:`。
+///
+/// 基岩版与Java版的方块ID和状态名有不少差异（如 `minecraft:grass` 对应Java版的
+/// `minecraft:grass_block`），这里不做映射，需要时用 [`McsData::remap_palette_states`] 转换。
+/// 实体与旧版文件中的数据值（`val`）不转换。
+pub fn read<P: AsRef<Path>>(path: P) -> Result<McsData, McStreamError> {
+    let file = File::open(path)?;
+    let (_, root) = nbt::read_named::<LittleEndian, _>(&mut BufReader::new(file))?;
+
+    let format_version = root.get("format_version").and_then(|v| v.as_i32());
+    if format_version != Some(MCSTRUCTURE_FORMAT_VERSION) {
+        return Err(invalid("不支持的format_version"));
+    }
+
+    let size = read_int_triple(root.get("size"), "size")?;
+    if size.iter().any(|&len| len < 0) {
+        return Err(invalid("size不能为负数"));
+    }
+    let [size_x, size_y, size_z] = size.map(|len| len as u64);
+    let volume = size_x.saturating_mul(size_y).saturating_mul(size_z);
+    if volume > MAX_MCSTRUCTURE_VOLUME {
+        return Err(invalid("结构体积过大"));
+    }
+
+    let structure = root
+        .get("structure")
+        .ok_or_else(|| invalid("缺少structure"))?;
+    let layers = structure
+        .get("block_indices")
+        .and_then(|b| b.as_list())
+        .filter(|l| !l.is_empty())
+        .ok_or_else(|| invalid("缺少block_indices"))?;
+    let primary = read_layer(&layers[0], volume as usize)?;
+    let secondary = match layers.get(1) {
+        Some(layer) => read_layer(layer, volume as usize)?,
+        None => Vec::new(),
+    };
+
+    let palette = structure
+        .get("palette")
+        .and_then(|p| p.get("default"))
+        .ok_or_else(|| invalid("缺少palette"))?;
+    let mut states = Vec::new();
+    for entry in palette
+        .get("block_palette")
+        .and_then(|p| p.as_list())
+        .ok_or_else(|| invalid("缺少block_palette"))?
+    {
+        states.push(block_state_from_entry(entry).ok_or_else(|| invalid("调色板条目格式错误"))?);
+    }
+
+    let resolve = |index: i32| -> Result<Option<&String>, McStreamError> {
+        if index < 0 {
+            return Ok(None);
+        }
+        let state = states
+            .get(index as usize)
+            .ok_or_else(|| invalid("方块索引超出调色板范围"))?;
+        Ok((split_block_state(state).0 != "minecraft:air").then_some(state))
+    };
+
+    // 方块实体数据以索引数组中的下标为键
+    let mut block_entities = HashMap::new();
+    if let Some(NbtValue::Compound(fields)) = palette.get("block_position_data") {
+        for (key, value) in fields {
+            let index: usize = key
+                .parse()
+                .map_err(|_| invalid("block_position_data的键格式错误"))?;
+            if let Some(data) = value.get("block_entity_data") {
+                block_entities.insert(index, data);
+            }
+        }
+    }
+
+    let mut encoder = McsEncoder::new(CompressionType::Zstandard);
+    for (i, &index) in primary.iter().enumerate() {
+        let block = resolve(index)?;
+        let overlay = match secondary.get(i) {
+            Some(&index) => resolve(index)?,
+            None => None,
+        };
+
+        let i = i as u64;
+        let x = (i / (size_y * size_z)) as i32;
+        let y = (i / size_z % size_y) as i32;
+        let z = (i % size_z) as i32;
+        let nbt = match block_entities.get(&(i as usize)) {
+            Some(data) => Some(nbt::to_bytes("", data)?),
+            None => None,
+        };
+
+        match (block, overlay) {
+            (Some(block), Some(overlay)) => {
+                encoder.add_block_with_overlay(block.clone(), overlay.clone(), x, y, z, nbt)?
+            }
+            // 只有第1层有方块时作为主方块
+            (Some(block), None) | (None, Some(block)) => {
+                encoder.add_block(block.clone(), x, y, z, nbt)?
+            }
+            (None, None) => {}
+        }
+    }
+
+    Ok(encoder.to_mcs_data())
+}
+
+/// 将解码后的建筑写出为基岩版 .mcstructure 文件
+///
+/// 坐标以建筑的最小角为原点，`structure_world_origin` 记录该最小角；覆盖层写入第1层。
+/// 方块ID与状态原样写出（取值为 `true`/`false` 的状态写成Byte，整数写成Int，其余为String），
+/// 不做Java版到基岩版的映射；NBT必须是二进制NBT复合标签，作为方块实体数据原样写出。
+/// 同一位置有多个方块时以最后一个为准，包围盒超过 [`MAX_MCSTRUCTURE_VOLUME`] 时返回错误。
+pub fn write<P: AsRef<Path>>(decoder: &McsDecoder, path: P) -> Result<(), McStreamError> {
+    let root = build_mcstructure(decoder.get_chunks())?;
+
+    let file = File::create(path)?;
+    let mut writer = BufWriter::new(file);
+    nbt::write_named::<LittleEndian, _>(&mut writer, "", &root)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn palette_entry(block_id: &str) -> NbtValue {
+    let (name, properties) = split_block_state(block_id);
+    let states = properties
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), state_value_from_str(value)))
+        .collect();
+
+    NbtValue::Compound(vec![
+        ("name".to_string(), NbtValue::String(name.to_string())),
+        ("states".to_string(), NbtValue::Compound(states)),
+        (
+            "version".to_string(),
+            NbtValue::Int(MCSTRUCTURE_BLOCK_VERSION),
+        ),
+    ])
+}
+
+fn build_mcstructure(chunks: &HashMap<ChunkPos, ChunkData>) -> Result<NbtValue, McStreamError> {
+    // 按区块坐标排序，保证调色板顺序稳定
+    let mut sorted: Vec<&ChunkData> = chunks.values().collect();
+    sorted.sort_by_key(|chunk| (chunk.pos.x, chunk.pos.z));
+
+    let mut bounds: Option<(BlockPos, BlockPos)> = None;
+    for chunk in &sorted {
+        for block in &chunk.blocks {
+            let pos = block.pos.to_global(chunk.pos);
+            let (min, max) = bounds.get_or_insert((pos, pos));
+            *min = BlockPos::new(min.x.min(pos.x), min.y.min(pos.y), min.z.min(pos.z));
+            *max = BlockPos::new(max.x.max(pos.x), max.y.max(pos.y), max.z.max(pos.z));
+        }
+    }
+    let Some((min, max)) = bounds else {
+        return Err(McStreamError::ValidationError(
+            "没有方块数据可写入".to_string(),
+        ));
+    };
+
+    let size = [
+        (max.x as i64 - min.x as i64 + 1) as u64,
+        (max.y as i64 - min.y as i64 + 1) as u64,
+        (max.z as i64 - min.z as i64 + 1) as u64,
+    ];
+    let volume = size[0].saturating_mul(size[1]).saturating_mul(size[2]);
+    if volume > MAX_MCSTRUCTURE_VOLUME {
+        return Err(McStreamError::ValidationError(format!(
+            "结构过大，最多允许 {} 个方块位置",
+            MAX_MCSTRUCTURE_VOLUME
+        )));
+    }
+
+    let mut palette = Vec::new();
+    let mut state_index: HashMap<&str, i32> = HashMap::new();
+    let mut primary = vec![-1; volume as usize];
+    let mut secondary = vec![-1; volume as usize];
+    let mut block_entities = BTreeMap::new();
+
+    for chunk in sorted {
+        for block in &chunk.blocks {
+            let mut lookup = |palette_index: u32| -> Result<i32, McStreamError> {
+                let block_id = chunk
+                    .palette
+                    .get(palette_index)
+                    .ok_or_else(|| McStreamError::PaletteError("无效的调色板索引".to_string()))?;
+                Ok(*state_index.entry(block_id).or_insert_with(|| {
+                    palette.push(palette_entry(block_id));
+                    (palette.len() - 1) as i32
+                }))
+            };
+
+            let pos = block.pos.to_global(chunk.pos);
+            let index = ((pos.x - min.x) as u64 * size[1] + (pos.y - min.y) as u64) * size[2]
+                + (pos.z - min.z) as u64;
+            let index = index as usize;
+            primary[index] = lookup(block.palette_index)?;
+            secondary[index] = match block.overlay {
+                Some(overlay) => lookup(overlay)?,
+                None => -1,
+            };
+
+            match block.nbt_decompressed()? {
+                Some(nbt_data) => {
+                    let (_, value) = nbt::parse(&nbt_data)?;
+                    if !matches!(value, NbtValue::Compound(_)) {
+                        return Err(McStreamError::NbtError("方块NBT必须是复合标签".to_string()));
+                    }
+                    block_entities.insert(index, value);
+                }
+                None => {
+                    block_entities.remove(&index);
+                }
+            }
+        }
+    }
+
+    let layer =
+        |indices: Vec<i32>| NbtValue::List(indices.into_iter().map(NbtValue::Int).collect());
+    let block_position_data = block_entities
+        .into_iter()
+        .map(|(index, value)| {
+            (
+                index.to_string(),
+                NbtValue::Compound(vec![("block_entity_data".to_string(), value)]),
+            )
+        })
+        .collect();
+    let int_triple = |values: [i32; 3]| NbtValue::List(values.map(NbtValue::Int).to_vec());
+
+    Ok(NbtValue::Compound(vec![
+        (
+            "format_version".to_string(),
+            NbtValue::Int(MCSTRUCTURE_FORMAT_VERSION),
+        ),
+        ("size".to_string(), int_triple(size.map(|len| len as i32))),
+        (
+            "structure".to_string(),
+            NbtValue::Compound(vec![
+                (
+                    "block_indices".to_string(),
+                    NbtValue::List(vec![layer(primary), layer(secondary)]),
+                ),
+                ("entities".to_string(), NbtValue::List(Vec::new())),
+                (
+                    "palette".to_string(),
+                    NbtValue::Compound(vec![(
+                        "default".to_string(),
+                        NbtValue::Compound(vec![
+                            ("block_palette".to_string(), NbtValue::List(palette)),
+                            (
+                                "block_position_data".to_string(),
+                                NbtValue::Compound(block_position_data),
+                            ),
+                        ]),
+                    )]),
+                ),
+            ]),
+        ),
+        (
+            "structure_world_origin".to_string(),
+            int_triple([min.x, min.y, min.z]),
+        ),
+    ]))
+}
@@ -0,0 +1,5 @@
+// 与Minecraft原版及其他工具文件格式的互相转换
+
+pub mod anvil;
+pub mod mcstructure;
+pub mod structure;
@@ -0,0 +1,306 @@
+// 原版结构方块导出的 .nbt 文件（gzip压缩的大端NBT）
+// 结构文件中的方块状态以 Name + Properties 复合标签表示，
+// 转换为MCS调色板条目时写成 `name[key=value,...]` 形式
+
+use crate::{
+    error::McStreamError,
+    nbt::{self, NbtValue},
+    packer::McsEncoder,
+    types::{BlockPos, ChunkData, ChunkPos, McsData},
+    unpacker::McsDecoder,
+    CompressionType,
+};
+use byteorder::BigEndian;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// 写出结构文件时使用的数据版本（Minecraft 1.20.1）
+pub const STRUCTURE_DATA_VERSION: i32 = 3465;
+
+/// 将方块状态拆分为方块名与属性列表
+pub fn split_block_state(id: &str) -> (&str, Vec<(&str, &str)>) {
+    match (id.find('['), id.strip_suffix(']')) {
+        (Some(start), Some(rest)) if start < rest.len() => {
+            let properties = rest[start + 1..]
+                .split(',')
+                .filter(|p| !p.is_empty())
+                .map(|p| p.split_once('=').unwrap_or((p, "")))
+                .collect();
+            (&id[..start], properties)
+        }
+        _ => (id, Vec::new()),
+    }
+}
+
+/// 由方块名与属性拼接方块状态字符串
+pub fn join_block_state(name: &str, properties: &[(&str, &str)]) -> String {
+    if properties.is_empty() {
+        return name.to_string();
+    }
+
+    let properties: Vec<String> = properties
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    format!("{}[{}]", name, properties.join(","))
+}
+
+/// 将 Name + Properties 复合标签转换为方块状态字符串
+pub fn block_state_from_nbt(entry: &NbtValue) -> Option<String> {
+    let name = entry.get("Name")?.as_str()?;
+
+    let mut properties = Vec::new();
+    if let Some(NbtValue::Compound(fields)) = entry.get("Properties") {
+        for (key, value) in fields {
+            properties.push((key.as_str(), value.as_str()?));
+        }
+    }
+
+    Some(join_block_state(name, &properties))
+}
+
+fn invalid(message: &str) -> McStreamError {
+    McStreamError::ValidationError(format!("结构文件格式错误: {}", message))
+}
+
+pub(crate) fn read_int_triple(
+    value: Option<&NbtValue>,
+    field: &str,
+) -> Result<[i32; 3], McStreamError> {
+    let list = value
+        .and_then(|v| v.as_list())
+        .filter(|l| l.len() == 3)
+        .ok_or_else(|| invalid(field))?;
+
+    let mut result = [0; 3];
+    for (slot, item) in result.iter_mut().zip(list) {
+        *slot = item.as_i32().ok_or_else(|| invalid(field))?;
+    }
+    Ok(result)
+}
+
+/// 读取原版结构 .nbt 文件
+///
+/// 结构中的坐标原样作为全局坐标，空气方块会被忽略，实体暂不转换。
+pub fn read_structure_nbt<P: AsRef<Path>>(path: P) -> Result<McsData, McStreamError> {
+    let file = File::open(path)?;
+    let mut reader = GzDecoder::new(BufReader::new(file));
+    let (_, root) = nbt::read_named::<BigEndian, _>(&mut reader)?;
+
+    // 带随机变体的结构使用 palettes，此时取第一套调色板
+    let palette = match root.get("palette") {
+        Some(palette) => palette.as_list(),
+        None => root
+            .get("palettes")
+            .and_then(|p| p.as_list())
+            .and_then(|p| p.first())
+            .and_then(|p| p.as_list()),
+    }
+    .ok_or_else(|| invalid("缺少palette"))?;
+
+    let mut states = Vec::with_capacity(palette.len());
+    for entry in palette {
+        states.push(block_state_from_nbt(entry).ok_or_else(|| invalid("调色板条目格式错误"))?);
+    }
+
+    let blocks = root
+        .get("blocks")
+        .and_then(|b| b.as_list())
+        .ok_or_else(|| invalid("缺少blocks"))?;
+
+    let mut encoder = McsEncoder::new(CompressionType::Zstandard);
+
+    for block in blocks {
+        let state = block
+            .get("state")
+            .and_then(|s| s.as_i32())
+            .ok_or_else(|| invalid("方块缺少state"))?;
+        let block_id = usize::try_from(state)
+            .ok()
+            .and_then(|s| states.get(s))
+            .ok_or_else(|| invalid("方块state超出调色板范围"))?;
+
+        let [x, y, z] = read_int_triple(block.get("pos"), "方块pos")?;
+
+        let nbt = match block.get("nbt") {
+            Some(nbt) => Some(nbt::to_bytes("", nbt)?),
+            None => None,
+        };
+
+        encoder.add_block(block_id.clone(), x, y, z, nbt)?;
+    }
+
+    Ok(encoder.to_mcs_data())
+}
+
+/// 将解码后的建筑写出为原版结构 .nbt 文件
+///
+/// 坐标以建筑的最小角为原点；NBT必须是二进制NBT复合标签。
+/// 结构格式每个位置只有一个方块，覆盖层不会写出。
+pub fn write_structure_nbt<P: AsRef<Path>>(
+    decoder: &McsDecoder,
+    path: P,
+) -> Result<(), McStreamError> {
+    let root = build_structure(decoder.get_chunks())?;
+
+    let file = File::create(path)?;
+    let mut writer = GzEncoder::new(BufWriter::new(file), Compression::default());
+    nbt::write_named::<BigEndian, _>(&mut writer, "", &root)?;
+    writer.finish()?.flush()?;
+
+    Ok(())
+}
+
+// 全局坐标、方块ID与解压后的NBT
+type StructureEntry<'a> = (BlockPos, &'a str, Option<Cow<'a, [u8]>>);
+
+fn build_structure(chunks: &HashMap<ChunkPos, ChunkData>) -> Result<NbtValue, McStreamError> {
+    let mut entries: Vec<StructureEntry> = Vec::new();
+
+    for chunk in chunks.values() {
+        for block in &chunk.blocks {
+            let block_id = chunk
+                .palette
+                .get(block.palette_index)
+                .ok_or_else(|| McStreamError::PaletteError("无效的调色板索引".to_string()))?;
+            // 以 `keep_nbt_compressed` 打开的解码器中NBT仍是单独压缩的
+            let nbt_data = match (&block.nbt, block.nbt_compression) {
+                (Some(_), Some(_)) => block.nbt_decompressed()?.map(Cow::Owned),
+                (nbt, _) => nbt.as_deref().map(Cow::Borrowed),
+            };
+            entries.push((block.pos.to_global(chunk.pos), block_id, nbt_data));
+        }
+    }
+
+    if entries.is_empty() {
+        return Err(McStreamError::ValidationError(
+            "没有方块数据可写入".to_string(),
+        ));
+    }
+
+    // 固定方块顺序，保证输出稳定
+    entries.sort_by_key(|(pos, _, _)| (pos.y, pos.z, pos.x));
+
+    let mut min = entries[0].0;
+    let mut max = entries[0].0;
+    for (pos, _, _) in &entries {
+        min = BlockPos::new(min.x.min(pos.x), min.y.min(pos.y), min.z.min(pos.z));
+        max = BlockPos::new(max.x.max(pos.x), max.y.max(pos.y), max.z.max(pos.z));
+    }
+
+    let mut palette = Vec::new();
+    let mut state_index: HashMap<&str, i32> = HashMap::new();
+    let mut blocks = Vec::with_capacity(entries.len());
+
+    for (pos, block_id, nbt_data) in entries {
+        let state = match state_index.get(block_id) {
+            Some(state) => *state,
+            None => {
+                let (name, properties) = split_block_state(block_id);
+                let mut entry = vec![("Name".to_string(), NbtValue::String(name.to_string()))];
+                if !properties.is_empty() {
+                    entry.push((
+                        "Properties".to_string(),
+                        NbtValue::Compound(
+                            properties
+                                .into_iter()
+                                .map(|(k, v)| (k.to_string(), NbtValue::String(v.to_string())))
+                                .collect(),
+                        ),
+                    ));
+                }
+                palette.push(NbtValue::Compound(entry));
+
+                let state = (palette.len() - 1) as i32;
+                state_index.insert(block_id, state);
+                state
+            }
+        };
+
+        let mut fields = vec![
+            ("state".to_string(), NbtValue::Int(state)),
+            (
+                "pos".to_string(),
+                NbtValue::List(vec![
+                    NbtValue::Int(pos.x - min.x),
+                    NbtValue::Int(pos.y - min.y),
+                    NbtValue::Int(pos.z - min.z),
+                ]),
+            ),
+        ];
+
+        if let Some(nbt_data) = nbt_data {
+            let (_, value) = nbt::parse(&nbt_data)?;
+            if !matches!(value, NbtValue::Compound(_)) {
+                return Err(McStreamError::NbtError("方块NBT必须是复合标签".to_string()));
+            }
+            fields.push(("nbt".to_string(), value));
+        }
+
+        blocks.push(NbtValue::Compound(fields));
+    }
+
+    Ok(NbtValue::Compound(vec![
+        (
+            "DataVersion".to_string(),
+            NbtValue::Int(STRUCTURE_DATA_VERSION),
+        ),
+        (
+            "size".to_string(),
+            NbtValue::List(vec![
+                NbtValue::Int(max.x - min.x + 1),
+                NbtValue::Int(max.y - min.y + 1),
+                NbtValue::Int(max.z - min.z + 1),
+            ]),
+        ),
+        ("palette".to_string(), NbtValue::List(palette)),
+        ("blocks".to_string(), NbtValue::List(blocks)),
+        ("entities".to_string(), NbtValue::List(Vec::new())),
+    ]))
+}
+
+#[cfg(all(test, feature = "zstd"))]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn writes_separately_compressed_nbt() {
+        let nbt_value = NbtValue::Compound(vec![(
+            "id".to_string(),
+            NbtValue::String("minecraft:chest".to_string()),
+        )]);
+        let mut encoder =
+            McsEncoder::new(CompressionType::None).with_nbt_compression(CompressionType::Zstandard);
+        encoder
+            .add_block(
+                "minecraft:chest".to_string(),
+                1,
+                2,
+                3,
+                Some(nbt::to_bytes("", &nbt_value).unwrap()),
+            )
+            .unwrap();
+        let mcs = NamedTempFile::new().unwrap();
+        encoder.write_to_file(mcs.path()).unwrap();
+
+        let decoder = McsDecoder::builder()
+            .keep_nbt_compressed(true)
+            .open(mcs.path())
+            .unwrap();
+        let structure = NamedTempFile::new().unwrap();
+        write_structure_nbt(&decoder, structure.path()).unwrap();
+
+        let data = read_structure_nbt(structure.path()).unwrap();
+        let chunk = data.chunks.values().next().unwrap();
+        let (_, value) = nbt::parse(chunk.blocks[0].nbt.as_ref().unwrap()).unwrap();
+        assert_eq!(
+            value.get("id").and_then(|id| id.as_str()),
+            Some("minecraft:chest")
+        );
+    }
+}
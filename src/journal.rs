@@ -0,0 +1,265 @@
+// 追加式编辑日志
+// 日志位于区块数据之后，每次编辑作为一条事件追加到文件末尾，
+// 当前状态通过在区块数据上按顺序重放事件得到
+
+use crate::{
+    chunk::validate_local_pos,
+    error::McStreamError,
+    header::{read_header, update_flags, McsFlags},
+    palette::Palette,
+    types::{Block, BlockPos, ChunkData, ChunkPos},
+};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const OP_ADD: u8 = 0;
+const OP_REMOVE: u8 = 1;
+
+/// 日志中的编辑操作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalOp {
+    /// 放置（或替换）一个方块
+    Add {
+        block_id: String,
+        pos: BlockPos,
+        nbt: Option<Vec<u8>>,
+    },
+    /// 移除一个方块
+    Remove { pos: BlockPos },
+}
+
+impl JournalOp {
+    /// 操作的方块坐标
+    pub fn pos(&self) -> BlockPos {
+        match self {
+            JournalOp::Add { pos, .. } | JournalOp::Remove { pos } => *pos,
+        }
+    }
+}
+
+/// 日志事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEvent {
+    pub timestamp: u64, // Unix时间戳（毫秒）
+    pub author: u32,    // 作者ID
+    pub op: JournalOp,
+}
+
+impl JournalEvent {
+    /// 以当前时间创建事件
+    pub fn now(author: u32, op: JournalOp) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        Self {
+            timestamp,
+            author,
+            op,
+        }
+    }
+}
+
+/// 写入一条日志事件
+pub fn write_event<W: Write>(writer: &mut W, event: &JournalEvent) -> Result<(), McStreamError> {
+    let mut record = Vec::new();
+    record.write_u64::<LittleEndian>(event.timestamp)?;
+    record.write_u32::<LittleEndian>(event.author)?;
+
+    let pos = match &event.op {
+        JournalOp::Add { pos, .. } => {
+            record.write_u8(OP_ADD)?;
+            pos
+        }
+        JournalOp::Remove { pos } => {
+            record.write_u8(OP_REMOVE)?;
+            pos
+        }
+    };
+    record.write_i32::<LittleEndian>(pos.x)?;
+    record.write_i32::<LittleEndian>(pos.y)?;
+    record.write_i32::<LittleEndian>(pos.z)?;
+
+    if let JournalOp::Add { block_id, nbt, .. } = &event.op {
+        if block_id.len() > u16::MAX as usize {
+            return Err(McStreamError::PaletteError(
+                "调色板条目长度超过上限".to_string(),
+            ));
+        }
+        record.write_u16::<LittleEndian>(block_id.len() as u16)?;
+        record.write_all(block_id.as_bytes())?;
+
+        match nbt {
+            Some(nbt) => {
+                record.write_u8(0x01)?;
+                record.write_u32::<LittleEndian>(nbt.len() as u32)?;
+                record.write_all(nbt)?;
+            }
+            None => record.write_u8(0x00)?,
+        }
+    }
+
+    // 每条记录以长度为前缀，便于顺序追加与读取
+    writer.write_u32::<LittleEndian>(record.len() as u32)?;
+    writer.write_all(&record)?;
+
+    Ok(())
+}
+
+/// 读取数据流中剩余的全部日志事件
+pub fn read_events<R: Read>(reader: &mut R) -> Result<Vec<JournalEvent>, McStreamError> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let mut cursor = Cursor::new(data.as_slice());
+    let mut events = Vec::new();
+
+    while (cursor.position() as usize) < data.len() {
+        let record_len = cursor.read_u32::<LittleEndian>()? as usize;
+        let start = cursor.position() as usize;
+        let record = data.get(start..start + record_len).ok_or_else(|| {
+            McStreamError::ValidationError("编辑日志记录超出文件范围".to_string())
+        })?;
+        events.push(parse_event(record)?);
+        cursor.set_position((start + record_len) as u64);
+    }
+
+    Ok(events)
+}
+
+fn parse_event(record: &[u8]) -> Result<JournalEvent, McStreamError> {
+    let mut cursor = Cursor::new(record);
+
+    let timestamp = cursor.read_u64::<LittleEndian>()?;
+    let author = cursor.read_u32::<LittleEndian>()?;
+    let op = cursor.read_u8()?;
+    let pos = BlockPos::new(
+        cursor.read_i32::<LittleEndian>()?,
+        cursor.read_i32::<LittleEndian>()?,
+        cursor.read_i32::<LittleEndian>()?,
+    );
+
+    let op = match op {
+        OP_ADD => {
+            let id_len = cursor.read_u16::<LittleEndian>()?;
+            let mut buffer = vec![0u8; id_len as usize];
+            cursor.read_exact(&mut buffer)?;
+            let block_id = String::from_utf8(buffer)
+                .map_err(|_| McStreamError::PaletteError("非UTF-8编码的调色板条目".to_string()))?;
+
+            let nbt = if cursor.read_u8()? & 0x01 != 0 {
+                let nbt_len = cursor.read_u32::<LittleEndian>()?;
+                let mut nbt = vec![0u8; nbt_len as usize];
+                cursor.read_exact(&mut nbt)?;
+                Some(nbt)
+            } else {
+                None
+            };
+
+            JournalOp::Add { block_id, pos, nbt }
+        }
+        OP_REMOVE => JournalOp::Remove { pos },
+        _ => {
+            return Err(McStreamError::ValidationError(format!(
+                "未知的编辑日志操作: {}",
+                op
+            )))
+        }
+    };
+
+    Ok(JournalEvent {
+        timestamp,
+        author,
+        op,
+    })
+}
+
+/// 将一条日志事件应用到区块数据上
+///
+/// 放置空气等同于移除；移除后没有方块的区块会被删除。
+pub fn apply_event(
+    chunks: &mut HashMap<ChunkPos, ChunkData>,
+    event: &JournalEvent,
+) -> Result<(), McStreamError> {
+    let (pos, placed) = match &event.op {
+        JournalOp::Add { block_id, pos, .. } if block_id.contains("minecraft:air") => (pos, None),
+        JournalOp::Add { block_id, pos, nbt } => (pos, Some((block_id, nbt))),
+        JournalOp::Remove { pos } => (pos, None),
+    };
+
+    let chunk_pos = pos.chunk_pos();
+    let local_pos = pos.local_pos();
+    validate_local_pos(&local_pos)?;
+
+    match placed {
+        Some((block_id, nbt)) => {
+            let chunk = chunks.entry(chunk_pos).or_insert_with(|| ChunkData {
+                pos: chunk_pos,
+                palette: Palette::new(),
+                blocks: Vec::new(),
+            });
+
+            let palette_index = chunk.palette.get_or_insert(block_id.clone())?;
+            let block = Block {
+                palette_index,
+                pos: local_pos,
+                nbt: nbt.clone(),
+                nbt_compression: None,
+                overlay: None,
+                tags: 0,
+            };
+
+            match chunk.blocks.iter_mut().find(|b| b.pos == local_pos) {
+                Some(existing) => *existing = block,
+                None => chunk.blocks.push(block),
+            }
+        }
+        None => {
+            if let Some(chunk) = chunks.get_mut(&chunk_pos) {
+                chunk.blocks.retain(|b| b.pos != local_pos);
+                if chunk.blocks.is_empty() {
+                    chunks.remove(&chunk_pos);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 向已有的MCS文件末尾追加日志事件
+///
+/// 带签名的文件不能追加，否则签名会失效。
+pub fn append_events<P: AsRef<Path>>(
+    path: P,
+    events: &[JournalEvent],
+) -> Result<(), McStreamError> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+
+    let header = read_header(&mut file)?;
+    let mut flags = header.flags();
+    if flags.contains(McsFlags::SIGNATURE) {
+        return Err(McStreamError::ValidationError(
+            "带签名的文件不能追加编辑日志".to_string(),
+        ));
+    }
+
+    // 只设置日志位，其余位（包括未知位）保持不变
+    if !flags.contains(McsFlags::JOURNAL) {
+        flags.insert(McsFlags::JOURNAL);
+        update_flags(&mut file, flags.bits())?;
+    }
+
+    file.seek(SeekFrom::End(0))?;
+    for event in events {
+        write_event(&mut file, event)?;
+    }
+    file.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use crate::{
+    error::McStreamError,
+    palette::IdTable,
+    types::{ChunkData, ChunkIndexEntry, ChunkPos, McsHeader},
+    unpacker::{
+        gunzip_if_wrapped, read_chunk_at, read_validated_index, validate_dictionary, DecodeOptions,
+    },
+};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tempfile::NamedTempFile;
+
+/// 默认缓存的区块数量上限
+pub const DEFAULT_CACHE_CAPACITY: usize = 256;
+
+/// 最近最少使用（LRU）淘汰的区块缓存
+struct ChunkCache {
+    capacity: usize,
+    chunks: HashMap<ChunkPos, Arc<ChunkData>>,
+    order: VecDeque<ChunkPos>, // 队首为最久未使用
+}
+
+impl ChunkCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            chunks: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn touch(&mut self, pos: ChunkPos) {
+        if let Some(index) = self.order.iter().position(|p| *p == pos) {
+            self.order.remove(index);
+        }
+        self.order.push_back(pos);
+    }
+
+    fn get(&mut self, pos: ChunkPos) -> Option<Arc<ChunkData>> {
+        let chunk = self.chunks.get(&pos).cloned()?;
+        self.touch(pos);
+        Some(chunk)
+    }
+
+    fn insert(&mut self, pos: ChunkPos, chunk: Arc<ChunkData>) {
+        self.chunks.insert(pos, chunk);
+        self.touch(pos);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.chunks.len() > self.capacity {
+            match self.order.pop_front() {
+                Some(pos) => {
+                    self.chunks.remove(&pos);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// 按需加载的MCS解码器，只在访问时读取并解压区块
+pub struct LazyMcsDecoder {
+    path: PathBuf,
+    header: McsHeader,
+    index: HashMap<ChunkPos, Vec<ChunkIndexEntry>>, // 一个区块可能被拆分为多个条目
+    cache: Mutex<ChunkCache>,
+    options: DecodeOptions,
+    _unwrapped: Option<NamedTempFile>, // gzip包裹的文件解压后的临时文件，随解码器一起删除
+}
+
+impl LazyMcsDecoder {
+    /// 打开MCS文件，仅读取头部与区块索引表
+    ///
+    /// 文件被gzip包裹时先整个解压到临时文件，之后的区块从临时文件读取。
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, McStreamError> {
+        let unwrapped = gunzip_if_wrapped(path.as_ref())?;
+        let path = unwrapped.as_ref().map_or(path.as_ref(), |temp| temp.path());
+        let (header, entries, _) = read_validated_index(path)?;
+
+        let mut index: HashMap<ChunkPos, Vec<ChunkIndexEntry>> = HashMap::new();
+        for entry in entries {
+            index
+                .entry(ChunkPos::new(entry.chunk_x, entry.chunk_z))
+                .or_default()
+                .push(entry);
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            header,
+            index,
+            cache: Mutex::new(ChunkCache::new(DEFAULT_CACHE_CAPACITY)),
+            options: DecodeOptions::default(),
+            _unwrapped: unwrapped,
+        })
+    }
+
+    /// 设置缓存的区块数量上限，超出时淘汰最久未使用的区块
+    pub fn with_cache_capacity(self, capacity: usize) -> Self {
+        {
+            let mut cache = self.lock_cache();
+            cache.capacity = capacity;
+            cache.evict();
+        }
+        self
+    }
+
+    /// 使用Zstandard压缩字典解码区块（文件以该字典编码时必须提供）
+    pub fn with_dictionary(mut self, dictionary: Vec<u8>) -> Self {
+        self.options.dictionary = Some(dictionary);
+        self
+    }
+
+    /// 使用方块ID表还原按编号存储的调色板（文件以该表编码时必须提供）
+    pub fn with_id_table(mut self, table: IdTable) -> Self {
+        self.options.id_table = Some(table);
+        self
+    }
+
+    /// 获取文件头
+    pub fn header(&self) -> &McsHeader {
+        &self.header
+    }
+
+    /// 获取文件中所有区块的坐标
+    pub fn chunk_positions(&self) -> impl Iterator<Item = &ChunkPos> {
+        self.index.keys()
+    }
+
+    /// 获取指定坐标的区块，未缓存时从文件读取
+    pub fn get_chunk(&self, x: i32, z: i32) -> Result<Option<Arc<ChunkData>>, McStreamError> {
+        let pos = ChunkPos::new(x, z);
+
+        if let Some(chunk) = self.lock_cache().get(pos) {
+            return Ok(Some(chunk));
+        }
+
+        let entries = match self.index.get(&pos) {
+            Some(entries) => entries,
+            None => return Ok(None),
+        };
+
+        let chunk = Arc::new(self.load_chunk(entries)?);
+        self.lock_cache().insert(pos, chunk.clone());
+
+        Ok(Some(chunk))
+    }
+
+    /// 并行预取一批区块并放入缓存
+    ///
+    /// 文件中不存在或已缓存的区块会被跳过。预取数量超过缓存上限时，
+    /// 只有最后加入的区块会保留。
+    pub fn prefetch(&self, chunks: &[ChunkPos]) -> Result<(), McStreamError> {
+        let missing: Vec<&Vec<ChunkIndexEntry>> = {
+            let cache = self.lock_cache();
+            chunks
+                .iter()
+                .filter(|pos| !cache.chunks.contains_key(pos))
+                .filter_map(|pos| self.index.get(pos))
+                .collect()
+        };
+
+        #[cfg(feature = "parallel")]
+        let iter = missing.par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let mut iter = missing.iter();
+        iter.try_for_each(|entries| {
+            let chunk = Arc::new(self.load_chunk(entries)?);
+            self.lock_cache().insert(chunk.pos, chunk);
+            Ok(())
+        })
+    }
+
+    /// 当前缓存的区块数量
+    pub fn cached_count(&self) -> usize {
+        self.lock_cache().chunks.len()
+    }
+
+    fn load_chunk(&self, entries: &[ChunkIndexEntry]) -> Result<ChunkData, McStreamError> {
+        validate_dictionary(&self.header, &self.options)?;
+
+        let mut chunk: Option<ChunkData> = None;
+        for entry in entries {
+            let part = read_chunk_at(&self.path, entry, &self.header, &self.options)?;
+            match chunk.as_mut() {
+                Some(chunk) => chunk.merge(part)?,
+                None => chunk = Some(part),
+            }
+        }
+
+        let mut chunk = chunk.ok_or(McStreamError::ChunkIndexError)?;
+        for block in &mut chunk.blocks {
+            block.decompress_nbt_in_place()?;
+        }
+        Ok(chunk)
+    }
+
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, ChunkCache> {
+        // 缓存只保存已完成解码的区块，即使持锁线程崩溃其内容也保持一致
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
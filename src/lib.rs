@@ -1,30 +1,69 @@
+pub mod batch;
+pub mod bloom;
 pub mod chunk;
 pub mod compression;
+pub mod copy;
 pub mod error;
+pub mod export;
+pub mod format;
 pub mod header;
+pub mod info;
+pub mod integrity;
+pub mod interop;
+pub mod journal;
+pub mod lazy;
+pub mod memory;
 pub mod nbt;
 pub mod packer;
 pub mod palette;
+pub mod stream;
 pub mod types;
 pub mod unpacker;
 pub mod utils;
 
+#[cfg(feature = "zstd")]
+pub use crate::compression::train_dictionary;
+pub use crate::copy::copy_chunks;
 pub use crate::error::McStreamError;
+pub use crate::info::McsInfo;
+pub use crate::lazy::LazyMcsDecoder;
+pub use crate::memory::{decode_from_bytes, encode_to_bytes};
 pub use crate::packer::McsEncoder;
-pub use crate::unpacker::McsDecoder;
+pub use crate::stream::McsChunkReader;
+pub use crate::unpacker::{McsDecoder, McsDecoderBuilder};
 
 /// MCStream版本号常量
-pub const MCS_VERSION: u16 = 0x0100; // 1.0版本
+pub const MCS_VERSION: u16 = 0x0101; // 1.1版本
+
+/// 仍可读取的最低版本号（1.0版本的索引条目不含方块数）
+pub const MCS_MIN_VERSION: u16 = 0x0100;
 
 /// MCStream魔数常量
 pub const MCS_MAGIC: &[u8; 8] = b"MCSTRM\0\0";
 
 /// 压缩算法枚举
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
 pub enum CompressionType {
-    None = 0,
-    Zstandard = 1,
-    LZ4 = 2,
-    Brotli = 3,
+    None,
+    Zstandard,
+    LZ4,
+    Brotli,
+    LZ4Block, // LZ4块格式，数据前为4字节（小端）的原始长度，没有帧头尾，适合大量很小的区块
+    /// 使用方注册的压缩算法（见 [`compression::register_custom_compression`]），
+    /// 值即头部中的压缩类型，须在 [`compression::CUSTOM_COMPRESSION_RANGE`] 内
+    Custom(u8),
+}
+
+impl CompressionType {
+    /// 头部中的压缩类型值
+    pub fn to_u8(self) -> u8 {
+        match self {
+            CompressionType::None => 0,
+            CompressionType::Zstandard => 1,
+            CompressionType::LZ4 => 2,
+            CompressionType::Brotli => 3,
+            CompressionType::LZ4Block => 4,
+            CompressionType::Custom(id) => id,
+        }
+    }
 }
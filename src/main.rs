@@ -1,8 +1,21 @@
 use clap::{Parser, Subcommand};
-use mcstream::{CompressionType, McStreamError, McsDecoder, McsEncoder};
+use mcstream::chunk;
+use mcstream::compression::{compression_type_from_u8, decompress_data};
+use mcstream::format::{
+    index_entry_size, Endian, FieldLayout, HEADER_SIZE, INDEX_COUNT_SIZE, INDEX_ENTRY_LAYOUT,
+    INDEX_ENTRY_LAYOUT_V1_0, LAYOUT,
+};
+use mcstream::header::McsFlags;
+use mcstream::interop::{mcstructure, structure};
+use mcstream::types::{
+    ChunkData, ChunkEncoding, ChunkIndexEntry, ChunkPos, McsData, PaletteReport,
+};
+use mcstream::unpacker::{read_mcs_header, read_mcs_index, read_raw_chunk, sniff};
+use mcstream::{export::json, CompressionType, McStreamError, McsDecoder, McsEncoder, McsInfo};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::path::PathBuf;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 
 /// MCStream格式命令行工具 - Minecraft建筑高效二进制流式存储格式
 #[derive(Parser)]
@@ -38,6 +51,62 @@ enum Commands {
         /// 输出文件路径（JSON格式）
         #[arg(short, long)]
         output: PathBuf,
+
+        /// 方块顺序: none, coords（按y、z、x坐标）, original（保持添加顺序）
+        #[arg(long, default_value = "none")]
+        sort: String,
+    },
+
+    /// 在MCS与其他格式之间转换（根据输入文件内容判断，支持原版结构 .nbt 与基岩版 .mcstructure）
+    Convert {
+        /// 输入文件路径
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// 输出文件路径
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// 输出为MCS时使用的压缩算法: none, zstd, lz4, brotli
+        #[arg(short, long, default_value = "zstd")]
+        compression: String,
+    },
+
+    /// 使用其他压缩算法重新压缩MCS文件
+    Recompress {
+        /// 输入MCS文件路径
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// 新的压缩算法: none, zstd, lz4, brotli
+        #[arg(short, long)]
+        compression: String,
+
+        /// 输出MCS文件路径
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// 只读取单个区块并以JSON格式输出，无需加载整个文件
+    DumpChunk {
+        /// MCS文件路径
+        #[arg(short, long)]
+        file: PathBuf,
+
+        /// 区块X坐标
+        #[arg(long, allow_negative_numbers = true)]
+        x: i32,
+
+        /// 区块Z坐标
+        #[arg(long, allow_negative_numbers = true)]
+        z: i32,
+    },
+
+    /// 按字段列出MCS文件的头部、索引表与各区块概况，附每一段的字节偏移，用于调试格式问题
+    Hexdump {
+        /// MCS文件路径（不支持gzip包裹的文件）
+        #[arg(short, long)]
+        file: PathBuf,
     },
 
     /// 查看MCS文件信息
@@ -53,6 +122,9 @@ enum Commands {
 }
 
 fn main() -> Result<(), McStreamError> {
+    // 日志级别由 RUST_LOG 环境变量控制，默认只输出警告
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
     let cli = Cli::parse();
 
     match &cli.command {
@@ -79,16 +151,7 @@ fn main() -> Result<(), McStreamError> {
                 println!("检查目录写入权限...");
             }
 
-            let compression_type = match compression.to_lowercase().as_str() {
-                "none" => CompressionType::None,
-                "zstd" => CompressionType::Zstandard,
-                "lz4" => CompressionType::LZ4,
-                "brotli" => CompressionType::Brotli,
-                _ => {
-                    println!("不支持的压缩算法: {}，使用默认的zstd", compression);
-                    CompressionType::Zstandard
-                }
-            };
+            let compression_type = parse_compression(compression);
 
             println!("打包中...");
             match pack_json_to_mcs(input, output, compression_type) {
@@ -111,7 +174,11 @@ fn main() -> Result<(), McStreamError> {
             }
         }
 
-        Commands::Unpack { input, output } => {
+        Commands::Unpack {
+            input,
+            output,
+            sort,
+        } => {
             // 检查输入文件是否存在
             if !input.exists() {
                 return Err(McStreamError::Io(std::io::Error::new(
@@ -126,7 +193,7 @@ fn main() -> Result<(), McStreamError> {
             }
 
             println!("解包中...");
-            match unpack_mcs_to_json(input, output) {
+            match unpack_mcs_to_json(input, output, parse_block_order(sort)) {
                 Ok(_) => {
                     println!("解包完成: {}", output.display());
                     Ok(())
@@ -138,6 +205,94 @@ fn main() -> Result<(), McStreamError> {
             }
         }
 
+        Commands::Convert {
+            input,
+            output,
+            compression,
+        } => {
+            if !input.exists() {
+                return Err(McStreamError::Io(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("输入文件不存在: {}", input.display()),
+                )));
+            }
+
+            if let Some(parent) = output.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            println!("转换中...");
+            match convert_file(input, output, parse_compression(compression)) {
+                Ok(_) => {
+                    println!("转换完成: {}", output.display());
+                    Ok(())
+                }
+                Err(e) => {
+                    eprintln!("转换失败: {}", e);
+                    Err(e)
+                }
+            }
+        }
+
+        Commands::Recompress {
+            file,
+            compression,
+            output,
+        } => {
+            if !file.exists() {
+                return Err(McStreamError::Io(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("输入文件不存在: {}", file.display()),
+                )));
+            }
+
+            println!("重新压缩中...");
+            match recompress_file(file, output, parse_compression(compression)) {
+                Ok(_) => {
+                    println!("重新压缩完成: {}", output.display());
+                    Ok(())
+                }
+                Err(e) => {
+                    eprintln!("重新压缩失败: {}", e);
+                    Err(e)
+                }
+            }
+        }
+
+        Commands::DumpChunk { file, x, z } => {
+            if !file.exists() {
+                return Err(McStreamError::Io(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("文件不存在: {}", file.display()),
+                )));
+            }
+
+            match dump_chunk(file, ChunkPos::new(*x, *z)) {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    eprintln!("读取区块失败: {}", e);
+                    Err(e)
+                }
+            }
+        }
+
+        Commands::Hexdump { file } => {
+            if !file.exists() {
+                return Err(McStreamError::Io(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("文件不存在: {}", file.display()),
+                )));
+            }
+
+            match hexdump(file) {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    eprintln!("读取文件结构失败: {}", e);
+                    Err(e)
+                }
+            }
+        }
+
         Commands::Info { file, verbose } => {
             // 检查文件是否存在
             if !file.exists() {
@@ -158,6 +313,120 @@ fn main() -> Result<(), McStreamError> {
     }
 }
 
+/// 解析压缩算法名称，不支持时使用zstd
+fn parse_compression(compression: &str) -> CompressionType {
+    match compression.to_lowercase().as_str() {
+        "none" => CompressionType::None,
+        "zstd" => CompressionType::Zstandard,
+        "lz4" => CompressionType::LZ4,
+        "lz4-block" => CompressionType::LZ4Block,
+        "brotli" => CompressionType::Brotli,
+        _ => {
+            println!("不支持的压缩算法: {}，使用默认的zstd", compression);
+            CompressionType::Zstandard
+        }
+    }
+}
+
+fn parse_block_order(order: &str) -> json::BlockOrder {
+    match order.to_lowercase().as_str() {
+        "none" => json::BlockOrder::None,
+        "coords" => json::BlockOrder::Coords,
+        "original" => json::BlockOrder::Original,
+        _ => {
+            println!("不支持的方块顺序: {}，不进行排序", order);
+            json::BlockOrder::None
+        }
+    }
+}
+
+/// 在MCS与结构文件之间转换，方向由输入文件的内容决定
+///
+/// 输入为MCS文件时按输出的扩展名写出基岩版 .mcstructure 或原版结构 .nbt；
+/// 否则gzip压缩的输入按结构 .nbt 读取，其余按 .mcstructure 读取，并输出MCS。
+fn convert_file(
+    input: &Path,
+    output: &Path,
+    compression: CompressionType,
+) -> Result<(), McStreamError> {
+    if sniff(input) {
+        let decoder = McsDecoder::from_file(input)?;
+        if output.extension().is_some_and(|ext| ext == "mcstructure") {
+            mcstructure::write(&decoder, output)
+        } else {
+            structure::write_structure_nbt(&decoder, output)
+        }
+    } else {
+        let mut magic = [0u8; 2];
+        File::open(input)?.read_exact(&mut magic)?;
+        let data = if magic == [0x1F, 0x8B] {
+            structure::read_structure_nbt(input)?
+        } else {
+            mcstructure::read(input)?
+        };
+        let mut encoder = McsEncoder::new(compression);
+        for chunk in data.chunks.into_values() {
+            encoder.add_chunk(chunk)?;
+        }
+        encoder.write_to_file(output)?;
+        Ok(())
+    }
+}
+
+/// 解码后以新的压缩算法重新编码，保留调色板、NBT（含单独压缩方式）、编辑日志、采集原点与头部选项
+///
+/// 重新编码后签名不再有效，会被移除并给出警告。
+fn recompress_file(
+    input: &Path,
+    output: &Path,
+    compression: CompressionType,
+) -> Result<(), McStreamError> {
+    let decoder = McsDecoder::builder()
+        .keep_nbt_compressed(true)
+        .open(input)?;
+    McsEncoder::from_decoder(decoder, compression)?.write_to_file(output)?;
+    Ok(())
+}
+
+/// 读取索引后只解压指定区块（拆分为多个条目时合并，不重放编辑日志），以扁平JSON输出到标准输出
+fn dump_chunk(file: &Path, pos: ChunkPos) -> Result<(), McStreamError> {
+    let header = read_mcs_header(file)?;
+    let index = read_mcs_index(file)?;
+
+    let mut chunk: Option<ChunkData> = None;
+    for entry in index
+        .iter()
+        .filter(|e| e.chunk_x == pos.x && e.chunk_z == pos.z)
+    {
+        let compressed = read_raw_chunk(file, entry)?;
+        let part = if header.has_chunk_format() {
+            chunk::decompress_chunk_prefixed(&compressed, header.compression, pos)?
+        } else {
+            chunk::decompress_chunk(&compressed, header.compression, pos)?
+        };
+        match chunk.as_mut() {
+            Some(chunk) => chunk.merge(part)?,
+            None => chunk = Some(part),
+        }
+    }
+
+    let chunk = chunk.ok_or_else(|| {
+        McStreamError::ValidationError(format!("文件中没有区块 ({}, {})", pos.x, pos.z))
+    })?;
+
+    let data = McsData {
+        header,
+        chunks: HashMap::from([(pos, chunk)]),
+    };
+    let json = json::to_flat_json_ordered(&data, json::BlockOrder::Original)?;
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &json)
+        .map_err(|e| McStreamError::ValidationError(format!("JSON写入错误: {}", e)))?;
+    writeln!(stdout)?;
+
+    Ok(())
+}
+
 /// 打包JSON建筑数据为MCS格式
 fn pack_json_to_mcs(
     input: &PathBuf,
@@ -169,48 +438,16 @@ fn pack_json_to_mcs(
     let reader = BufReader::new(file);
 
     // 解析JSON
-    let data: serde_json::Value = serde_json::from_reader(reader)
+    let json: serde_json::Value = serde_json::from_reader(reader)
         .map_err(|e| McStreamError::ValidationError(format!("JSON解析错误: {}", e)))?;
 
+    // 转换为MCS数据
+    let data = json::from_flat_json(&json, compression)?;
+
     // 创建MCS编码器
     let mut encoder = McsEncoder::new(compression);
-
-    // 处理方块数据
-    if let Some(blocks) = data.get("blocks").and_then(|b| b.as_array()) {
-        for block in blocks {
-            let block_id = block
-                .get("id")
-                .and_then(|id| id.as_str())
-                .ok_or_else(|| McStreamError::ValidationError("方块缺少id字段".to_string()))?
-                .to_string();
-
-            let pos = block
-                .get("pos")
-                .and_then(|p| p.as_array())
-                .ok_or_else(|| McStreamError::ValidationError("方块缺少pos字段".to_string()))?;
-
-            if pos.len() != 3 {
-                return Err(McStreamError::ValidationError(
-                    "方块坐标格式错误".to_string(),
-                ));
-            }
-
-            let x = pos[0].as_i64().unwrap_or(0) as i32;
-            let y = pos[1].as_i64().unwrap_or(0) as i32;
-            let z = pos[2].as_i64().unwrap_or(0) as i32;
-
-            // 处理NBT数据
-            let nbt = block
-                .get("nbt")
-                .map(|n| {
-                    serde_json::to_vec(n).map_err(|e| {
-                        McStreamError::ValidationError(format!("无法序列化NBT: {}", e))
-                    })
-                })
-                .transpose()?;
-
-            encoder.add_block(block_id, x, y, z, nbt)?;
-        }
+    for chunk in data.chunks.into_values() {
+        encoder.add_chunk(chunk)?;
     }
 
     // 写入文件
@@ -220,101 +457,356 @@ fn pack_json_to_mcs(
 }
 
 /// 解包MCS文件为JSON格式
-fn unpack_mcs_to_json(input: &PathBuf, output: &PathBuf) -> Result<(), McStreamError> {
+fn unpack_mcs_to_json(
+    input: &PathBuf,
+    output: &PathBuf,
+    order: json::BlockOrder,
+) -> Result<(), McStreamError> {
     // 读取MCS文件
     let decoder = McsDecoder::from_file(input)?;
 
-    // 解析并获取数据
-    let chunks = decoder.get_chunks();
-
-    // 构建JSON对象
-    let mut blocks = Vec::new();
-
-    for chunk in chunks.values() {
-        for block in &chunk.blocks {
-            // 获取方块ID
-            let block_id = chunk
-                .palette
-                .get(block.palette_index as usize)
-                .ok_or_else(|| McStreamError::ValidationError("无效的调色板索引".to_string()))?;
-
-            // 计算全局坐标
-            let x = (chunk.pos.x * 16) + block.pos.x as i32;
-            let y = block.pos.actual_y();
-            let z = (chunk.pos.z * 16) + block.pos.z as i32;
-
-            // 转换NBT数据
-            let nbt = if let Some(nbt_data) = &block.nbt {
-                serde_json::from_slice::<serde_json::Value>(nbt_data)
-                    .map_err(|e| McStreamError::ValidationError(format!("NBT解析错误: {}", e)))?
-            } else {
-                serde_json::Value::Null
-            };
+    // 有编辑日志时输出重放后的当前状态
+    let data = if decoder.journal().is_empty() {
+        decoder.into_mcs_data()
+    } else {
+        decoder.replay()?
+    };
+
+    // 逐个方块写入文件，不在内存中构建完整的JSON
+    let file = File::create(output)?;
+    let mut writer = BufWriter::new(file);
+    json::write_flat_json(&data, order, &mut writer)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn compression_name(compression: u8) -> &'static str {
+    match compression {
+        0 => "无压缩",
+        1 => "Zstandard",
+        2 => "LZ4",
+        3 => "Brotli",
+        4 => "LZ4（块格式）",
+        0x80..=0xFF => "自定义",
+        _ => "未知",
+    }
+}
+
+// 以空格分隔的十六进制字节，超过 `max` 字节时截断
+fn hex_bytes(bytes: &[u8], max: usize) -> String {
+    let mut hex: Vec<String> = bytes
+        .iter()
+        .take(max)
+        .map(|b| format!("{:02X}", b))
+        .collect();
+    if bytes.len() > max {
+        hex.push("..".to_string());
+    }
+    hex.join(" ")
+}
+
+// 取文件中 `start` 起 `len` 字节，超出文件时报告该区域
+fn region<'a>(
+    bytes: &'a [u8],
+    start: usize,
+    len: usize,
+    what: &str,
+) -> Result<&'a [u8], McStreamError> {
+    bytes.get(start..start.saturating_add(len)).ok_or_else(|| {
+        McStreamError::ValidationError(format!(
+            "{}（偏移 0x{:08X}，{} 字节）超出文件范围，文件只有 {} 字节",
+            what,
+            start,
+            len,
+            bytes.len()
+        ))
+    })
+}
+
+// 按布局读取字段值（不超过4字节）
+fn field_value(bytes: &[u8], field: &FieldLayout) -> u32 {
+    let raw = &bytes[field.offset..field.offset + field.size];
+    let fold = |acc: u32, b: &u8| acc << 8 | *b as u32;
+    match field.endian {
+        Endian::Little => raw.iter().rev().fold(0, fold),
+        _ => raw.iter().fold(0, fold),
+    }
+}
 
-            // 创建方块对象
-            let block_obj = serde_json::json!({
-                "id": block_id,
-                "pos": [x, y, z],
-                "nbt": nbt
+/// 按字段列出头部、索引表、区块与尾部，每行以文件中的字节偏移开头
+///
+/// 头部与索引条目逐个字段显示原始字节与解析值；区块解压后给出格式字节、调色板与方块概况，
+/// 解压或解析失败的区块只显示错误，不影响其余部分。
+fn hexdump(file: &Path) -> Result<(), McStreamError> {
+    let header = read_mcs_header(file)?;
+    let index = read_mcs_index(file)?;
+    let bytes = std::fs::read(file)?;
+
+    let header_bytes = region(&bytes, 0, HEADER_SIZE, "头部")?;
+
+    println!("=== 头部 @0x{:08X}（{} 字节）===", 0, HEADER_SIZE);
+    for field in LAYOUT {
+        let raw = &header_bytes[field.offset..field.offset + field.size];
+        let value = match field.name {
+            "Magic" => format!("{:?}", String::from_utf8_lossy(raw)),
+            "Version" => format!("{}.{}", header.version >> 8, header.version & 0xFF),
+            "Compression" => compression_name(header.compression).to_string(),
+            "Flags" => {
+                let names = [
+                    (header.has_signature(), "签名"),
+                    (header.has_journal(), "编辑日志"),
+                    (header.has_tree_hash(), "树形哈希"),
+                    (header.has_chunk_format(), "区块格式字节"),
+                    (header.has_palette_bloom(), "调色板布隆过滤器"),
+                    (header.has_origin(), "采集原点"),
+                    (header.has_dictionary(), "压缩字典"),
+                    (header.has_framed_footer(), "尾部分帧"),
+                ];
+                let names: Vec<&str> = names
+                    .iter()
+                    .filter(|(set, _)| *set)
+                    .map(|(_, name)| *name)
+                    .collect();
+                format!("{:08b} [{}]", header.flags, names.join(", "))
+            }
+            _ => {
+                let value = field_value(header_bytes, field);
+                format!("{} (0x{:X})", value, value)
+            }
+        };
+        println!(
+            "0x{:08X}  {:<24} {:<18} {}",
+            field.offset,
+            hex_bytes(raw, 8),
+            field.name,
+            value
+        );
+    }
+
+    let index_offset = header.index_table_offset as usize;
+    let entry_size = index_entry_size(header.version);
+    let layout = if header.version >= 0x0101 {
+        INDEX_ENTRY_LAYOUT
+    } else {
+        INDEX_ENTRY_LAYOUT_V1_0
+    };
+    println!(
+        "\n=== 索引表 @0x{:08X}（{} 个条目，每个 {} 字节）===",
+        index_offset,
+        index.len(),
+        entry_size
+    );
+    println!(
+        "0x{:08X}  {:<24} {:<18} {}",
+        index_offset,
+        hex_bytes(
+            region(&bytes, index_offset, INDEX_COUNT_SIZE, "索引表条目数")?,
+            8
+        ),
+        "EntryCount",
+        index.len()
+    );
+    for (i, entry) in index.iter().enumerate() {
+        let start = index_offset + INDEX_COUNT_SIZE + i * entry_size;
+        let entry_bytes = region(&bytes, start, entry_size, &format!("索引条目 #{}", i))?;
+        let fields: Vec<String> = layout
+            .iter()
+            .map(|field| format!("{}={}", field.name, field_value(entry_bytes, field) as i32))
+            .collect();
+        println!(
+            "0x{:08X}  #{:<4} ({}, {})  数据 0x{:08X}，{} 字节  [{}]",
+            start,
+            i,
+            entry.chunk_x,
+            entry.chunk_z,
+            entry.data_offset,
+            entry.compressed_size,
+            fields.join(" ")
+        );
+    }
+
+    println!("\n=== 区块数据 ===");
+    let mut by_offset: Vec<&ChunkIndexEntry> = index.iter().collect();
+    by_offset.sort_by_key(|entry| entry.data_offset);
+    for entry in by_offset {
+        let pos = ChunkPos::new(entry.chunk_x, entry.chunk_z);
+        let start = entry.data_offset as usize;
+        let Some(compressed) = bytes.get(start..start + entry.compressed_size as usize) else {
+            println!(
+                "0x{:08X}  区块 ({}, {})：{} 字节的数据超出文件范围",
+                start, pos.x, pos.z, entry.compressed_size
+            );
+            continue;
+        };
+        println!(
+            "0x{:08X}  区块 ({}, {})：压缩 {} 字节，开头 {}",
+            start,
+            pos.x,
+            pos.z,
+            compressed.len(),
+            hex_bytes(compressed, 8)
+        );
+
+        let decoded = compression_type_from_u8(header.compression)
+            .and_then(|compression| decompress_data(compressed, compression))
+            .and_then(|raw| {
+                let chunk = if header.has_chunk_format() {
+                    chunk::deserialize_chunk_prefixed(&raw, pos)?
+                } else {
+                    chunk::deserialize_chunk(&raw, pos)?
+                };
+                Ok((raw, chunk))
             });
+        let (raw, chunk) = match decoded {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                println!("            无法解码: {}", e);
+                continue;
+            }
+        };
 
-            blocks.push(block_obj);
+        if header.has_chunk_format() {
+            println!("            区块格式字节: 0x{:02X}", raw[0]);
+        }
+        let nbt_count = chunk.blocks.iter().filter(|b| b.nbt.is_some()).count();
+        let overlay_count = chunk.blocks.iter().filter(|b| b.overlay.is_some()).count();
+        println!(
+            "            解压后 {} 字节：调色板 {} 项，方块 {} 个（含NBT {} 个，覆盖层 {} 个）",
+            raw.len(),
+            chunk.palette.len(),
+            chunk.blocks.len(),
+            nbt_count,
+            overlay_count
+        );
+        for (i, block_id) in chunk.palette.iter().enumerate().take(8) {
+            println!("              [{}] {}", i, block_id);
+        }
+        if chunk.palette.len() > 8 {
+            println!("              ...（另有 {} 项）", chunk.palette.len() - 8);
         }
     }
 
-    // 创建最终的JSON对象
-    let json = serde_json::json!({
-        "format": "mcs",
-        "version": "1.0",
-        "blocks": blocks
-    });
-
-    // 写入文件
-    let file = File::create(output)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &json)
-        .map_err(|e| McStreamError::ValidationError(format!("JSON写入错误: {}", e)))?;
+    // 尾部从区块数据与索引表中较靠后的一个之后开始
+    let index_end = index_offset + INDEX_COUNT_SIZE + index.len() * entry_size;
+    let footer_start = index
+        .iter()
+        .map(|e| e.data_offset as usize + e.compressed_size as usize)
+        .fold(index_end, usize::max);
+    println!(
+        "\n=== 尾部 @0x{:08X}（{} 字节）===",
+        footer_start,
+        bytes.len().saturating_sub(footer_start)
+    );
+    if footer_start < bytes.len() {
+        println!(
+            "0x{:08X}  {}",
+            footer_start,
+            hex_bytes(&bytes[footer_start..], 16)
+        );
+    }
 
     Ok(())
 }
 
 /// 打印MCS文件信息
 fn print_mcs_info(file: &PathBuf, verbose: bool) -> Result<(), McStreamError> {
-    let decoder = McsDecoder::from_file(file)?;
-    let header = decoder.header();
-    let chunks = decoder.get_chunks();
+    // 基本信息只读取头部和索引表，无需解压区块
+    let info = McsInfo::read(file)?;
+    let header = &info.header;
 
     println!("=== MCS文件信息 ===");
     println!("文件: {}", file.display());
+    println!("文件大小: {} 字节", info.file_size);
     println!("版本: {}.{}", header.version >> 8, header.version & 0xFF);
 
-    let compression = match header.compression {
-        0 => "无压缩",
-        1 => "Zstandard",
-        2 => "LZ4",
-        3 => "Brotli",
-        _ => "未知",
-    };
-    println!("压缩算法: {} ({})", compression, header.compression);
+    println!(
+        "压缩算法: {} ({})",
+        compression_name(header.compression),
+        header.compression
+    );
 
-    let has_signature = (header.flags & 0x01) != 0;
+    let has_signature = header.has_signature();
     println!("是否有签名: {}", if has_signature { "是" } else { "否" });
 
-    println!("区块数量: {}", chunks.len());
+    let has_journal = header.has_journal();
+    println!("是否有编辑日志: {}", if has_journal { "是" } else { "否" });
 
-    let mut total_blocks = 0;
-    for chunk in chunks.values() {
-        total_blocks += chunk.blocks.len();
+    if header.has_dictionary() {
+        println!("压缩字典ID: {:08x}", header.dictionary_id);
     }
-    println!("方块总数: {}", total_blocks);
 
-    if verbose {
+    let unknown = header.flags().unknown();
+    if unknown != McsFlags::empty() {
+        println!("未知标志位: 0x{:02X}", unknown.bits());
+    }
+
+    println!("区块数量: {}", info.chunk_count);
+    println!("区块压缩数据总大小: {} 字节", info.total_compressed_size);
+
+    if verbose && header.has_dictionary() {
+        println!("文件使用压缩字典，无法解码区块查看详细信息");
+    } else if verbose {
+        let (decoder, decode_report) = McsDecoder::from_file_with_report(file)?;
+        for warning in &decode_report.warnings {
+            println!("警告: {}", warning);
+        }
+        let chunks = decoder.chunks_sorted();
+
+        let mut total_blocks = 0;
+        for (_, chunk) in &chunks {
+            total_blocks += chunk.blocks.len();
+        }
+        println!("方块总数: {}", total_blocks);
+
+        let occupancy = decoder.occupancy();
+        if let Some((min, max)) = occupancy.bounds {
+            let (size_x, size_y, size_z) = occupancy.dimensions();
+            println!(
+                "包围盒: ({}, {}, {}) - ({}, {}, {})，尺寸 {}×{}×{}",
+                min.x, min.y, min.z, max.x, max.y, max.z, size_x, size_y, size_z
+            );
+            println!(
+                "体积: {}，填充率: {:.2}%",
+                occupancy.volume,
+                occupancy.fill_ratio * 100.0
+            );
+        }
+
+        if let Some(origin) = decoder.origin() {
+            println!("采集原点: ({}, {}, {})", origin.x, origin.y, origin.z);
+        }
+
+        if let Some(level) = decoder.compression_level() {
+            println!("压缩级别: {}", level);
+        }
+
+        if let Some(extension) = decoder.footer_extension() {
+            println!("尾部扩展数据: {} 字节", extension.len());
+        }
+
+        let report = PaletteReport::from_chunks(chunks.iter().map(|(_, chunk)| *chunk));
+        println!("不同方块ID数: {}", report.distinct_ids);
+        println!(
+            "调色板总大小: {} 字节（全局调色板: {} 字节，预计节省 {} 字节）",
+            report.chunk_palette_bytes, report.global_palette_bytes, report.estimated_bytes_saved
+        );
+
         println!("\n=== 详细信息 ===");
 
-        for (i, (pos, chunk)) in chunks.iter().enumerate() {
+        for (i, (pos, chunk)) in chunks.into_iter().enumerate() {
             println!("区块 #{} ({}, {})", i + 1, pos.x, pos.z);
             println!("  方块数量: {}", chunk.blocks.len());
             println!("  调色板大小: {}", chunk.palette.len());
+            let encoding = match chunk.recommended_encoding() {
+                ChunkEncoding::Sparse => "稀疏",
+                ChunkEncoding::Dense => "稠密",
+            };
+            println!(
+                "  密度: {:.2}%（建议编码: {}）",
+                chunk.density() * 100.0,
+                encoding
+            );
 
             if !chunk.blocks.is_empty() && i < 5 {
                 println!("  方块示例:");
@@ -322,7 +814,7 @@ fn print_mcs_info(file: &PathBuf, verbose: bool) -> Result<(), McStreamError> {
                     let block_id_str = "unknown".to_string();
                     let block_id = chunk
                         .palette
-                        .get(block.palette_index as usize)
+                        .get(block.palette_index)
                         .unwrap_or(&block_id_str);
                     println!(
                         "    #{}: {} @ ({}, {}, {})",
@@ -0,0 +1,95 @@
+// 在内存中编码与解码MCS数据，不访问文件系统
+// 供没有文件系统的目标（如浏览器中经wasm-bindgen调用的wasm32-unknown-unknown）使用，区块按顺序解码
+
+use crate::{
+    compression::compression_type_from_u8,
+    error::McStreamError,
+    header::McsFlags,
+    packer::McsEncoder,
+    types::McsData,
+    unpacker::{
+        decode_raw_chunk_with, insert_chunk, read_validated_index_from, validate_dictionary,
+        DecodeOptions, GZIP_MAGIC,
+    },
+};
+use flate2::read::GzDecoder;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+/// 将 `data` 编码为完整的MCS文件内容
+///
+/// 压缩算法取自 `data.header`，树形哈希、变长编码与调色板布隆过滤器随头部标志写入；
+/// 签名、编辑日志、采集原点与压缩字典不在 `McsData` 中，不会写入。
+pub fn encode_to_bytes(data: &McsData) -> Result<Vec<u8>, McStreamError> {
+    let header = &data.header;
+    let mut encoder = McsEncoder::new(compression_type_from_u8(header.compression)?);
+    if header.has_tree_hash() {
+        encoder = encoder.with_tree_hash();
+    }
+    if header.has_chunk_format() {
+        encoder = encoder.with_varint_encoding();
+    }
+    if header.has_palette_bloom() {
+        encoder = encoder.with_palette_bloom();
+    }
+    for chunk in data.chunks.values() {
+        encoder.add_chunk(chunk.clone())?;
+    }
+
+    let mut bytes = Vec::new();
+    encoder.write_to_writer(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// 从内存中的MCS文件内容解码全部区块，效果同 [`McsDecoder::from_file`](crate::McsDecoder::from_file)
+///
+/// 支持gzip包裹的数据；需要压缩字典或外部ID表的文件返回错误，编辑日志不回放。
+pub fn decode_from_bytes(bytes: &[u8]) -> Result<McsData, McStreamError> {
+    if bytes.starts_with(&GZIP_MAGIC) {
+        let mut unwrapped = Vec::new();
+        let limit = u32::MAX as u64 + 1;
+        GzDecoder::new(bytes)
+            .take(limit)
+            .read_to_end(&mut unwrapped)?;
+        return decode_from_bytes(&unwrapped);
+    }
+
+    let options = DecodeOptions::default();
+    let (header, entries) = read_validated_index_from(Cursor::new(bytes), bytes.len() as u64)?;
+
+    let unknown = header.flags().unknown();
+    if unknown != McsFlags::empty() {
+        log::warn!(
+            "数据含有未知的头部标志位 0x{:02X}，对应的数据将被忽略",
+            unknown.bits()
+        );
+    }
+    validate_dictionary(&header, &options)?;
+
+    // 各区块的范围已在读取索引时校验
+    let mut chunks = HashMap::new();
+    for entry in &entries {
+        let start = entry.data_offset as usize;
+        let compressed = &bytes[start..start + entry.compressed_size as usize];
+        let (mut chunk, _, _) = decode_raw_chunk_with(
+            compressed,
+            entry,
+            &header,
+            &options,
+            None,
+            &mut str::to_owned,
+        )?;
+        for block in &mut chunk.blocks {
+            block.decompress_nbt_in_place()?;
+        }
+        log::debug!(
+            "解码区块 ({}, {})：{} 个方块",
+            entry.chunk_x,
+            entry.chunk_z,
+            chunk.blocks.len()
+        );
+        insert_chunk(&mut chunks, chunk)?;
+    }
+
+    Ok(McsData { header, chunks })
+}
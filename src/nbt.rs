@@ -1,8 +1,10 @@
-// 这是一个简单的NBT处理工具，仅用于存储和传递NBT数据
+// 这是一个简单的NBT处理工具，用于存储和传递NBT数据
 // MCStream格式本身并不处理NBT内容，只是将其作为二进制数据保存
-// 实际项目中可能需要更完整的NBT解析库
+// 格式转换等场景可使用 NbtValue 解析和生成完整的NBT树
 
 use crate::error::McStreamError;
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+use std::io::{Cursor, Read, Write};
 
 /// 验证NBT数据是否有效（简单验证）
 pub fn validate_nbt(data: &[u8]) -> Result<(), McStreamError> {
@@ -18,6 +20,27 @@ pub fn validate_nbt(data: &[u8]) -> Result<(), McStreamError> {
     }
 }
 
+/// 完整解析NBT数据以验证其结构
+///
+/// 要求数据恰好是一个带名称的根标签：长度不越界、复合标签正确结束、
+/// 列表元素类型一致，且末尾没有多余字节。
+pub fn validate_deep(data: &[u8]) -> Result<(), McStreamError> {
+    let mut cursor = Cursor::new(data);
+    match read_named::<BigEndian, _>(&mut cursor) {
+        Ok(_) => {}
+        Err(McStreamError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            return Err(McStreamError::NbtError("NBT数据不完整".to_string()));
+        }
+        Err(e) => return Err(e),
+    }
+
+    if cursor.position() as usize != data.len() {
+        return Err(McStreamError::NbtError("NBT数据末尾有多余字节".to_string()));
+    }
+
+    Ok(())
+}
+
 /// NBT标签类型枚举
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -71,3 +94,362 @@ pub fn get_nbt_root_type(data: &[u8]) -> Result<NbtTagType, McStreamError> {
 
     NbtTagType::try_from(data[0])
 }
+
+/// 解析时允许的最大嵌套深度，防止恶意数据导致栈溢出
+pub const MAX_NBT_DEPTH: usize = 512;
+
+/// NBT标签值
+#[derive(Debug, Clone, PartialEq)]
+pub enum NbtValue {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<NbtValue>), // 元素类型由第一个元素决定，空列表写出为End类型
+    Compound(Vec<(String, NbtValue)>), // 保持文件中的字段顺序
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl NbtValue {
+    /// 获取标签类型
+    pub fn tag_type(&self) -> NbtTagType {
+        match self {
+            NbtValue::Byte(_) => NbtTagType::Byte,
+            NbtValue::Short(_) => NbtTagType::Short,
+            NbtValue::Int(_) => NbtTagType::Int,
+            NbtValue::Long(_) => NbtTagType::Long,
+            NbtValue::Float(_) => NbtTagType::Float,
+            NbtValue::Double(_) => NbtTagType::Double,
+            NbtValue::ByteArray(_) => NbtTagType::ByteArray,
+            NbtValue::String(_) => NbtTagType::String,
+            NbtValue::List(_) => NbtTagType::List,
+            NbtValue::Compound(_) => NbtTagType::Compound,
+            NbtValue::IntArray(_) => NbtTagType::IntArray,
+            NbtValue::LongArray(_) => NbtTagType::LongArray,
+        }
+    }
+
+    /// 获取复合标签中的字段
+    pub fn get(&self, name: &str) -> Option<&NbtValue> {
+        match self {
+            NbtValue::Compound(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// 作为整数读取（Byte/Short/Int）
+    pub fn as_i32(&self) -> Option<i32> {
+        match self {
+            NbtValue::Byte(v) => Some(*v as i32),
+            NbtValue::Short(v) => Some(*v as i32),
+            NbtValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// 作为字符串读取
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            NbtValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// 作为列表读取
+    pub fn as_list(&self) -> Option<&[NbtValue]> {
+        match self {
+            NbtValue::List(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// 按路径查找子标签，路径格式见 [`crate::nbt::query`]
+    pub fn query(&self, path: &str) -> Result<Option<NbtValue>, McStreamError> {
+        let steps = parse_path(path)?;
+        let Some((last, parents)) = steps.split_last() else {
+            return Ok(Some(self.clone()));
+        };
+
+        let mut current = self;
+        for step in parents {
+            let next = match (step, current) {
+                (PathStep::Field(name), _) => current.get(name),
+                (PathStep::Index(i), NbtValue::List(items)) => items.get(*i),
+                _ => None,
+            };
+            match next {
+                Some(value) => current = value,
+                None => return Ok(None),
+            }
+        }
+
+        // 数组元素不是独立的标签，最后一步单独取出
+        Ok(match (last, current) {
+            (PathStep::Field(name), _) => current.get(name).cloned(),
+            (PathStep::Index(i), NbtValue::List(items)) => items.get(*i).cloned(),
+            (PathStep::Index(i), NbtValue::ByteArray(v)) => v.get(*i).map(|v| NbtValue::Byte(*v)),
+            (PathStep::Index(i), NbtValue::IntArray(v)) => v.get(*i).map(|v| NbtValue::Int(*v)),
+            (PathStep::Index(i), NbtValue::LongArray(v)) => v.get(*i).map(|v| NbtValue::Long(*v)),
+            _ => None,
+        })
+    }
+}
+
+// 路径中的一步：复合标签的字段名或列表（数组）下标
+enum PathStep<'a> {
+    Field(&'a str),
+    Index(usize),
+}
+
+// 解析 `Items[0].id` 形式的路径：字段名以 `.` 分隔，每个字段名后可跟若干 `[下标]`
+fn parse_path(path: &str) -> Result<Vec<PathStep<'_>>, McStreamError> {
+    let invalid = || McStreamError::NbtError(format!("无效的NBT路径: {}", path));
+
+    let mut steps = Vec::new();
+    if path.is_empty() {
+        return Ok(steps);
+    }
+
+    for segment in path.split('.') {
+        let (name, mut rest) = segment.split_at(segment.find('[').unwrap_or(segment.len()));
+        if name.is_empty() && (rest.is_empty() || !steps.is_empty()) {
+            return Err(invalid());
+        }
+        if !name.is_empty() {
+            steps.push(PathStep::Field(name));
+        }
+
+        while !rest.is_empty() {
+            let close = rest.find(']').ok_or_else(invalid)?;
+            let index = rest
+                .get(1..close)
+                .and_then(|i| i.parse().ok())
+                .filter(|_| rest.starts_with('['))
+                .ok_or_else(invalid)?;
+            steps.push(PathStep::Index(index));
+            rest = &rest[close + 1..];
+        }
+    }
+
+    Ok(steps)
+}
+
+/// 读取带名称的根标签（Java版为大端，基岩版为小端）
+pub fn read_named<B: ByteOrder, R: Read>(
+    reader: &mut R,
+) -> Result<(String, NbtValue), McStreamError> {
+    let tag_type = NbtTagType::try_from(reader.read_u8()?)?;
+    if tag_type == NbtTagType::End {
+        return Err(McStreamError::NbtError("根标签不能为End".to_string()));
+    }
+
+    let name = read_string::<B, R>(reader)?;
+    let value = read_payload::<B, R>(reader, tag_type, 0)?;
+
+    Ok((name, value))
+}
+
+/// 写入带名称的根标签
+pub fn write_named<B: ByteOrder, W: Write>(
+    writer: &mut W,
+    name: &str,
+    value: &NbtValue,
+) -> Result<(), McStreamError> {
+    writer.write_u8(value.tag_type() as u8)?;
+    write_string::<B, W>(writer, name)?;
+    write_payload::<B, W>(writer, value)
+}
+
+/// 解析Java版（大端）二进制NBT数据
+pub fn parse(data: &[u8]) -> Result<(String, NbtValue), McStreamError> {
+    read_named::<BigEndian, _>(&mut Cursor::new(data))
+}
+
+/// 解析Java版二进制NBT数据并按路径查找子标签
+///
+/// 路径从根标签的内容开始，字段名以 `.` 分隔，列表与数组用 `[下标]` 访问，如 `Items[0].id`；
+/// 空路径返回根标签本身。路径不存在（字段缺失、下标越界或类型不符）时返回 `None`，
+/// 数据无法解析或路径格式错误时返回错误。
+pub fn query(data: &[u8], path: &str) -> Result<Option<NbtValue>, McStreamError> {
+    let (_, root) = parse(data)?;
+    root.query(path)
+}
+
+/// 序列化为Java版（大端）二进制NBT数据
+pub fn to_bytes(name: &str, value: &NbtValue) -> Result<Vec<u8>, McStreamError> {
+    let mut buffer = Vec::new();
+    write_named::<BigEndian, _>(&mut buffer, name, value)?;
+    Ok(buffer)
+}
+
+fn read_string<B: ByteOrder, R: Read>(reader: &mut R) -> Result<String, McStreamError> {
+    let len = reader.read_u16::<B>()?;
+    let mut buffer = vec![0u8; len as usize];
+    reader.read_exact(&mut buffer)?;
+    String::from_utf8(buffer)
+        .map_err(|_| McStreamError::NbtError("非UTF-8编码的字符串".to_string()))
+}
+
+fn write_string<B: ByteOrder, W: Write>(writer: &mut W, value: &str) -> Result<(), McStreamError> {
+    if value.len() > u16::MAX as usize {
+        return Err(McStreamError::NbtError("字符串长度超过上限".to_string()));
+    }
+    writer.write_u16::<B>(value.len() as u16)?;
+    writer.write_all(value.as_bytes())?;
+    Ok(())
+}
+
+fn read_len<B: ByteOrder, R: Read>(reader: &mut R) -> Result<usize, McStreamError> {
+    let len = reader.read_i32::<B>()?;
+    if len < 0 {
+        return Err(McStreamError::NbtError(format!("无效的长度: {}", len)));
+    }
+    Ok(len as usize)
+}
+
+fn write_len<B: ByteOrder, W: Write>(writer: &mut W, len: usize) -> Result<(), McStreamError> {
+    let len =
+        i32::try_from(len).map_err(|_| McStreamError::NbtError("数组长度超过上限".to_string()))?;
+    writer.write_i32::<B>(len)?;
+    Ok(())
+}
+
+// 长度来自不可信数据，预分配时加以限制，实际容量随读取增长
+fn bounded_capacity(len: usize) -> usize {
+    len.min(4096)
+}
+
+fn read_payload<B: ByteOrder, R: Read>(
+    reader: &mut R,
+    tag_type: NbtTagType,
+    depth: usize,
+) -> Result<NbtValue, McStreamError> {
+    if depth > MAX_NBT_DEPTH {
+        return Err(McStreamError::NbtError("NBT嵌套过深".to_string()));
+    }
+
+    let value = match tag_type {
+        NbtTagType::End => {
+            return Err(McStreamError::NbtError("意外的End标签".to_string()));
+        }
+        NbtTagType::Byte => NbtValue::Byte(reader.read_i8()?),
+        NbtTagType::Short => NbtValue::Short(reader.read_i16::<B>()?),
+        NbtTagType::Int => NbtValue::Int(reader.read_i32::<B>()?),
+        NbtTagType::Long => NbtValue::Long(reader.read_i64::<B>()?),
+        NbtTagType::Float => NbtValue::Float(reader.read_f32::<B>()?),
+        NbtTagType::Double => NbtValue::Double(reader.read_f64::<B>()?),
+        NbtTagType::ByteArray => {
+            let len = read_len::<B, R>(reader)?;
+            let mut values = Vec::with_capacity(bounded_capacity(len));
+            for _ in 0..len {
+                values.push(reader.read_i8()?);
+            }
+            NbtValue::ByteArray(values)
+        }
+        NbtTagType::String => NbtValue::String(read_string::<B, R>(reader)?),
+        NbtTagType::List => {
+            let element_type = NbtTagType::try_from(reader.read_u8()?)?;
+            let len = read_len::<B, R>(reader)?;
+            if element_type == NbtTagType::End && len > 0 {
+                return Err(McStreamError::NbtError(
+                    "非空列表的元素类型不能为End".to_string(),
+                ));
+            }
+            let mut values = Vec::with_capacity(bounded_capacity(len));
+            for _ in 0..len {
+                values.push(read_payload::<B, R>(reader, element_type, depth + 1)?);
+            }
+            NbtValue::List(values)
+        }
+        NbtTagType::Compound => {
+            let mut fields = Vec::new();
+            loop {
+                let field_type = NbtTagType::try_from(reader.read_u8()?)?;
+                if field_type == NbtTagType::End {
+                    break;
+                }
+                let name = read_string::<B, R>(reader)?;
+                let value = read_payload::<B, R>(reader, field_type, depth + 1)?;
+                fields.push((name, value));
+            }
+            NbtValue::Compound(fields)
+        }
+        NbtTagType::IntArray => {
+            let len = read_len::<B, R>(reader)?;
+            let mut values = Vec::with_capacity(bounded_capacity(len));
+            for _ in 0..len {
+                values.push(reader.read_i32::<B>()?);
+            }
+            NbtValue::IntArray(values)
+        }
+        NbtTagType::LongArray => {
+            let len = read_len::<B, R>(reader)?;
+            let mut values = Vec::with_capacity(bounded_capacity(len));
+            for _ in 0..len {
+                values.push(reader.read_i64::<B>()?);
+            }
+            NbtValue::LongArray(values)
+        }
+    };
+
+    Ok(value)
+}
+
+fn write_payload<B: ByteOrder, W: Write>(
+    writer: &mut W,
+    value: &NbtValue,
+) -> Result<(), McStreamError> {
+    match value {
+        NbtValue::Byte(v) => writer.write_i8(*v)?,
+        NbtValue::Short(v) => writer.write_i16::<B>(*v)?,
+        NbtValue::Int(v) => writer.write_i32::<B>(*v)?,
+        NbtValue::Long(v) => writer.write_i64::<B>(*v)?,
+        NbtValue::Float(v) => writer.write_f32::<B>(*v)?,
+        NbtValue::Double(v) => writer.write_f64::<B>(*v)?,
+        NbtValue::ByteArray(values) => {
+            write_len::<B, W>(writer, values.len())?;
+            for v in values {
+                writer.write_i8(*v)?;
+            }
+        }
+        NbtValue::String(v) => write_string::<B, W>(writer, v)?,
+        NbtValue::List(values) => {
+            let element_type = values.first().map_or(NbtTagType::End, |v| v.tag_type());
+            if values.iter().any(|v| v.tag_type() != element_type) {
+                return Err(McStreamError::NbtError("列表元素类型不一致".to_string()));
+            }
+            writer.write_u8(element_type as u8)?;
+            write_len::<B, W>(writer, values.len())?;
+            for v in values {
+                write_payload::<B, W>(writer, v)?;
+            }
+        }
+        NbtValue::Compound(fields) => {
+            for (name, v) in fields {
+                writer.write_u8(v.tag_type() as u8)?;
+                write_string::<B, W>(writer, name)?;
+                write_payload::<B, W>(writer, v)?;
+            }
+            writer.write_u8(NbtTagType::End as u8)?;
+        }
+        NbtValue::IntArray(values) => {
+            write_len::<B, W>(writer, values.len())?;
+            for v in values {
+                writer.write_i32::<B>(*v)?;
+            }
+        }
+        NbtValue::LongArray(values) => {
+            write_len::<B, W>(writer, values.len())?;
+            for v in values {
+                writer.write_i64::<B>(*v)?;
+            }
+        }
+    }
+
+    Ok(())
+}
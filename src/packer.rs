@@ -1,23 +1,115 @@
 use crate::{
-    chunk::{compress_chunk, validate_local_pos, write_chunk_index},
+    bloom::{write_palette_blooms, PaletteBloom},
+    chunk::{
+        deserialize_chunk_with, serialize_chunk, serialize_chunk_with_level, validate_local_pos,
+        write_chunk_index_as, CHUNK_FORMAT_COMPRESSION_LEVEL, CHUNK_FORMAT_ID_TABLE,
+        CHUNK_FORMAT_NARROW_INDEX, CHUNK_FORMAT_SPATIAL_INDEX, CHUNK_FORMAT_VARINT,
+        CHUNK_FORMAT_WIDE_INDEX, NARROW_INDEX_PALETTE_LIMIT,
+    },
+    compression::{
+        compress_data, compress_data_with, decompress_data_limited_with, dictionary_id,
+        CompressOptions,
+    },
     error::McStreamError,
-    header::write_header,
-    types::{Block, ChunkData, ChunkIndexEntry, ChunkPos, LocalBlockPos},
-    CompressionType,
+    format::{index_entry_size, HEADER_SIZE, INDEX_COUNT_SIZE},
+    header::{update_dictionary_id, update_index_table_offset, write_header_as, McsFlags},
+    integrity::{chunk_hashes, write_tree_hash, Hash, TreeHash},
+    journal::{apply_event, write_event, JournalEvent},
+    nbt,
+    palette::{self, IdPolicy, IdTable, Palette, MAX_PALETTE_LEN},
+    types::{
+        Block, BlockPos, ChunkData, ChunkEncoding, ChunkIndexEntry, ChunkPos, McsData, McsHeader,
+    },
+    unpacker::{read_validated_index, McsDecoder},
+    CompressionType, MCS_MIN_VERSION, MCS_VERSION,
 };
 use byteorder::{LittleEndian, WriteBytesExt};
-use std::collections::HashMap;
-use std::fs::OpenOptions;
-use std::io::BufWriter;
-use std::io::{Seek, Write};
-use std::path::Path;
+use flate2::{write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// 方块过滤器，可修改方块ID与NBT，返回false时丢弃该方块
+pub type BlockFilter = Box<dyn FnMut(&mut String, &mut Option<Vec<u8>>) -> bool + Send>;
+
+/// 签名函数，接收树形哈希的根并返回签名数据
+pub type Signer = Box<dyn Fn(&Hash) -> Vec<u8> + Send>;
+
+// 区块拆分后各部分的方块数与数据
+type ChunkParts = Vec<(u32, Vec<u8>)>;
+
+/// 一次写入的结果摘要
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteSummary {
+    pub bytes_written: u64,      // 文件总字节数
+    pub index_table_offset: u32, // 区块索引表偏移
+    pub chunk_count: usize,      // 区块数（拆分的区块按一个计）
+    pub data_hash: Hash,         // 整个文件的SHA-256
+}
+
+// 边写入边计算哈希与字节数
+pub(crate) struct HashingWriter<'a, W: Write> {
+    inner: &'a mut W,
+    pub(crate) hasher: Sha256,
+    pub(crate) bytes_written: u64,
+}
+
+impl<'a, W: Write> HashingWriter<'a, W> {
+    pub(crate) fn new(inner: &'a mut W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            bytes_written: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for HashingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes_written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
 
 /// MCS编码器，用于将建筑数据打包成MCS格式
 pub struct McsEncoder {
     compression: CompressionType,
     has_signature: bool,
     chunks: HashMap<ChunkPos, ChunkData>,
+    dirty: HashSet<ChunkPos>, // 上次保存后修改过的区块
+    raw_chunks: HashMap<ChunkPos, RawChunk>,
     signature: Option<Vec<u8>>,
+    nbt_compression: Option<CompressionType>,
+    block_filter: Option<BlockFilter>,
+    journal: Vec<JournalEvent>,
+    tree_hash: bool,
+    palette_bloom: bool,
+    signer: Option<Signer>,
+    chunk_format: u8,
+    tail_index: bool,
+    canonical: bool,
+    max_chunk_bytes: Option<usize>,
+    validate_nbt_on_add: bool,
+    id_policy: IdPolicy,
+    allowed_namespaces: Option<Vec<String>>,
+    cursor: BlockPos,
+    origin: Option<BlockPos>,
+    dictionary: Option<Vec<u8>>,
+    id_table: Option<IdTable>,
+    long_window_log: Option<u32>,
+    compression_level: Option<i32>,
+    framed_footer: bool,
+    footer_extension: Vec<u8>,
+    target_version: u16,
 }
 
 impl McsEncoder {
@@ -27,7 +119,31 @@ impl McsEncoder {
             compression,
             has_signature: false,
             chunks: HashMap::new(),
+            dirty: HashSet::new(),
+            raw_chunks: HashMap::new(),
             signature: None,
+            nbt_compression: None,
+            block_filter: None,
+            journal: Vec::new(),
+            tree_hash: false,
+            palette_bloom: false,
+            chunk_format: 0,
+            tail_index: false,
+            canonical: false,
+            max_chunk_bytes: None,
+            validate_nbt_on_add: false,
+            id_policy: IdPolicy::default(),
+            allowed_namespaces: None,
+            signer: None,
+            cursor: BlockPos::new(0, 0, 0),
+            origin: None,
+            dictionary: None,
+            id_table: None,
+            long_window_log: None,
+            compression_level: None,
+            framed_footer: false,
+            footer_extension: Vec::new(),
+            target_version: MCS_VERSION,
         }
     }
 
@@ -38,6 +154,356 @@ impl McsEncoder {
         self
     }
 
+    /// 在区块数据之后写入树形哈希（并行计算每个区块的SHA-256）
+    pub fn with_tree_hash(mut self) -> Self {
+        self.tree_hash = true;
+        self
+    }
+
+    /// 在尾部写入每个区块调色板的布隆过滤器，便于不解压区块查询方块ID
+    pub fn with_palette_bloom(mut self) -> Self {
+        self.palette_bloom = true;
+        self
+    }
+
+    /// 记录采集建筑时的世界坐标原点，粘贴时由 `McsDecoder::paste_offset` 计算平移量
+    pub fn with_origin(mut self, origin: BlockPos) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// 使用Zstandard压缩字典压缩区块数据（见 `train_dictionary`），只适用于Zstandard
+    ///
+    /// 字典本身不写入文件，头部只记录其ID，解码时须提供相同的字典。
+    pub fn with_dictionary(mut self, dictionary: Vec<u8>) -> Self {
+        self.dictionary = Some(dictionary);
+        self
+    }
+
+    /// 区块压缩启用Zstandard长距离匹配，窗口为 `2^window_log` 字节（见 `LONG_WINDOW_LOG_RANGE`）
+    ///
+    /// 对含大范围重复结构的大区块能提高压缩率，解压无需额外设置；只适用于Zstandard。
+    pub fn with_long_distance_matching(mut self, window_log: u32) -> Self {
+        self.long_window_log = Some(window_log);
+        self
+    }
+
+    /// 区块压缩使用指定的压缩级别（见 `compression_level_range`），并在每个区块中记录该级别
+    ///
+    /// 只适用于Zstandard与Brotli，其他算法写入时返回错误；记录的级别只供查看
+    /// （见 [`McsDecoder::compression_level`]），解码不需要它。单独压缩的NBT仍使用默认级别。
+    pub fn with_compression_level(mut self, level: i32) -> Self {
+        self.chunk_format |= CHUNK_FORMAT_COMPRESSION_LEVEL;
+        self.compression_level = Some(level);
+        self
+    }
+
+    /// 设置签名函数，写入时对树形哈希的根签名
+    ///
+    /// 会同时启用树形哈希，并覆盖 `with_signature` 设置的固定签名。
+    pub fn with_signer(mut self, signer: Signer) -> Self {
+        self.has_signature = true;
+        self.tree_hash = true;
+        self.signer = Some(signer);
+        self
+    }
+
+    /// 区块内的计数、长度与调色板索引使用变长整数，减小稀疏区块的体积
+    ///
+    /// 未启用时输出与旧版格式逐字节一致。
+    pub fn with_varint_encoding(mut self) -> Self {
+        self.chunk_format |= CHUNK_FORMAT_VARINT;
+        self
+    }
+
+    /// 每个区块附带按坐标排序的方块下标，解码后 [`McsDecoder::get_block`](crate::McsDecoder::get_block) 无需再建立索引
+    ///
+    /// 每个方块增加4字节未压缩数据，使用变长编码时更少。
+    pub fn with_spatial_index(mut self) -> Self {
+        self.chunk_format |= CHUNK_FORMAT_SPATIAL_INDEX;
+        self
+    }
+
+    /// 调色板中在 `table` 里的方块ID只存储编号，其余ID仍以字符串存储
+    ///
+    /// 表本身不写入文件，解码时须提供相同的表（见 `McsDecoderBuilder::id_table`）。
+    pub fn with_id_table(mut self, table: &IdTable) -> Self {
+        self.chunk_format |= CHUNK_FORMAT_ID_TABLE;
+        self.id_table = Some(table.clone());
+        self
+    }
+
+    /// 调色板不超过256项的区块用单字节存储调色板索引，其余区块仍为2字节
+    ///
+    /// 每个区块在区块格式字节中记录所用的宽度。
+    pub fn with_narrow_palette_indices(mut self) -> Self {
+        self.chunk_format |= CHUNK_FORMAT_NARROW_INDEX;
+        self
+    }
+
+    /// 调色板超过65535项的区块用4字节存储调色板大小与索引，其余区块仍为2字节（或启用时的单字节）
+    ///
+    /// 未启用时这样的区块写入失败。只有合并了大量方块状态的建筑才需要。
+    pub fn with_wide_palette_indices(mut self) -> Self {
+        self.chunk_format |= CHUNK_FORMAT_WIDE_INDEX;
+        self
+    }
+
+    /// 为签名与扩展数据加上长度前缀，读取方可以忽略签名之后的未知数据
+    pub fn with_framed_footer(mut self) -> Self {
+        self.framed_footer = true;
+        self
+    }
+
+    /// 在尾部写入应用自定义的扩展数据，解码时通过 [`McsDecoder::footer_extension`] 读取
+    ///
+    /// 会同时启用 `with_framed_footer`。
+    pub fn with_footer_extension(mut self, extension: Vec<u8>) -> Self {
+        self.framed_footer = true;
+        self.footer_extension = extension;
+        self
+    }
+
+    /// 按指定的格式版本写入，使旧版本的读取方也能打开输出的文件
+    ///
+    /// 目标为1.0时索引条目不含方块数；建筑用到了1.0无法表示的内容（树形哈希、编辑日志、
+    /// 覆盖层、区块拆分等）时写入返回错误，而不是悄悄丢弃。
+    pub fn with_target_version(mut self, version: u16) -> Self {
+        self.target_version = version;
+        self
+    }
+
+    /// 规范输出：写入前整理每个区块（见 [`ChunkData::canonicalize`]），内容相同的建筑总是得到相同的字节
+    ///
+    /// 区块本来就按 `(x, z)` 顺序写入，压缩使用固定的级别且单线程执行，结果不受添加顺序与运行环境影响，
+    /// 适合存放在git等版本控制中。`add_raw_chunk` 添加的区块原样写入，不在保证范围内；
+    /// `update_file` 不再复制旧文件中的区块，以免混入未整理的数据。
+    pub fn with_canonical_order(mut self) -> Self {
+        self.canonical = true;
+        self
+    }
+
+    /// 将索引表写在区块数据之后（尾部之前），而不是紧跟头部
+    pub fn with_tail_index(mut self) -> Self {
+        self.tail_index = true;
+        self
+    }
+
+    /// 限制单个区块序列化后（压缩前）的字节数
+    ///
+    /// 超过上限的区块按Y分段拆分为多个同坐标的索引条目，解码时会重新合并。
+    pub fn with_max_chunk_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_chunk_bytes = Some(max_bytes);
+        self
+    }
+
+    /// 添加方块时完整解析NBT数据，拒绝结构无效的NBT
+    ///
+    /// 启用后NBT必须是二进制NBT，JSON形式的NBT（如 `pack` 命令的输入）会被拒绝。
+    pub fn with_nbt_validation(mut self) -> Self {
+        self.validate_nbt_on_add = true;
+        self
+    }
+
+    /// 设置NBT数据单独压缩所用的算法
+    ///
+    /// 启用后每个NBT数据在添加时即被压缩，解码时可保持压缩状态以降低内存占用。
+    pub fn with_nbt_compression(mut self, compression: CompressionType) -> Self {
+        self.nbt_compression = Some(compression);
+        self
+    }
+
+    /// 由密集体素数组构建编码器（Zstandard压缩）
+    ///
+    /// `size` 为 `(x, y, z)` 方向的尺寸，`indices` 按 y → z → x 排列（x 变化最快，y 最慢），
+    /// 与 [`McsDecoder::to_grid`] 的布局相同，每个值为 `palette` 中的索引；空气方块会被跳过，`origin` 为数组原点的世界坐标。
+    pub fn from_voxel_grid(
+        palette: &[String],
+        indices: &[u16],
+        size: (usize, usize, usize),
+        origin: BlockPos,
+    ) -> Result<McsEncoder, McStreamError> {
+        let (size_x, size_y, size_z) = size;
+        let volume = size_x
+            .checked_mul(size_y)
+            .and_then(|v| v.checked_mul(size_z));
+        if volume != Some(indices.len()) {
+            return Err(McStreamError::ValidationError(format!(
+                "体素数量 ({}) 与尺寸 {}×{}×{} 不符",
+                indices.len(),
+                size_x,
+                size_y,
+                size_z
+            )));
+        }
+
+        let is_air: Vec<bool> = palette
+            .iter()
+            .map(|id| id.contains("minecraft:air"))
+            .collect();
+
+        let coord = |origin: i32, offset: usize| {
+            i32::try_from(offset)
+                .ok()
+                .and_then(|offset| origin.checked_add(offset))
+                .ok_or_else(|| McStreamError::ValidationError("体素坐标溢出".to_string()))
+        };
+
+        // 每个区块记录全局调色板索引到区块调色板索引的映射，避免逐个查找字符串
+        let mut chunks: HashMap<ChunkPos, (ChunkData, Vec<Option<u32>>)> = HashMap::new();
+
+        for (i, &index) in indices.iter().enumerate() {
+            let air = *is_air.get(index as usize).ok_or_else(|| {
+                McStreamError::PaletteError(format!("体素调色板索引越界: {}", index))
+            })?;
+            if air {
+                continue;
+            }
+
+            let (gx, gz, gy) = (i % size_x, (i / size_x) % size_z, i / (size_x * size_z));
+            let pos = BlockPos::new(
+                coord(origin.x, gx)?,
+                coord(origin.y, gy)?,
+                coord(origin.z, gz)?,
+            );
+
+            let local_pos = pos.local_pos();
+            validate_local_pos(&local_pos)?;
+
+            let chunk_pos = pos.chunk_pos();
+            let (chunk, mapping) = chunks.entry(chunk_pos).or_insert_with(|| {
+                (
+                    ChunkData {
+                        pos: chunk_pos,
+                        palette: Palette::new(),
+                        blocks: Vec::new(),
+                    },
+                    vec![None; palette.len()],
+                )
+            });
+
+            let palette_index = match mapping[index as usize] {
+                Some(palette_index) => palette_index,
+                None => {
+                    let palette_index = chunk
+                        .palette
+                        .get_or_insert(palette[index as usize].clone())?;
+                    mapping[index as usize] = Some(palette_index);
+                    palette_index
+                }
+            };
+
+            chunk.blocks.push(Block {
+                palette_index,
+                pos: local_pos,
+                nbt: None,
+                nbt_compression: None,
+                overlay: None,
+                tags: 0,
+            });
+        }
+
+        let mut encoder = McsEncoder::new(CompressionType::Zstandard).with_origin(origin);
+        for (chunk, _) in chunks.into_values() {
+            encoder.add_chunk(chunk)?;
+        }
+        Ok(encoder)
+    }
+
+    /// 由解码器的内容构建编码器，保留调色板、NBT、编辑日志、采集原点、区块格式与头部选项
+    ///
+    /// 解码器应以 `keep_nbt_compressed(true)` 打开，NBT的单独压缩方式才能保留。
+    /// 区块按方块ID表编码时沿用解码时提供的表。
+    /// 重新编码后签名不再有效，不会被保留。
+    pub fn from_decoder(
+        decoder: McsDecoder,
+        compression: CompressionType,
+    ) -> Result<McsEncoder, McStreamError> {
+        let header = decoder.header();
+
+        let mut encoder = McsEncoder::new(compression);
+        if header.has_tree_hash() {
+            encoder = encoder.with_tree_hash();
+        }
+        // 区块格式按实际读到的格式字节复制，各区块按调色板大小省略的索引宽度位在写入时同样会被省略
+        let chunk_format = decoder.chunk_format();
+        encoder.chunk_format = chunk_format
+            & (CHUNK_FORMAT_VARINT
+                | CHUNK_FORMAT_NARROW_INDEX
+                | CHUNK_FORMAT_SPATIAL_INDEX
+                | CHUNK_FORMAT_WIDE_INDEX);
+        // 改用其他算法时原来的级别不再适用
+        if let Some(level) = decoder.compression_level() {
+            if compression == decoder.compression_type() {
+                encoder = encoder.with_compression_level(level);
+            }
+        }
+        if chunk_format & CHUNK_FORMAT_ID_TABLE != 0 {
+            match decoder.id_table() {
+                Some(table) => encoder = encoder.with_id_table(table),
+                None => {
+                    log::warn!("原文件的区块按方块ID表编码，但解码器中没有该表，输出不使用ID表")
+                }
+            }
+        }
+        if header.has_palette_bloom() {
+            encoder = encoder.with_palette_bloom();
+        }
+        if let Some(origin) = decoder.origin() {
+            encoder = encoder.with_origin(origin);
+        }
+        if header.index_table_offset as usize != HEADER_SIZE {
+            encoder = encoder.with_tail_index();
+        }
+        if header.has_framed_footer() {
+            encoder = encoder
+                .with_footer_extension(decoder.footer_extension().unwrap_or_default().to_vec());
+        }
+        if header.has_signature() {
+            log::warn!("原文件的签名在重新编码后失效，输出不含签名");
+        }
+
+        for event in decoder.journal() {
+            encoder.record_event(event.clone());
+        }
+        for chunk in decoder.into_mcs_data().chunks.into_values() {
+            encoder.add_chunk(chunk)?;
+        }
+        // 区块与文件中一致，之后用 `update_file` 保存时可直接复制
+        encoder.mark_clean();
+
+        Ok(encoder)
+    }
+
+    /// 设置方块ID超过长度上限时的处理方式，默认返回错误
+    ///
+    /// 作用于 `add_block` 与 `add_block_with_overlay` 的主方块与覆盖层。
+    pub fn with_id_policy(mut self, policy: IdPolicy) -> Self {
+        self.id_policy = policy;
+        self
+    }
+
+    /// 只接受指定命名空间的方块ID，其余ID在添加时返回错误；不含冒号的ID视为 `minecraft`
+    ///
+    /// 作用于 `add_block` 系列方法（主方块与覆盖层）以及 `add_chunk` 的调色板。
+    pub fn with_allowed_namespaces(mut self, namespaces: &[&str]) -> Self {
+        self.allowed_namespaces = Some(namespaces.iter().map(|n| n.to_string()).collect());
+        self
+    }
+
+    /// 设置方块过滤器，在 `add_block` 中对每个方块调用
+    ///
+    /// 过滤器先于空气过滤执行：它能看到输入中的空气方块，
+    /// 且被改写为 `minecraft:air` 的方块随后会被丢弃。
+    pub fn set_block_filter(&mut self, filter: BlockFilter) {
+        self.block_filter = Some(filter);
+    }
+
+    /// 移除方块过滤器
+    pub fn clear_block_filter(&mut self) {
+        self.block_filter = None;
+    }
+
     /// 添加一个方块
     pub fn add_block(
         &mut self,
@@ -47,41 +513,136 @@ impl McsEncoder {
         z: i32,
         nbt: Option<Vec<u8>>,
     ) -> Result<(), McStreamError> {
+        self.insert_block(block_id, None, BlockPos::new(x, y, z), nbt, 0)
+    }
+
+    /// 添加一个带编辑器标签的方块，`tags` 的各位含义由工具自行约定（见 [`Block::tags`]）
+    pub fn add_block_tagged(
+        &mut self,
+        block_id: String,
+        x: i32,
+        y: i32,
+        z: i32,
+        nbt: Option<Vec<u8>>,
+        tags: u16,
+    ) -> Result<(), McStreamError> {
+        self.insert_block(block_id, None, BlockPos::new(x, y, z), nbt, tags)
+    }
+
+    /// 添加一个带覆盖层的方块（如含水的楼梯：主方块为楼梯，覆盖层为水）
+    ///
+    /// 方块过滤器只作用于主方块；覆盖层为空气时等同于 `add_block`。
+    pub fn add_block_with_overlay(
+        &mut self,
+        block_id: String,
+        overlay_id: String,
+        x: i32,
+        y: i32,
+        z: i32,
+        nbt: Option<Vec<u8>>,
+    ) -> Result<(), McStreamError> {
+        self.insert_block(block_id, Some(overlay_id), BlockPos::new(x, y, z), nbt, 0)
+    }
+
+    /// 将光标移动到指定的世界坐标，供 `place` / `place_relative` 使用
+    pub fn move_to(&mut self, x: i32, y: i32, z: i32) {
+        self.cursor = BlockPos::new(x, y, z);
+    }
+
+    /// 当前光标位置，初始为原点
+    pub fn cursor(&self) -> BlockPos {
+        self.cursor
+    }
+
+    /// 在光标处添加一个方块，光标不移动
+    pub fn place(&mut self, block_id: String, nbt: Option<Vec<u8>>) -> Result<(), McStreamError> {
+        let BlockPos { x, y, z } = self.cursor;
+        self.add_block(block_id, x, y, z, nbt)
+    }
+
+    /// 将光标移动 `(dx, dy, dz)` 后在新位置添加一个方块
+    ///
+    /// 坐标溢出时返回错误且光标不变；方块被过滤器丢弃时光标仍会移动。
+    pub fn place_relative(
+        &mut self,
+        dx: i32,
+        dy: i32,
+        dz: i32,
+        block_id: String,
+        nbt: Option<Vec<u8>>,
+    ) -> Result<(), McStreamError> {
+        let BlockPos { x, y, z } = self.cursor;
+        let (Some(x), Some(y), Some(z)) = (x.checked_add(dx), y.checked_add(dy), z.checked_add(dz))
+        else {
+            return Err(McStreamError::CoordinateOutOfRange);
+        };
+        self.move_to(x, y, z);
+        self.place(block_id, nbt)
+    }
+
+    pub(crate) fn insert_block(
+        &mut self,
+        mut block_id: String,
+        overlay_id: Option<String>,
+        pos: BlockPos,
+        mut nbt: Option<Vec<u8>>,
+        tags: u16,
+    ) -> Result<(), McStreamError> {
+        if let Some(filter) = self.block_filter.as_mut() {
+            if !filter(&mut block_id, &mut nbt) {
+                return Ok(());
+            }
+        }
+
+        let block_id = self.id_policy.apply(block_id)?;
+        let overlay_id = overlay_id.map(|id| self.id_policy.apply(id)).transpose()?;
+
         if block_id.contains("minecraft:air") {
             return Ok(());
         }
+        let overlay_id = overlay_id.filter(|id| !id.contains("minecraft:air"));
 
-        let chunk_pos = ChunkPos {
-            x: x >> 4,
-            z: z >> 4,
-        };
+        if let Some(allowed) = &self.allowed_namespaces {
+            for id in std::iter::once(&block_id).chain(&overlay_id) {
+                palette::validate_namespace(id, allowed)?;
+            }
+        }
 
-        let local_pos = LocalBlockPos {
-            x: (x & 0xF) as u8,
-            y: (y + 64) as u16,
-            z: (z & 0xF) as u8,
-        };
+        let chunk_pos = pos.chunk_pos();
+        let local_pos = pos.local_pos();
 
         validate_local_pos(&local_pos)?;
 
+        if let (Some(nbt), true) = (&nbt, self.validate_nbt_on_add) {
+            nbt::validate_deep(nbt)?;
+        }
+
+        let (nbt, nbt_compression) = match (nbt, self.nbt_compression) {
+            (Some(nbt), Some(compression)) => {
+                (Some(compress_data(&nbt, compression)?), Some(compression))
+            }
+            (nbt, _) => (nbt, None),
+        };
+
         let chunk = self.chunks.entry(chunk_pos).or_insert_with(|| ChunkData {
             pos: chunk_pos,
-            palette: Vec::new(),
+            palette: Palette::new(),
             blocks: Vec::new(),
         });
+        self.dirty.insert(chunk_pos);
 
-        let palette_index = match chunk.palette.iter().position(|id| *id == block_id) {
-            Some(index) => index as u16,
-            None => {
-                chunk.palette.push(block_id);
-                (chunk.palette.len() - 1) as u16
-            }
-        };
+        let palette_index = chunk.palette.get_or_insert(block_id)?;
+        let overlay = overlay_id
+            .map(|id| chunk.palette.get_or_insert(id))
+            .transpose()?;
 
         chunk.blocks.push(Block {
             palette_index,
             pos: local_pos,
             nbt,
+            nbt_compression,
+            overlay,
+            tags,
         });
 
         Ok(())
@@ -102,15 +663,119 @@ impl McsEncoder {
 
     /// 添加区块数据
     pub fn add_chunk(&mut self, chunk: ChunkData) -> Result<(), McStreamError> {
+        if let Some(allowed) = &self.allowed_namespaces {
+            for block_id in &chunk.palette {
+                palette::validate_namespace(block_id, allowed)?;
+            }
+        }
         for block in &chunk.blocks {
             validate_local_pos(&block.pos)?;
+            if self.validate_nbt_on_add {
+                if let Some(nbt) = block.nbt_decompressed()? {
+                    nbt::validate_deep(&nbt)?;
+                }
+            }
         }
+        self.dirty.insert(chunk.pos);
         self.chunks.insert(chunk.pos, chunk);
         Ok(())
     }
 
-    /// 将所有数据写入MCS文件
-    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), McStreamError> {
+    /// 添加已压缩的区块数据，写入时原样复制，不再序列化与压缩
+    ///
+    /// `compressed` 须按编码器当前的区块格式、方块ID表与压缩字典编码，例如来自设置相同的另一个文件；
+    /// 添加时解压校验一次并读取方块数与调色板，因此应在设置编码选项之后调用。文件中的区块共用头部的压缩算法，
+    /// `compression` 与编码器不同、位置上已有区块或数据中没有方块时返回错误。
+    /// 这些区块不出现在 `get_chunks` 与 `to_mcs_data` 中。
+    pub fn add_raw_chunk(
+        &mut self,
+        pos: ChunkPos,
+        compressed: Vec<u8>,
+        compression: CompressionType,
+    ) -> Result<(), McStreamError> {
+        if compression != self.compression {
+            return Err(McStreamError::ValidationError(format!(
+                "区块 ({}, {}) 的压缩算法与编码器不一致",
+                pos.x, pos.z
+            )));
+        }
+        if self.raw_chunks.contains_key(&pos) || self.chunks.contains_key(&pos) {
+            return Err(McStreamError::ValidationError(format!(
+                "区块 ({}, {}) 已存在",
+                pos.x, pos.z
+            )));
+        }
+
+        let data = decompress_data_limited_with(
+            &compressed,
+            compression,
+            None,
+            self.dictionary.as_deref(),
+        )?;
+        let (chunk, _, _) = deserialize_chunk_with(
+            &data,
+            pos,
+            self.chunk_format != 0,
+            false,
+            self.id_table.as_ref(),
+            &mut str::to_owned,
+        )?;
+        if chunk.blocks.is_empty() {
+            return Err(McStreamError::ValidationError(format!(
+                "区块 ({}, {}) 没有方块",
+                pos.x, pos.z
+            )));
+        }
+        if let Some(allowed) = &self.allowed_namespaces {
+            for block_id in &chunk.palette {
+                palette::validate_namespace(block_id, allowed)?;
+            }
+        }
+        self.check_target_version(&[&chunk])?;
+
+        self.raw_chunks.insert(
+            pos,
+            RawChunk {
+                data: compressed,
+                block_count: chunk.blocks.len() as u32,
+                bloom: PaletteBloom::from_palette(chunk.palette.as_slice()),
+            },
+        );
+        Ok(())
+    }
+
+    /// 移除坐标处的所有方块，返回是否有方块被移除
+    ///
+    /// 调色板保持不变，区块变为空时写入时跳过。
+    pub fn remove_block(&mut self, x: i32, y: i32, z: i32) -> bool {
+        let pos = BlockPos::new(x, y, z);
+        let Some(chunk) = self.chunks.get_mut(&pos.chunk_pos()) else {
+            return false;
+        };
+        let local_pos = pos.local_pos();
+        let before = chunk.blocks.len();
+        chunk.blocks.retain(|block| block.pos != local_pos);
+        if chunk.blocks.len() == before {
+            return false;
+        }
+        self.dirty.insert(chunk.pos);
+        true
+    }
+
+    /// 区块在上次保存（或由 `from_decoder` 读入）后是否被修改过
+    ///
+    /// 新添加的区块总是视为已修改。
+    pub fn is_dirty(&self, pos: ChunkPos) -> bool {
+        self.dirty.contains(&pos)
+    }
+
+    /// 将所有区块标记为未修改，用于刚由 `write_to_file` 保存之后
+    pub fn mark_clean(&mut self) {
+        self.dirty.clear();
+    }
+
+    /// 将所有数据写入MCS文件，返回写入结果的摘要
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<WriteSummary, McStreamError> {
         let path = path.as_ref();
 
         if let Some(parent) = path.parent() {
@@ -124,88 +789,982 @@ impl McsEncoder {
         let file = OpenOptions::new().write(true).create_new(true).open(path)?;
 
         let mut writer = BufWriter::new(file);
-        self.write_to_writer(&mut writer)?;
+        let summary = self.write_to_writer(&mut writer)?;
         writer.flush()?;
 
-        Ok(())
+        Ok(summary)
     }
 
-    /// 将数据写入到指定的写入器
-    fn write_to_writer<W: Write + Seek>(&self, writer: &mut W) -> Result<(), McStreamError> {
+    /// 将所有数据写入MCS文件，并在外层整体使用gzip压缩
+    ///
+    /// 用于网络传输等场景，`McsDecoder::from_file` 与 `LazyMcsDecoder::open` 可直接读取。返回的摘要描述的是
+    /// gzip解压后的MCS数据。
+    pub fn write_to_file_gzip<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<WriteSummary, McStreamError> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = File::create(path)?;
+        let mut writer = GzEncoder::new(BufWriter::new(file), Compression::default());
+        let summary = self.write_to_writer(&mut writer)?;
+        writer.finish()?.flush()?;
+
+        Ok(summary)
+    }
+
+    /// 以内容哈希命名写入目录，返回文件路径
+    ///
+    /// 文件名为编码结果的SHA-256十六进制值（`<hash>.mcs`）。同名文件已存在时不再写入；
+    /// 否则先写入同目录下的临时文件再重命名，不会留下不完整的文件。
+    pub fn write_content_addressed<P: AsRef<Path>>(
+        &self,
+        dir: P,
+    ) -> Result<PathBuf, McStreamError> {
+        let dir = dir.as_ref();
+
+        let mut bytes = Vec::new();
+        let summary = self.write_to_writer(&mut bytes)?;
+
+        let hash: String = summary
+            .data_hash
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let path = dir.join(format!("{}.mcs", hash));
+        if path.exists() {
+            return Ok(path);
+        }
+
+        std::fs::create_dir_all(dir)?;
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        temp.write_all(&bytes)?;
+        temp.as_file().sync_all()?;
+
+        // 并发写入同一内容时，另一方先完成也视为成功
+        match temp.persist_noclobber(&path) {
+            Ok(_) => Ok(path),
+            Err(_) if path.exists() => Ok(path),
+            Err(e) => Err(e.error.into()),
+        }
+    }
+
+    /// 重新保存此前由本编码器写入（或经 `from_decoder` 读入）的文件，只重新压缩修改过的区块
+    ///
+    /// 未修改的区块直接从 `path` 复制压缩数据；`path` 的版本、压缩算法、区块格式字节、方块ID表、压缩级别
+    /// 或压缩字典与当前设置不同时所有区块重新编码，其中缺少该区块时照常编码该区块。
+    /// 使用区块格式字节时，复制前需解压区块以比较格式。新文件先写入同目录下的临时文件再替换 `path`，`path` 不存在时直接写入。
+    /// 成功后所有区块标记为未修改。
+    pub fn update_file<P: AsRef<Path>>(&mut self, path: P) -> Result<WriteSummary, McStreamError> {
+        let path = path.as_ref();
+        let mut previous = if path.exists() && !self.canonical {
+            PreviousFile::open(path, self)?
+        } else {
+            None
+        };
+
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        std::fs::create_dir_all(dir)?;
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        let mut writer = BufWriter::new(temp.as_file_mut());
+        let summary = self.write_reusing(&mut writer, previous.as_mut())?;
+        writer.flush()?;
+        drop(writer);
+
+        // 先关闭旧文件再替换
+        drop(previous);
+        temp.persist(path).map_err(|e| e.error)?;
+        self.dirty.clear();
+        Ok(summary)
+    }
+
+    /// 将数据按顺序写入到指定的写入器，写入的同时计算整个文件的哈希
+    pub fn write_to_writer<W: Write>(&self, writer: &mut W) -> Result<WriteSummary, McStreamError> {
+        self.write_reusing(writer, None)
+    }
+
+    fn write_reusing<W: Write>(
+        &self,
+        writer: &mut W,
+        mut previous: Option<&mut PreviousFile>,
+    ) -> Result<WriteSummary, McStreamError> {
+        // 没有方块的区块不写入，也不占用索引条目
+        let chunks: Vec<_> = self
+            .chunks
+            .values()
+            .filter(|chunk| !chunk.blocks.is_empty())
+            .collect();
+        let skipped = self.chunks.len() - chunks.len();
+        if skipped > 0 {
+            log::debug!("跳过 {} 个空区块", skipped);
+        }
+
+        let canonical: Vec<ChunkData> = if self.canonical {
+            chunks
+                .iter()
+                .map(|chunk| {
+                    let mut chunk = (*chunk).clone();
+                    chunk.canonicalize()?;
+                    Ok(chunk)
+                })
+                .collect::<Result<_, McStreamError>>()?
+        } else {
+            Vec::new()
+        };
+        let chunks = if self.canonical {
+            canonical.iter().collect()
+        } else {
+            chunks
+        };
+
         // 检查是否有区块
-        if self.chunks.is_empty() {
+        if chunks.is_empty() && self.raw_chunks.is_empty() {
             return Err(McStreamError::ValidationError(
                 "没有区块数据可写入".to_string(),
             ));
         }
 
-        // 1. 首先写入头部（20字节）
-        write_header(writer, self.compression, self.has_signature)?;
-
-        // 2. 计算索引表位置：头部大小 = 20字节
-        let index_table_offset = 20u32;
-
-        // 修改头部中的索引表偏移值
-        writer.seek(std::io::SeekFrom::Start(12))?; // 索引表偏移字段位置
-        writer.write_u32::<LittleEndian>(index_table_offset)?;
+        if self.has_signature && !self.journal.is_empty() {
+            return Err(McStreamError::ValidationError(
+                "带签名的文件不能包含编辑日志".to_string(),
+            ));
+        }
 
-        // 3. 跳到索引表位置
-        writer.seek(std::io::SeekFrom::Start(index_table_offset as u64))?;
+        self.check_target_version(&chunks)?;
 
-        // 4. 准备区块数据
+        // 1. 准备区块数据
         let mut chunk_index = Vec::new();
         let mut chunk_data = Vec::new();
+        let mut blooms = Vec::new();
 
-        for chunk in self.chunks.values() {
-            let compressed = compress_chunk(chunk, self.compression)?;
-            chunk_index.push(ChunkIndexEntry {
-                chunk_x: chunk.pos.x,
-                chunk_z: chunk.pos.z,
-                data_offset: 0, // 临时值，稍后更新
-                compressed_size: compressed.len() as u32,
-            });
-            chunk_data.push(compressed);
+        let chunk_count = chunks.len() + self.raw_chunks.len();
+        let mut encoded = Vec::with_capacity(chunk_count);
+        let mut reused = 0;
+        for chunk in chunks {
+            let copied = match previous.as_deref_mut() {
+                Some(previous) if !self.dirty.contains(&chunk.pos) => {
+                    previous.read_parts(chunk, self)?
+                }
+                _ => None,
+            };
+            let parts = match copied {
+                Some(parts) => {
+                    reused += 1;
+                    parts
+                }
+                None => self.compress_parts(chunk)?,
+            };
+            let bloom = PaletteBloom::from_palette(chunk.palette.as_slice());
+            encoded.push((chunk.pos, parts, bloom));
         }
+        for (pos, raw) in &self.raw_chunks {
+            if self
+                .chunks
+                .get(pos)
+                .is_some_and(|chunk| !chunk.blocks.is_empty())
+            {
+                return Err(McStreamError::ValidationError(format!(
+                    "区块 ({}, {}) 同时含有方块与已压缩的数据",
+                    pos.x, pos.z
+                )));
+            }
+            encoded.push((*pos, vec![(raw.block_count, raw.data.clone())], raw.bloom));
+        }
+
+        // 区块按 `(x, z)` 顺序写入，相同的内容总是得到相同的字节
+        encoded.sort_unstable_by_key(|(pos, _, _)| (pos.x, pos.z));
+        for (pos, parts, bloom) in encoded {
+            for (block_count, compressed) in parts {
+                chunk_index.push(ChunkIndexEntry {
+                    chunk_x: pos.x,
+                    chunk_z: pos.z,
+                    data_offset: 0, // 临时值，稍后更新
+                    compressed_size: compressed.len() as u32,
+                    block_count: Some(block_count),
+                });
+                chunk_data.push(compressed);
+                // 拆分后的各部分共用整个区块的过滤器，只会多出误报
+                blooms.push(bloom);
+            }
+        }
+
+        // 2. 计算区块数据起始位置：索引表在头部之后时需要先留出索引表的空间
+        let entry_size = index_entry_size(self.target_version);
+        let index_size = (INDEX_COUNT_SIZE + chunk_index.len() * entry_size) as u32;
+        let mut current_offset = if self.tail_index {
+            HEADER_SIZE as u32
+        } else {
+            HEADER_SIZE as u32 + index_size
+        };
 
-        // 5. 写入区块索引表（先写入长度，后面再更新偏移）
-        write_chunk_index(writer, &chunk_index)?;
+        for (entry, compressed) in chunk_index.iter_mut().zip(&chunk_data) {
+            entry.data_offset = current_offset;
+            current_offset += compressed.len() as u32;
+        }
 
-        // 6. 更新并写入实际的区块数据
-        let mut current_offset = writer.stream_position()? as u32;
+        let index_table_offset = if self.tail_index {
+            current_offset
+        } else {
+            HEADER_SIZE as u32
+        };
 
-        for (i, compressed) in chunk_data.iter().enumerate() {
-            // 更新区块索引的偏移
-            chunk_index[i].data_offset = current_offset;
+        // 3. 写入头部（20字节），偏移量已知，整个文件可以顺序写出
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        write_header_as(
+            &mut header,
+            self.target_version,
+            self.compression,
+            self.header_flags(),
+        )?;
+        update_index_table_offset(&mut Cursor::new(&mut header), index_table_offset)?;
+        update_dictionary_id(&mut Cursor::new(&mut header), self.dictionary_id())?;
 
-            // 写入压缩数据
-            writer.write_all(compressed)?;
+        let mut writer = HashingWriter::new(writer);
+        writer.write_all(&header)?;
 
-            // 更新下一个区块的偏移
-            current_offset += compressed.len() as u32;
+        // 4. 写入索引表与区块数据
+        if self.tail_index {
+            for compressed in &chunk_data {
+                writer.write_all(compressed)?;
+            }
+            write_chunk_index_as(&mut writer, &chunk_index, self.target_version)?;
+        } else {
+            write_chunk_index_as(&mut writer, &chunk_index, self.target_version)?;
+            for compressed in &chunk_data {
+                writer.write_all(compressed)?;
+            }
         }
 
-        // 7. 回到索引表位置，用更新后的偏移值重新写入
-        writer.seek(std::io::SeekFrom::Start(index_table_offset as u64))?;
-        write_chunk_index(writer, &chunk_index)?;
+        // 5. 写入树形哈希（如果需要）
+        let tree = if self.tree_hash {
+            let tree = TreeHash::from_leaves(chunk_hashes(&chunk_data));
+            write_tree_hash(&mut writer, &tree)?;
+            Some(tree)
+        } else {
+            None
+        };
 
-        // 8. 跳到文件末尾
-        writer.seek(std::io::SeekFrom::End(0))?;
+        // 6. 写入调色板布隆过滤器（如果需要）
+        if self.palette_bloom {
+            write_palette_blooms(&mut writer, &blooms)?;
+        }
 
-        // 9. 写入签名数据（如果需要）
-        if self.has_signature && self.signature.is_some() {
-            writer.write_all(self.signature.as_ref().unwrap())?;
+        // 7. 写入采集原点（如果有）
+        if let Some(origin) = self.origin {
+            for coord in [origin.x, origin.y, origin.z] {
+                writer.write_i32::<LittleEndian>(coord)?;
+            }
         }
 
+        // 8. 准备签名数据（如果需要）
+        let signature = if self.has_signature {
+            match (&self.signer, &tree, &self.signature) {
+                (Some(signer), Some(tree), _) => signer(&tree.root),
+                (None, _, Some(signature)) => signature.clone(),
+                _ => {
+                    log::warn!("已设置签名标志但没有签名数据，文件尾部不含签名");
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+
+        // 9. 分帧时签名与扩展数据带长度前缀，位于编辑日志之前，日志仍可在文件末尾追加
+        if self.framed_footer {
+            writer.write_u32::<LittleEndian>(footer_len(&signature, "签名")?)?;
+            writer.write_u32::<LittleEndian>(footer_len(&self.footer_extension, "扩展数据")?)?;
+            writer.write_all(&signature)?;
+            writer.write_all(&self.footer_extension)?;
+        }
+
+        // 10. 写入编辑日志（如果有）
+        for event in &self.journal {
+            write_event(&mut writer, event)?;
+        }
+
+        // 未分帧时签名延续到文件末尾
+        if !self.framed_footer {
+            writer.write_all(&signature)?;
+        }
+
+        if previous.is_some() {
+            log::debug!("{} 个未修改的区块直接复制", reused);
+        }
+        log::debug!(
+            "写入完成：{} 个区块，{} 个索引条目，共 {} 字节",
+            chunk_count,
+            chunk_index.len(),
+            writer.bytes_written
+        );
+
+        Ok(WriteSummary {
+            bytes_written: writer.bytes_written,
+            index_table_offset,
+            chunk_count,
+            data_hash: writer.hasher.finalize().into(),
+        })
+    }
+
+    /// 记录一条编辑日志事件，写入时追加在区块数据之后
+    pub fn record_event(&mut self, event: JournalEvent) {
+        self.journal.push(event);
+    }
+
+    /// 获取尚未合并的编辑日志
+    pub fn journal(&self) -> &[JournalEvent] {
+        &self.journal
+    }
+
+    /// 将编辑日志按顺序合并进区块数据并清空日志
+    pub fn compact_journal(&mut self) -> Result<(), McStreamError> {
+        for event in &self.journal {
+            apply_event(&mut self.chunks, event)?;
+            self.dirty.insert(event.op.pos().chunk_pos());
+        }
+        self.journal.clear();
         Ok(())
     }
 
+    fn serialize(&self, chunk: &ChunkData) -> Result<Vec<u8>, McStreamError> {
+        if self.chunk_format != 0 {
+            serialize_chunk_with_level(
+                chunk,
+                self.format_for(chunk),
+                self.id_table.as_ref(),
+                self.compression_level,
+            )
+        } else {
+            serialize_chunk(chunk)
+        }
+    }
+
+    // 区块实际写入的格式字节：调色板过大时不用单字节索引，不需要时不用4字节索引
+    fn format_for(&self, chunk: &ChunkData) -> u8 {
+        let mut format = self.chunk_format;
+        if chunk.palette.len() > NARROW_INDEX_PALETTE_LIMIT {
+            format &= !CHUNK_FORMAT_NARROW_INDEX;
+        }
+        if chunk.palette.len() <= MAX_PALETTE_LEN || format & CHUNK_FORMAT_NARROW_INDEX != 0 {
+            format &= !CHUNK_FORMAT_WIDE_INDEX;
+        }
+        format
+    }
+
+    // 序列化并压缩区块，超过大小上限的区块按Y分段拆分为多个同坐标的条目，返回每部分的方块数与压缩数据
+    fn compress_parts(&self, chunk: &ChunkData) -> Result<ChunkParts, McStreamError> {
+        let parts = self.serialize_parts(chunk)?;
+        if parts.len() > 1 && self.target_version < 0x0101 {
+            return Err(self.unsupported_by_target("区块拆分"));
+        }
+        if parts.len() > 1 {
+            log::debug!(
+                "区块 ({}, {}) 超过大小上限，拆分为 {} 个条目",
+                chunk.pos.x,
+                chunk.pos.z,
+                parts.len()
+            );
+        }
+        let options = CompressOptions {
+            dictionary: self.dictionary.as_deref(),
+            long_window_log: self.long_window_log,
+            level: self.compression_level,
+        };
+        parts
+            .into_iter()
+            .map(|(block_count, data)| {
+                let compressed = compress_data_with(&data, self.compression, &options)?;
+                log::debug!(
+                    "编码区块 ({}, {})：{} 个方块，压缩后 {} 字节",
+                    chunk.pos.x,
+                    chunk.pos.z,
+                    block_count,
+                    compressed.len()
+                );
+                Ok((block_count, compressed))
+            })
+            .collect()
+    }
+
+    // 序列化区块，超过 `max_chunk_bytes` 时拆分，返回每部分的方块数与数据
+    fn serialize_parts(&self, chunk: &ChunkData) -> Result<ChunkParts, McStreamError> {
+        let data = self.serialize(chunk)?;
+        match self.max_chunk_bytes {
+            Some(max) if data.len() > max => {
+                let mut blocks: Vec<&Block> = chunk.blocks.iter().collect();
+                blocks.sort_by_key(|block| block.pos.y);
+
+                let mut parts = Vec::new();
+                self.split_by_y(chunk, &blocks, max, &mut parts)?;
+                Ok(parts)
+            }
+            _ => Ok(vec![(chunk.blocks.len() as u32, data)]),
+        }
+    }
+
+    // 按Y坐标二分拆分，同一Y层的方块不会被拆开，因此单层过大时仍会超出上限
+    fn split_by_y(
+        &self,
+        chunk: &ChunkData,
+        blocks: &[&Block],
+        max: usize,
+        parts: &mut ChunkParts,
+    ) -> Result<(), McStreamError> {
+        let data = self.serialize(&sub_chunk(chunk, blocks))?;
+
+        let mid_y = blocks[blocks.len() / 2].pos.y;
+        let mut split = blocks.partition_point(|block| block.pos.y < mid_y);
+        if split == 0 {
+            split = blocks.partition_point(|block| block.pos.y <= mid_y);
+        }
+
+        if data.len() <= max || split == blocks.len() {
+            parts.push((blocks.len() as u32, data));
+            return Ok(());
+        }
+
+        self.split_by_y(chunk, &blocks[..split], max, parts)?;
+        self.split_by_y(chunk, &blocks[split..], max, parts)
+    }
+
+    // 1.0版本只有签名标志位，方块只能带未压缩的NBT
+    fn check_target_version(&self, chunks: &[&ChunkData]) -> Result<(), McStreamError> {
+        if !(MCS_MIN_VERSION..=MCS_VERSION).contains(&self.target_version) {
+            return Err(McStreamError::UnsupportedVersion(self.target_version));
+        }
+        if self.target_version >= 0x0101 {
+            return Ok(());
+        }
+
+        let sections = [
+            (self.tree_hash, "树形哈希"),
+            (!self.journal.is_empty(), "编辑日志"),
+            (
+                self.chunk_format != 0,
+                "区块格式字节（变长整数、方块ID表、单字节或4字节索引、空间索引、压缩级别）",
+            ),
+            (self.palette_bloom, "调色板布隆过滤器"),
+            (self.origin.is_some(), "采集原点"),
+            (self.dictionary.is_some(), "压缩字典"),
+            (self.framed_footer, "尾部分帧"),
+            (
+                self.compression == CompressionType::LZ4Block
+                    || self.nbt_compression == Some(CompressionType::LZ4Block),
+                "LZ4块格式",
+            ),
+            (
+                matches!(self.compression, CompressionType::Custom(_))
+                    || matches!(self.nbt_compression, Some(CompressionType::Custom(_))),
+                "自定义压缩算法",
+            ),
+        ];
+        if let Some((_, name)) = sections.iter().find(|(used, _)| *used) {
+            return Err(self.unsupported_by_target(name));
+        }
+
+        for block in chunks.iter().flat_map(|chunk| &chunk.blocks) {
+            if block.nbt_compression.is_some() {
+                return Err(self.unsupported_by_target("单独压缩的NBT"));
+            }
+            if block.overlay.is_some() {
+                return Err(self.unsupported_by_target("覆盖层"));
+            }
+            if block.tags != 0 {
+                return Err(self.unsupported_by_target("编辑器标签"));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn unsupported_by_target(&self, feature: &str) -> McStreamError {
+        McStreamError::ValidationError(format!(
+            "目标版本 {}.{} 不支持{}",
+            self.target_version >> 8,
+            self.target_version & 0xFF,
+            feature
+        ))
+    }
+
+    fn header_flags(&self) -> u8 {
+        let mut flags = McsFlags::empty();
+        if self.has_signature {
+            flags |= McsFlags::SIGNATURE;
+        }
+        if !self.journal.is_empty() {
+            flags |= McsFlags::JOURNAL;
+        }
+        if self.tree_hash {
+            flags |= McsFlags::TREE_HASH;
+        }
+        if self.chunk_format != 0 {
+            flags |= McsFlags::CHUNK_FORMAT;
+        }
+        if self.palette_bloom {
+            flags |= McsFlags::PALETTE_BLOOM;
+        }
+        if self.origin.is_some() {
+            flags |= McsFlags::ORIGIN;
+        }
+        if self.dictionary.is_some() {
+            flags |= McsFlags::DICTIONARY;
+        }
+        if self.framed_footer {
+            flags |= McsFlags::FRAMED_FOOTER;
+        }
+        flags.bits()
+    }
+
+    // 头部记录的压缩字典ID，未使用字典时为0
+    fn dictionary_id(&self) -> u32 {
+        self.dictionary.as_deref().map_or(0, dictionary_id)
+    }
+
+    /// 按 `(x, z)` 顺序列出每个区块按密度建议的编码方式，见 [`ChunkData::recommended_encoding`]
+    pub fn recommended_encoding(&self) -> Vec<(ChunkPos, ChunkEncoding)> {
+        let mut encodings: Vec<_> = self
+            .chunks
+            .values()
+            .map(|chunk| (chunk.pos, chunk.recommended_encoding()))
+            .collect();
+        encodings.sort_unstable_by_key(|(pos, _)| (pos.x, pos.z));
+        encodings
+    }
+
     /// 获取当前存储的区块数据
     pub fn get_chunks(&self) -> &HashMap<ChunkPos, ChunkData> {
         &self.chunks
     }
 
+    /// 转换为McsData结构
+    pub fn to_mcs_data(&self) -> McsData {
+        McsData {
+            header: McsHeader {
+                version: MCS_VERSION,
+                compression: self.compression.to_u8(),
+                flags: self.header_flags(),
+                // 索引表位置在写入时才能确定，这里记为紧跟在头部之后
+                index_table_offset: HEADER_SIZE as u32,
+                dictionary_id: self.dictionary_id(),
+            },
+            chunks: self.chunks.clone(),
+        }
+    }
+
+    /// 移除没有方块的区块，返回移除的区块数
+    ///
+    /// 写入时会自动跳过这些区块，此方法用于在写入前显式清理（如检查 `get_chunks`）。
+    pub fn prune_empty(&mut self) -> usize {
+        let before = self.chunks.len();
+        self.chunks.retain(|_, chunk| !chunk.blocks.is_empty());
+        before - self.chunks.len()
+    }
+
     /// 清空所有区块数据
     pub fn clear(&mut self) {
         self.chunks.clear();
+        self.dirty.clear();
+        self.raw_chunks.clear();
+    }
+}
+
+// 由 `add_raw_chunk` 添加的已压缩区块
+struct RawChunk {
+    data: Vec<u8>,
+    block_count: u32,
+    bloom: PaletteBloom,
+}
+
+// 上一次保存的文件，`update_file` 从中复制未修改区块的压缩数据
+struct PreviousFile {
+    reader: BufReader<File>,
+    entries: HashMap<ChunkPos, Vec<ChunkIndexEntry>>,
+}
+
+impl PreviousFile {
+    // 文件的编码设置与 `encoder` 不同时区块数据不能复用，返回 `None`
+    fn open(path: &Path, encoder: &McsEncoder) -> Result<Option<Self>, McStreamError> {
+        let (header, index, _) = read_validated_index(path)?;
+        let compatible = header.version == encoder.target_version
+            && header.compression == encoder.compression.to_u8()
+            && header.has_chunk_format() == (encoder.chunk_format != 0)
+            && header.dictionary_id == encoder.dictionary_id();
+        if !compatible {
+            log::debug!("{} 的编码设置与当前不同，所有区块重新编码", path.display());
+            return Ok(None);
+        }
+
+        let mut entries: HashMap<ChunkPos, Vec<ChunkIndexEntry>> = HashMap::new();
+        for entry in index {
+            let pos = ChunkPos::new(entry.chunk_x, entry.chunk_z);
+            entries.entry(pos).or_default().push(entry);
+        }
+        Ok(Some(Self {
+            reader: BufReader::new(File::open(path)?),
+            entries,
+        }))
+    }
+
+    // 读取区块各条目的压缩数据；文件中没有该区块，或方块数对不上（文件已被其他程序修改）时返回 `None`。
+    // 区块格式字节、方块ID表或压缩级别与 `encoder` 不同时不再复用此文件，之后的区块全部重新编码
+    fn read_parts(
+        &mut self,
+        chunk: &ChunkData,
+        encoder: &McsEncoder,
+    ) -> Result<Option<ChunkParts>, McStreamError> {
+        let Some(entries) = self.entries.get(&chunk.pos) else {
+            return Ok(None);
+        };
+        let block_count: Option<u64> = entries
+            .iter()
+            .map(|entry| entry.block_count.map(u64::from))
+            .sum();
+        if block_count != Some(chunk.blocks.len() as u64) {
+            return Ok(None);
+        }
+
+        let mut parts = Vec::with_capacity(entries.len());
+        for entry in entries {
+            self.reader
+                .seek(SeekFrom::Start(entry.data_offset as u64))?;
+            let mut data = vec![0u8; entry.compressed_size as usize];
+            self.reader.read_exact(&mut data)?;
+            if encoder.chunk_format != 0 && !same_chunk_format(&data, chunk.pos, encoder) {
+                log::debug!(
+                    "区块 ({}, {}) 的区块格式与当前设置不同，所有区块重新编码",
+                    chunk.pos.x,
+                    chunk.pos.z
+                );
+                self.entries.clear();
+                return Ok(None);
+            }
+            parts.push((entry.block_count.unwrap_or(0), data));
+        }
+        Ok(Some(parts))
+    }
+}
+
+// 已压缩的区块数据的格式字节（含方块ID表与压缩级别）是否与 `encoder` 对这些方块的设置相同，
+// 只读取格式字节无法判断索引宽度，因此解码整个区块；数据损坏时同样视为不同
+fn same_chunk_format(compressed: &[u8], pos: ChunkPos, encoder: &McsEncoder) -> bool {
+    let Ok(data) = decompress_data_limited_with(
+        compressed,
+        encoder.compression,
+        None,
+        encoder.dictionary.as_deref(),
+    ) else {
+        return false;
+    };
+    // 方块ID表不同时解码失败
+    match deserialize_chunk_with(
+        &data,
+        pos,
+        true,
+        false,
+        encoder.id_table.as_ref(),
+        &mut str::to_owned,
+    ) {
+        Ok((part, _, prefix)) => {
+            prefix.format == encoder.format_for(&part)
+                && prefix.compression_level == encoder.compression_level
+        }
+        Err(_) => false,
+    }
+}
+
+// 由部分方块构建区块，调色板只保留用到的条目
+fn sub_chunk(chunk: &ChunkData, blocks: &[&Block]) -> ChunkData {
+    let mut palette = Vec::new();
+    let mut mapping = HashMap::new();
+    let mut remap = |index: u32| {
+        *mapping.entry(index).or_insert_with(|| {
+            palette.push(chunk.palette[index].clone());
+            (palette.len() - 1) as u32
+        })
+    };
+
+    let blocks = blocks
+        .iter()
+        .map(|block| {
+            let mut block = (*block).clone();
+            block.palette_index = remap(block.palette_index);
+            block.overlay = block.overlay.map(&mut remap);
+            block
+        })
+        .collect();
+
+    ChunkData {
+        pos: chunk.pos,
+        palette: Palette::from(palette),
+        blocks,
+    }
+}
+
+// 尾部分帧的长度前缀为u32
+fn footer_len(data: &[u8], what: &str) -> Result<u32, McStreamError> {
+    u32::try_from(data.len()).map_err(|_| {
+        McStreamError::ValidationError(format!("尾部{}超过4GB，无法写入长度前缀", what))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    fn round_trip(encoder: McsEncoder, id_table: Option<IdTable>) {
+        let mut original = NamedTempFile::new().unwrap();
+        encoder.write_to_writer(original.as_file_mut()).unwrap();
+
+        let mut builder = McsDecoder::builder().keep_nbt_compressed(true);
+        if let Some(table) = id_table {
+            builder = builder.id_table(table);
+        }
+        let decoder = builder.open(original.path()).unwrap();
+        let reencoded = McsEncoder::from_decoder(decoder, CompressionType::None).unwrap();
+        let mut bytes = Vec::new();
+        reencoded.write_to_writer(&mut bytes).unwrap();
+        assert_eq!(bytes, std::fs::read(original.path()).unwrap());
+    }
+
+    fn add_blocks(encoder: &mut McsEncoder) {
+        for x in 0..20 {
+            encoder
+                .add_block(format!("minecraft:block_{}", x % 5), x, x, -x, None)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn from_decoder_keeps_chunk_format() {
+        let mut encoder = McsEncoder::new(CompressionType::None)
+            .with_varint_encoding()
+            .with_narrow_palette_indices()
+            .with_spatial_index();
+        add_blocks(&mut encoder);
+        round_trip(encoder, None);
+
+        let table = IdTable::new(vec!["minecraft:block_0".to_string()]).unwrap();
+        let mut encoder = McsEncoder::new(CompressionType::None)
+            .with_id_table(&table)
+            .with_wide_palette_indices();
+        add_blocks(&mut encoder);
+        round_trip(encoder, Some(table));
+    }
+
+    #[test]
+    fn update_file_rewrites_on_format_change() {
+        let file = NamedTempFile::new().unwrap();
+        let mut encoder = McsEncoder::new(CompressionType::None).with_varint_encoding();
+        add_blocks(&mut encoder);
+        encoder.write_to_file(file.path()).unwrap();
+
+        let decoder = McsDecoder::from_file(file.path()).unwrap();
+        let mut encoder = McsEncoder::from_decoder(decoder, CompressionType::None)
+            .unwrap()
+            .with_narrow_palette_indices();
+        encoder.update_file(file.path()).unwrap();
+        let decoder = McsDecoder::from_file(file.path()).unwrap();
+        assert_ne!(decoder.chunk_format() & CHUNK_FORMAT_NARROW_INDEX, 0);
+
+        let old = IdTable::new(vec!["minecraft:block_0".to_string()]).unwrap();
+        let new = IdTable::new(vec!["minecraft:block_1".to_string()]).unwrap();
+        let mut encoder = McsEncoder::new(CompressionType::None).with_id_table(&old);
+        add_blocks(&mut encoder);
+        encoder.write_to_file(file.path()).unwrap();
+
+        let decoder = McsDecoder::builder()
+            .id_table(old)
+            .open(file.path())
+            .unwrap();
+        let mut encoder = McsEncoder::from_decoder(decoder, CompressionType::None)
+            .unwrap()
+            .with_id_table(&new);
+        encoder.update_file(file.path()).unwrap();
+        assert!(McsDecoder::builder()
+            .id_table(new)
+            .open(file.path())
+            .is_ok());
+    }
+
+    #[test]
+    fn update_file_writes_voxel_grid_chunks() {
+        let file = NamedTempFile::new().unwrap();
+        let mut old = McsEncoder::new(CompressionType::Zstandard);
+        for x in 0..4 {
+            old.add_block("minecraft:stone".to_string(), x, 0, 0, None)
+                .unwrap();
+        }
+        old.write_to_file(file.path()).unwrap();
+
+        let palette = vec!["minecraft:air".to_string(), "minecraft:dirt".to_string()];
+        let mut encoder =
+            McsEncoder::from_voxel_grid(&palette, &[1, 1, 1, 1], (4, 1, 1), BlockPos::new(0, 0, 0))
+                .unwrap();
+        assert!(encoder.is_dirty(ChunkPos::new(0, 0)));
+        encoder.update_file(file.path()).unwrap();
+
+        let decoder = McsDecoder::from_file(file.path()).unwrap();
+        let chunk = &decoder.get_chunks()[&ChunkPos::new(0, 0)];
+        assert_eq!(chunk.blocks.len(), 4);
+        assert!(chunk
+            .blocks
+            .iter()
+            .all(|b| chunk.palette.get(b.palette_index) == Some("minecraft:dirt")));
+    }
+
+    // 全局坐标 → 方块ID
+    fn placed(encoder: &McsEncoder) -> HashMap<BlockPos, &str> {
+        encoder
+            .get_chunks()
+            .values()
+            .flat_map(|chunk| {
+                chunk.blocks.iter().map(|block| {
+                    (
+                        block.pos.to_global(chunk.pos),
+                        chunk.palette[block.palette_index].as_str(),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cursor_crosses_chunk_boundaries() {
+        let mut encoder = McsEncoder::new(CompressionType::None);
+        encoder.move_to(14, 0, -2);
+        encoder.place("minecraft:stone".to_string(), None).unwrap();
+        for _ in 0..3 {
+            encoder
+                .place_relative(1, 1, 1, "minecraft:stone".to_string(), None)
+                .unwrap();
+        }
+        assert_eq!(encoder.cursor(), BlockPos::new(17, 3, 1));
+
+        encoder.move_to(1, 0, 1);
+        for _ in 0..3 {
+            encoder
+                .place_relative(-1, 0, -1, "minecraft:dirt".to_string(), None)
+                .unwrap();
+        }
+
+        let blocks = placed(&encoder);
+        let expected = [
+            ((14, 0, -2), "minecraft:stone"),
+            ((15, 1, -1), "minecraft:stone"),
+            ((16, 2, 0), "minecraft:stone"),
+            ((17, 3, 1), "minecraft:stone"),
+            ((0, 0, 0), "minecraft:dirt"),
+            ((-1, 0, -1), "minecraft:dirt"),
+            ((-2, 0, -2), "minecraft:dirt"),
+        ];
+        assert_eq!(blocks.len(), expected.len());
+        for ((x, y, z), id) in expected {
+            assert_eq!(blocks[&BlockPos::new(x, y, z)], id);
+        }
+        let mut chunks: Vec<_> = encoder.get_chunks().keys().map(|p| (p.x, p.z)).collect();
+        chunks.sort_unstable();
+        assert_eq!(chunks, [(-1, -1), (0, -1), (0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn cursor_overflow_keeps_position() {
+        let mut encoder = McsEncoder::new(CompressionType::None);
+        encoder.move_to(i32::MAX, 0, 0);
+        assert!(matches!(
+            encoder.place_relative(1, 0, 0, "minecraft:stone".to_string(), None),
+            Err(McStreamError::CoordinateOutOfRange)
+        ));
+        assert_eq!(encoder.cursor(), BlockPos::new(i32::MAX, 0, 0));
+        assert!(encoder.get_chunks().is_empty());
+    }
+
+    // 同一组方块，按 `order` 给出的顺序添加
+    fn canonical_sample(order: &[usize]) -> McsEncoder {
+        let mut encoder = McsEncoder::new(CompressionType::None).with_canonical_order();
+        for &i in order {
+            let (x, y, z) = (
+                i as i32 * 7 % 40 - 20,
+                i as i32 % 13,
+                i as i32 * 5 % 37 - 18,
+            );
+            let id = format!("minecraft:block_{}", i % 9);
+            let nbt = (i % 10 == 0).then(|| vec![i as u8; 4]);
+            if i % 4 == 0 {
+                encoder
+                    .add_block_with_overlay(id, "minecraft:water".to_string(), x, y, z, nbt)
+                    .unwrap();
+            } else {
+                encoder.add_block(id, x, y, z, nbt).unwrap();
+            }
+        }
+        encoder
+    }
+
+    fn write_bytes(encoder: &McsEncoder) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        encoder.write_to_writer(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn canonical_bytes_are_stable() {
+        let forward: Vec<usize> = (0..200).collect();
+        let shuffled: Vec<usize> = (0..200).map(|i| i * 37 % 200).collect();
+        let a = write_bytes(&canonical_sample(&forward));
+        let b = write_bytes(&canonical_sample(&shuffled));
+        assert_eq!(a, b);
+
+        let mut plain = canonical_sample(&shuffled);
+        plain.canonical = false;
+        assert_ne!(write_bytes(&plain), a);
+
+        // 规范输出在不同版本之间保持不变，修改序列化方式时须同时更新此值
+        let digest: String = Sha256::digest(&a)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(
+            digest,
+            "0ee3e4b8b33349fb689e43bcc8e0ef09c0bf3b8e1f30cf16738a16723e44b37e"
+        );
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn compression_level_is_recorded() {
+        let mut encoder = McsEncoder::new(CompressionType::Zstandard).with_compression_level(19);
+        add_blocks(&mut encoder);
+        let mut file = NamedTempFile::new().unwrap();
+        encoder.write_to_writer(file.as_file_mut()).unwrap();
+
+        let decoder = McsDecoder::from_file(file.path()).unwrap();
+        assert_eq!(decoder.compression_level(), Some(19));
+        let reencoded = McsEncoder::from_decoder(decoder, CompressionType::Zstandard).unwrap();
+        assert_eq!(reencoded.compression_level, Some(19));
+
+        let mut plain = McsEncoder::new(CompressionType::Zstandard);
+        add_blocks(&mut plain);
+        let mut file = NamedTempFile::new().unwrap();
+        plain.write_to_writer(file.as_file_mut()).unwrap();
+        assert_eq!(
+            McsDecoder::from_file(file.path())
+                .unwrap()
+                .compression_level(),
+            None
+        );
+
+        for (compression, level) in [(CompressionType::Zstandard, 23), (CompressionType::None, 3)] {
+            let mut encoder = McsEncoder::new(compression).with_compression_level(level);
+            add_blocks(&mut encoder);
+            assert!(encoder.write_to_writer(&mut Vec::new()).is_err());
+        }
     }
 }
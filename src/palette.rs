@@ -1,7 +1,117 @@
-use crate::error::McStreamError;
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-use std::collections::HashMap;
+use crate::{
+    error::McStreamError,
+    utils::{read_u16_field, read_u32_field, write_u16_field, write_u32_field},
+};
+use sha2::{Digest, Sha256};
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::hash::Hash;
 use std::io::{Read, Write};
+use std::ops::Index;
+use std::sync::{Arc, Mutex};
+
+/// 调色板条目的最大字节数
+pub const MAX_ID_LEN: usize = u16::MAX as usize;
+
+/// 默认的2字节调色板索引可容纳的条目数，更大的调色板须以4字节索引写入
+pub const MAX_PALETTE_LEN: usize = u16::MAX as usize;
+
+/// 方块ID超过长度上限时的处理方式
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum IdPolicy {
+    /// 返回错误
+    #[default]
+    Reject,
+    /// 在字符边界处截断到长度上限
+    Truncate,
+    /// 替换为指定的ID
+    Replace(String),
+}
+
+impl IdPolicy {
+    /// 按策略处理方块ID，长度合法的ID原样返回
+    pub fn apply(&self, block_id: String) -> Result<String, McStreamError> {
+        if block_id.len() <= MAX_ID_LEN {
+            return Ok(block_id);
+        }
+
+        match self {
+            IdPolicy::Reject => Err(McStreamError::PaletteError(format!(
+                "调色板条目长度超过上限: {} 字节",
+                block_id.len()
+            ))),
+            IdPolicy::Truncate => {
+                log::warn!("方块ID超过 {} 字节，已截断: {}", MAX_ID_LEN, block_id);
+                let mut end = MAX_ID_LEN;
+                while !block_id.is_char_boundary(end) {
+                    end -= 1;
+                }
+                let mut block_id = block_id;
+                block_id.truncate(end);
+                Ok(block_id)
+            }
+            IdPolicy::Replace(replacement) if replacement.len() <= MAX_ID_LEN => {
+                log::warn!(
+                    "方块ID超过 {} 字节，已替换为 {}: {}",
+                    MAX_ID_LEN,
+                    replacement,
+                    block_id
+                );
+                Ok(replacement.clone())
+            }
+            IdPolicy::Replace(_) => Err(McStreamError::PaletteError(
+                "替换用的调色板条目长度超过上限".to_string(),
+            )),
+        }
+    }
+}
+
+/// 解析后的方块状态：方块名与属性，如 `minecraft:chest[facing=north]`
+///
+/// 属性按名称排序，[`Display`](fmt::Display) 输出的规范形式与属性的书写顺序无关。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BlockState {
+    pub name: String,
+    pub properties: BTreeMap<String, String>,
+}
+
+impl BlockState {
+    /// 解析方块ID；不含 `[...]` 或括号不完整时整个字符串为方块名，没有 `=` 的属性值为空
+    pub fn parse(block_id: &str) -> Self {
+        let (name, properties) = match (block_id.find('['), block_id.strip_suffix(']')) {
+            (Some(start), Some(rest)) if start < rest.len() => (
+                &block_id[..start],
+                rest[start + 1..]
+                    .split(',')
+                    .filter(|p| !p.is_empty())
+                    .map(|p| p.split_once('=').unwrap_or((p, "")))
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            ),
+            _ => (block_id, BTreeMap::new()),
+        };
+        Self {
+            name: name.to_string(),
+            properties,
+        }
+    }
+}
+
+impl fmt::Display for BlockState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if self.properties.is_empty() {
+            return Ok(());
+        }
+        let properties: Vec<String> = self
+            .properties
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        write!(f, "[{}]", properties.join(","))
+    }
+}
 
 /// 验证调色板是否合法（不能包含空气方块）
 pub fn validate_palette(palette: &[String]) -> Result<(), McStreamError> {
@@ -12,32 +122,65 @@ pub fn validate_palette(palette: &[String]) -> Result<(), McStreamError> {
     Ok(())
 }
 
+/// 方块ID的命名空间，不含冒号的ID视为 `minecraft`
+pub fn namespace_of(block_id: &str) -> &str {
+    // 方块状态（`[...]`）中可能含有冒号，只看其前面的部分
+    let name = block_id.split('[').next().unwrap_or_default();
+    name.split_once(':')
+        .map_or("minecraft", |(namespace, _)| namespace)
+}
+
+/// 验证方块ID的命名空间在允许的列表中
+pub fn validate_namespace(block_id: &str, allowed: &[String]) -> Result<(), McStreamError> {
+    let namespace = namespace_of(block_id);
+    if allowed.iter().any(|a| a == namespace) {
+        return Ok(());
+    }
+
+    Err(McStreamError::PaletteError(format!(
+        "方块ID {} 的命名空间 {} 不在允许的列表中",
+        block_id, namespace
+    )))
+}
+
 /// 写入调色板到数据流
 pub fn write_palette<W: Write>(writer: &mut W, palette: &[String]) -> Result<(), McStreamError> {
+    write_palette_as(writer, palette, false)
+}
+
+/// 写入调色板到数据流，`varint` 为真时大小与长度使用变长整数
+pub fn write_palette_as<W: Write>(
+    writer: &mut W,
+    palette: &[String],
+    varint: bool,
+) -> Result<(), McStreamError> {
+    write_palette_sized(writer, palette, varint, false)
+}
+
+/// 写入调色板到数据流，`wide` 为真时调色板大小为4字节（见 [`CHUNK_FORMAT_WIDE_INDEX`](crate::chunk::CHUNK_FORMAT_WIDE_INDEX)）
+pub fn write_palette_sized<W: Write>(
+    writer: &mut W,
+    palette: &[String],
+    varint: bool,
+    wide: bool,
+) -> Result<(), McStreamError> {
     // 验证调色板
     validate_palette(palette)?;
 
-    // 调色板大小必须小于等于u16的最大值
-    if palette.len() > u16::MAX as usize {
-        return Err(McStreamError::PaletteError(
-            "调色板条目数超过上限".to_string(),
-        ));
-    }
-
-    // 写入调色板大小（2字节，小端）
-    writer.write_u16::<LittleEndian>(palette.len() as u16)?;
+    // 写入调色板大小（2或4字节小端，或变长整数）
+    write_palette_len(writer, palette.len(), varint, wide)?;
 
     // 写入每个调色板条目
     for entry in palette {
         // 块ID长度必须小于等于u16的最大值
-        if entry.len() > u16::MAX as usize {
+        if entry.len() > MAX_ID_LEN {
             return Err(McStreamError::PaletteError(
                 "调色板条目长度超过上限".to_string(),
             ));
         }
 
-        // 写入字符串长度（2字节，小端）
-        writer.write_u16::<LittleEndian>(entry.len() as u16)?;
+        // 写入字符串长度（2字节小端，或变长整数）
+        write_u16_field(writer, entry.len() as u16, varint)?;
 
         // 写入字符串内容
         writer.write_all(entry.as_bytes())?;
@@ -48,21 +191,49 @@ pub fn write_palette<W: Write>(writer: &mut W, palette: &[String]) -> Result<(),
 
 /// 从数据流读取调色板
 pub fn read_palette<R: Read>(reader: &mut R) -> Result<Vec<String>, McStreamError> {
-    // 读取调色板大小（2字节，小端）
-    let palette_size = reader.read_u16::<LittleEndian>()?;
+    read_palette_as(reader, false)
+}
+
+/// 从数据流读取调色板，`varint` 须与写入时一致
+pub fn read_palette_as<R: Read>(
+    reader: &mut R,
+    varint: bool,
+) -> Result<Vec<String>, McStreamError> {
+    read_palette_with(reader, varint, &mut str::to_owned)
+}
 
-    // 读取每个调色板条目
-    let mut palette = Vec::with_capacity(palette_size as usize);
+/// 从数据流读取调色板，每个条目经 `intern` 转换为调色板元素（如 [`PaletteInterner`] 中的共享ID）
+pub fn read_palette_with<R: Read, S>(
+    reader: &mut R,
+    varint: bool,
+    intern: &mut impl FnMut(&str) -> S,
+) -> Result<Vec<S>, McStreamError> {
+    read_palette_sized(reader, varint, false, intern)
+}
+
+/// 从数据流读取调色板，`wide` 须与写入时一致（见 [`write_palette_sized`]）
+pub fn read_palette_sized<R: Read, S>(
+    reader: &mut R,
+    varint: bool,
+    wide: bool,
+    intern: &mut impl FnMut(&str) -> S,
+) -> Result<Vec<S>, McStreamError> {
+    // 读取调色板大小（2或4字节小端，或变长整数）
+    let palette_size = read_palette_len(reader, varint, wide)?;
+
+    // 读取每个调色板条目，字符串内容读入可复用的缓冲区
+    let mut palette = Vec::with_capacity(bounded_capacity(palette_size));
+    let mut buffer = Vec::new();
     for _ in 0..palette_size {
-        // 读取字符串长度（2字节，小端）
-        let str_len = reader.read_u16::<LittleEndian>()?;
+        // 读取字符串长度（2字节小端，或变长整数）
+        let str_len = read_u16_field(reader, varint)?;
 
         // 读取字符串内容
-        let mut buffer = vec![0u8; str_len as usize];
+        buffer.resize(str_len as usize, 0);
         reader.read_exact(&mut buffer)?;
 
         // 转换为UTF-8字符串
-        let entry = String::from_utf8(buffer)
+        let entry = std::str::from_utf8(&buffer)
             .map_err(|_| McStreamError::PaletteError("非UTF-8编码的调色板条目".to_string()))?;
 
         // 验证不能包含空气方块
@@ -70,39 +241,393 @@ pub fn read_palette<R: Read>(reader: &mut R) -> Result<Vec<String>, McStreamErro
             return Err(McStreamError::AirInPalette);
         }
 
-        palette.push(entry);
+        palette.push(intern(entry));
     }
 
     Ok(palette)
 }
 
-/// 根据方块ID列表生成调色板
-pub fn create_palette(
-    block_ids: &[String],
-) -> Result<(Vec<String>, HashMap<String, u16>), McStreamError> {
-    let mut unique_ids = Vec::new();
-    let mut id_to_index = HashMap::new();
+// 默认的调色板大小为2字节，超过 MAX_PALETTE_LEN 项时须使用4字节
+fn write_palette_len<W: Write>(
+    writer: &mut W,
+    len: usize,
+    varint: bool,
+    wide: bool,
+) -> Result<(), McStreamError> {
+    if wide {
+        let len = u32::try_from(len)
+            .map_err(|_| McStreamError::PaletteError("调色板条目数超过上限".to_string()))?;
+        return write_u32_field(writer, len, varint);
+    }
+    if len > MAX_PALETTE_LEN {
+        return Err(McStreamError::PaletteError(format!(
+            "调色板有 {} 个条目，超过2字节索引的上限 {}，需使用4字节索引",
+            len, MAX_PALETTE_LEN
+        )));
+    }
+    write_u16_field(writer, len as u16, varint)
+}
+
+fn read_palette_len<R: Read>(
+    reader: &mut R,
+    varint: bool,
+    wide: bool,
+) -> Result<u32, McStreamError> {
+    if wide {
+        read_u32_field(reader, varint)
+    } else {
+        Ok(read_u16_field(reader, varint)? as u32)
+    }
+}
 
-    for id in block_ids {
-        // 跳过空气方块
-        if id.contains("minecraft:air") {
-            continue;
+// 条目数来自文件，预分配的容量不超过一个区块可能用到的条目数
+fn bounded_capacity(len: u32) -> usize {
+    (len as usize).min(u16::MAX as usize)
+}
+
+/// 方块ID表，把常用的方块ID映射为数字，区块调色板中只需存储编号
+///
+/// 表本身不写入文件，区块中只记录表ID，编码与解码须使用相同的表。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdTable {
+    ids: Vec<String>,
+    lookup: HashMap<String, u16>,
+    table_id: u32,
+}
+
+impl IdTable {
+    /// 按给定顺序建立ID表，第 `n` 个ID的编号为 `n`
+    pub fn new(ids: Vec<String>) -> Result<Self, McStreamError> {
+        // 编号0保留给内联字符串，表中最多容纳 u16::MAX - 1 个ID
+        if ids.len() >= u16::MAX as usize {
+            return Err(McStreamError::PaletteError(
+                "ID表条目数超过上限".to_string(),
+            ));
         }
 
-        // 如果这个ID不在调色板中，添加它
-        if !id_to_index.contains_key(id) {
-            let index = unique_ids.len() as u16;
-            unique_ids.push(id.clone());
-            id_to_index.insert(id.clone(), index);
+        let mut lookup = HashMap::with_capacity(ids.len());
+        let mut hasher = Sha256::new();
+        for (index, id) in ids.iter().enumerate() {
+            if id.len() > MAX_ID_LEN {
+                return Err(McStreamError::PaletteError(
+                    "ID表条目长度超过上限".to_string(),
+                ));
+            }
+            if lookup.insert(id.clone(), index as u16).is_some() {
+                return Err(McStreamError::PaletteError(format!(
+                    "ID表中 {} 重复出现",
+                    id
+                )));
+            }
+            hasher.update((id.len() as u16).to_le_bytes());
+            hasher.update(id.as_bytes());
         }
+
+        let hash = hasher.finalize();
+        let table_id = u32::from_le_bytes([hash[0], hash[1], hash[2], hash[3]]);
+        Ok(Self {
+            ids,
+            lookup,
+            table_id,
+        })
+    }
+
+    /// 从每行一个方块ID的文本建立ID表，忽略空行
+    pub fn from_lines(text: &str) -> Result<Self, McStreamError> {
+        Self::new(
+            text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        )
+    }
+
+    /// 方块ID的编号，不在表中时为 `None`
+    pub fn index_of(&self, id: &str) -> Option<u16> {
+        self.lookup.get(id).copied()
+    }
+
+    /// 编号对应的方块ID
+    pub fn get(&self, index: u16) -> Option<&str> {
+        self.ids.get(index as usize).map(String::as_str)
+    }
+
+    /// 表ID（各条目SHA-256的前4字节，小端），用于确认编码与解码使用同一张表
+    pub fn table_id(&self) -> u32 {
+        self.table_id
     }
 
-    // 检查调色板大小
-    if unique_ids.len() > u16::MAX as usize {
-        return Err(McStreamError::PaletteError(
-            "调色板条目数超过上限".to_string(),
-        ));
+    pub fn len(&self) -> usize {
+        self.ids.len()
     }
 
-    Ok((unique_ids, id_to_index))
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+/// 按ID表写入调色板：每个条目以编号开头，0表示其后为内联字符串，`n` 表示表中第 `n - 1` 个ID
+pub fn write_palette_with_table<W: Write>(
+    writer: &mut W,
+    palette: &[String],
+    varint: bool,
+    table: &IdTable,
+) -> Result<(), McStreamError> {
+    write_palette_with_table_sized(writer, palette, varint, false, table)
+}
+
+/// 按ID表写入调色板，`wide` 为真时调色板大小为4字节
+pub fn write_palette_with_table_sized<W: Write>(
+    writer: &mut W,
+    palette: &[String],
+    varint: bool,
+    wide: bool,
+    table: &IdTable,
+) -> Result<(), McStreamError> {
+    validate_palette(palette)?;
+
+    write_palette_len(writer, palette.len(), varint, wide)?;
+
+    for entry in palette {
+        match table.index_of(entry) {
+            Some(index) => write_u16_field(writer, index + 1, varint)?,
+            None => {
+                if entry.len() > MAX_ID_LEN {
+                    return Err(McStreamError::PaletteError(
+                        "调色板条目长度超过上限".to_string(),
+                    ));
+                }
+                write_u16_field(writer, 0, varint)?;
+                write_u16_field(writer, entry.len() as u16, varint)?;
+                writer.write_all(entry.as_bytes())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 读取按ID表写入的调色板（见 [`write_palette_with_table`]）
+pub fn read_palette_with_table<R: Read, S>(
+    reader: &mut R,
+    varint: bool,
+    table: &IdTable,
+    intern: &mut impl FnMut(&str) -> S,
+) -> Result<Vec<S>, McStreamError> {
+    read_palette_with_table_sized(reader, varint, false, table, intern)
+}
+
+/// 读取按ID表写入的调色板，`wide` 须与写入时一致
+pub fn read_palette_with_table_sized<R: Read, S>(
+    reader: &mut R,
+    varint: bool,
+    wide: bool,
+    table: &IdTable,
+    intern: &mut impl FnMut(&str) -> S,
+) -> Result<Vec<S>, McStreamError> {
+    let palette_size = read_palette_len(reader, varint, wide)?;
+
+    let mut palette = Vec::with_capacity(bounded_capacity(palette_size));
+    let mut buffer = Vec::new();
+    for _ in 0..palette_size {
+        let code = read_u16_field(reader, varint)?;
+        let entry = if code == 0 {
+            let str_len = read_u16_field(reader, varint)?;
+            buffer.resize(str_len as usize, 0);
+            reader.read_exact(&mut buffer)?;
+            std::str::from_utf8(&buffer)
+                .map_err(|_| McStreamError::PaletteError("非UTF-8编码的调色板条目".to_string()))?
+        } else {
+            table.get(code - 1).ok_or_else(|| {
+                McStreamError::PaletteError(format!("ID表中不存在编号 {}", code - 1))
+            })?
+        };
+
+        if entry.contains("minecraft:air") {
+            return Err(McStreamError::AirInPalette);
+        }
+
+        palette.push(intern(entry));
+    }
+
+    Ok(palette)
+}
+
+/// 方块ID池，相同的ID共享同一份存储，可在多个线程间共享
+#[derive(Debug, Default)]
+pub struct PaletteInterner {
+    pool: Mutex<HashSet<Arc<str>>>,
+}
+
+impl PaletteInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 取得与 `id` 相同的共享ID，首次出现时加入池中
+    pub fn intern(&self, id: &str) -> Arc<str> {
+        let mut pool = self.pool.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(shared) = pool.get(id) {
+            return Arc::clone(shared);
+        }
+        let shared: Arc<str> = Arc::from(id);
+        pool.insert(Arc::clone(&shared));
+        shared
+    }
+
+    /// 池中不同ID的数量
+    pub fn len(&self) -> usize {
+        self.pool.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// 池是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// 可作为调色板元素的方块ID（如 `String`、`Arc<str>`）
+pub trait PaletteId: Borrow<str> + Hash + Eq + Clone {}
+
+impl<S: Borrow<str> + Hash + Eq + Clone> PaletteId for S {}
+
+/// 区块调色板，按索引顺序保存方块ID，同时维护ID到索引的映射
+///
+/// 从已有列表构建时保留重复的条目（只可能来自外部写入的文件），映射指向第一次出现的位置。
+#[derive(Debug, Clone)]
+pub struct Palette<S = String> {
+    ids: Vec<S>,
+    lookup: HashMap<S, u32>,
+}
+
+impl<S> Default for Palette<S> {
+    fn default() -> Self {
+        Self {
+            ids: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+}
+
+impl<S> Palette<S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 条目数
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// 调色板是否为空
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// 按索引顺序遍历条目
+    pub fn iter(&self) -> std::slice::Iter<'_, S> {
+        self.ids.iter()
+    }
+
+    /// 全部条目，下标即调色板索引
+    pub fn as_slice(&self) -> &[S] {
+        &self.ids
+    }
+
+    pub fn into_vec(self) -> Vec<S> {
+        self.ids
+    }
+}
+
+impl<S: Borrow<str>> Palette<S> {
+    /// 索引对应的方块ID
+    pub fn get(&self, index: u32) -> Option<&str> {
+        self.ids.get(index as usize).map(Borrow::borrow)
+    }
+}
+
+impl<S: PaletteId> Palette<S> {
+    /// 方块ID在调色板中的索引
+    pub fn index_of(&self, block_id: &str) -> Option<u32> {
+        self.lookup.get(block_id).copied()
+    }
+
+    /// 获取方块ID的索引，不存在时追加
+    ///
+    /// 空气方块返回 `AirInPalette`，条目数已达上限（`u32::MAX`）时返回 `PaletteError`。
+    /// 超过 [`MAX_PALETTE_LEN`] 项的调色板只能以4字节索引写入。
+    pub fn get_or_insert(&mut self, block_id: S) -> Result<u32, McStreamError> {
+        let key: &str = block_id.borrow();
+        if let Some(&index) = self.lookup.get(key) {
+            return Ok(index);
+        }
+        if key.contains("minecraft:air") {
+            return Err(McStreamError::AirInPalette);
+        }
+        if self.ids.len() >= u32::MAX as usize {
+            return Err(McStreamError::PaletteError(
+                "调色板条目数超过上限".to_string(),
+            ));
+        }
+
+        let index = self.ids.len() as u32;
+        self.lookup.insert(block_id.clone(), index);
+        self.ids.push(block_id);
+        Ok(index)
+    }
+
+    /// 移除索引处的条目，其后条目的索引减一，方块中的索引由调用方调整
+    pub fn remove(&mut self, index: u32) -> S {
+        let removed = self.ids.remove(index as usize);
+        *self = Self::from(std::mem::take(&mut self.ids));
+        removed
+    }
+}
+
+impl<S: PaletteId> From<Vec<S>> for Palette<S> {
+    fn from(ids: Vec<S>) -> Self {
+        let mut lookup = HashMap::with_capacity(ids.len());
+        for (index, id) in ids.iter().enumerate().take(u32::MAX as usize) {
+            lookup.entry(id.clone()).or_insert(index as u32);
+        }
+        Self { ids, lookup }
+    }
+}
+
+impl<S> Index<u32> for Palette<S> {
+    type Output = S;
+
+    fn index(&self, index: u32) -> &S {
+        &self.ids[index as usize]
+    }
+}
+
+impl<S> IntoIterator for Palette<S> {
+    type Item = S;
+    type IntoIter = std::vec::IntoIter<S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ids.into_iter()
+    }
+}
+
+impl<'a, S> IntoIterator for &'a Palette<S> {
+    type Item = &'a S;
+    type IntoIter = std::slice::Iter<'a, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ids.iter()
+    }
+}
+
+/// 根据方块ID列表生成调色板，空气方块被跳过，重复的ID只保留一次
+pub fn create_palette(block_ids: &[String]) -> Result<Palette, McStreamError> {
+    let mut palette = Palette::new();
+    for id in block_ids {
+        if !id.contains("minecraft:air") {
+            palette.get_or_insert(id.clone())?;
+        }
+    }
+    Ok(palette)
 }
@@ -0,0 +1,172 @@
+// 逐个读取区块的迭代器：只读取一次索引表，每次 `next` 才读取并解压一个区块
+// 适合逐区块处理后即丢弃的流水线，内存中最多只有一个区块
+
+use crate::{
+    error::McStreamError,
+    types::{ChunkData, ChunkIndexEntry, ChunkPos, McsHeader},
+    unpacker::{
+        decode_raw_chunk_with, read_validated_index_from, validate_dictionary, DecodeOptions,
+    },
+};
+use std::io::{Read, Seek, SeekFrom};
+
+/// 按索引顺序逐个产出区块的读取器
+///
+/// 拆分存储的区块（相邻的同坐标条目）合并后作为一个区块产出。某个区块解码失败时产出错误，
+/// 之后仍可继续读取其余区块。数据须为未经gzip包裹的MCS文件内容。
+pub struct McsChunkReader<R> {
+    reader: R,
+    header: McsHeader,
+    entries: Vec<ChunkIndexEntry>,
+    next: usize,
+    options: DecodeOptions,
+}
+
+impl<R: Read + Seek> McsChunkReader<R> {
+    /// 读取头部与索引表，使用默认的解码选项
+    pub fn new(reader: R) -> Result<Self, McStreamError> {
+        Self::with_options(reader, DecodeOptions::default())
+    }
+
+    /// 读取头部与索引表，按 `options` 解码区块
+    ///
+    /// 支持 `keep_nbt_compressed`、`min_block_count`、`max_decompressed_size`、`skip_nbt`、
+    /// `dictionary` 与 `id_table`；并行相关的选项不起作用。
+    pub fn with_options(mut reader: R, options: DecodeOptions) -> Result<Self, McStreamError> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let (header, entries) = read_validated_index_from(&mut reader, file_size)?;
+        validate_dictionary(&header, &options)?;
+
+        Ok(Self {
+            reader,
+            header,
+            entries,
+            next: 0,
+            options,
+        })
+    }
+
+    /// 获取文件头
+    pub fn header(&self) -> &McsHeader {
+        &self.header
+    }
+
+    /// 索引表中的全部条目（拆分的区块占多个条目）
+    pub fn entries(&self) -> &[ChunkIndexEntry] {
+        &self.entries
+    }
+
+    // 读取并解码一个条目
+    fn read_entry(&mut self, entry: &ChunkIndexEntry) -> Result<ChunkData, McStreamError> {
+        self.reader
+            .seek(SeekFrom::Start(entry.data_offset as u64))?;
+        let mut compressed = vec![0u8; entry.compressed_size as usize];
+        self.reader.read_exact(&mut compressed)?;
+
+        let (mut chunk, _, _) = decode_raw_chunk_with(
+            &compressed,
+            entry,
+            &self.header,
+            &self.options,
+            None,
+            &mut str::to_owned,
+        )?;
+        if !self.options.keep_nbt_compressed {
+            for block in &mut chunk.blocks {
+                block.decompress_nbt_in_place()?;
+            }
+        }
+        Ok(chunk)
+    }
+
+    // 读取 `start..end` 的条目（同一区块拆分后的各部分）并合并，总方块数少于下限时返回 `None`
+    fn read_chunk(&mut self, start: usize, end: usize) -> Result<Option<ChunkData>, McStreamError> {
+        let total = self.entries[start..end]
+            .iter()
+            .try_fold(0u64, |total, entry| Some(total + entry.block_count? as u64));
+        if total.is_some_and(|total| total < self.options.min_block_count as u64) {
+            return Ok(None);
+        }
+
+        let mut merged: Option<ChunkData> = None;
+        for i in start..end {
+            let entry = self.entries[i];
+            let part = self.read_entry(&entry)?;
+            match merged.as_mut() {
+                Some(chunk) => chunk.merge(part)?,
+                None => merged = Some(part),
+            }
+        }
+        Ok(merged)
+    }
+}
+
+impl<R: Read + Seek> Iterator for McsChunkReader<R> {
+    type Item = Result<(ChunkPos, ChunkData), McStreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next < self.entries.len() {
+            let start = self.next;
+            let first = self.entries[start];
+            let pos = ChunkPos::new(first.chunk_x, first.chunk_z);
+            let end = start
+                + self.entries[start..]
+                    .iter()
+                    .take_while(|e| (e.chunk_x, e.chunk_z) == (first.chunk_x, first.chunk_z))
+                    .count();
+            self.next = end;
+
+            match self.read_chunk(start, end) {
+                Ok(Some(chunk)) => {
+                    log::debug!(
+                        "读取区块 ({}, {})：{} 个方块",
+                        pos.x,
+                        pos.z,
+                        chunk.blocks.len()
+                    );
+                    return Some(Ok((pos, chunk)));
+                }
+                // 方块数少于下限
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{packer::McsEncoder, CompressionType};
+    use std::io::Cursor;
+
+    #[test]
+    fn min_block_count_sums_split_chunks() {
+        let mut encoder = McsEncoder::new(CompressionType::None).with_max_chunk_bytes(64);
+        for y in 0..10 {
+            encoder
+                .add_block(format!("minecraft:block_{}", y), 0, y, 0, None)
+                .unwrap();
+        }
+        for x in 0..3 {
+            encoder
+                .add_block("minecraft:stone".to_string(), 16 + x, 0, 0, None)
+                .unwrap();
+        }
+        let mut bytes = Vec::new();
+        encoder.write_to_writer(&mut bytes).unwrap();
+
+        let options = DecodeOptions {
+            min_block_count: 8,
+            ..DecodeOptions::default()
+        };
+        let reader = McsChunkReader::with_options(Cursor::new(bytes), options).unwrap();
+        assert!(reader.entries().len() > 2);
+        let chunks: Vec<_> = reader.map(Result::unwrap).collect();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].0, ChunkPos::new(0, 0));
+        assert_eq!(chunks[0].1.blocks.len(), 10);
+    }
+}
@@ -1,4 +1,14 @@
-use std::collections::HashMap;
+use crate::{
+    compression::decompress_data,
+    error::McStreamError,
+    format::BLOCK_RECORD_SIZE,
+    palette::{BlockState, Palette, PaletteId},
+    CompressionType,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// 区块的体积（16×384×16）
+pub const CHUNK_VOLUME: usize = 16 * 384 * 16;
 
 /// 方块位置（全局坐标）
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -22,10 +32,13 @@ impl BlockPos {
     }
 
     /// 获取相对于所在区块的局部坐标
+    ///
+    /// 负坐标同样适用：`x = -17` 位于区块 `-2` 的局部坐标 `15`。
+    /// Y超出可编码范围时编码为 `u16::MAX`，可由 `validate_local_pos` 拒绝，不会回绕到有效范围内。
     pub fn local_pos(&self) -> LocalBlockPos {
         LocalBlockPos {
             x: (self.x & 0xF) as u8,
-            y: (self.y + 64) as u16, // 编码Y坐标
+            y: u16::try_from(self.y.saturating_add(64)).unwrap_or(u16::MAX), // 编码Y坐标
             z: (self.z & 0xF) as u8,
         }
     }
@@ -42,6 +55,79 @@ impl ChunkPos {
     pub fn new(x: i32, z: i32) -> Self {
         Self { x, z }
     }
+
+    /// Morton（Z序）编码，相邻区块的编码也相近
+    pub fn morton_code(&self) -> u64 {
+        // 翻转符号位，使负坐标排在正坐标之前
+        spread_bits(self.x as u32 ^ 0x8000_0000) | (spread_bits(self.z as u32 ^ 0x8000_0000) << 1)
+    }
+}
+
+// 将32位整数的每一位间隔展开到64位的偶数位上
+fn spread_bits(value: u32) -> u64 {
+    let mut v = value as u64;
+    v = (v | (v << 16)) & 0x0000_FFFF_0000_FFFF;
+    v = (v | (v << 8)) & 0x00FF_00FF_00FF_00FF;
+    v = (v | (v << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    v = (v | (v << 2)) & 0x3333_3333_3333_3333;
+    v = (v | (v << 1)) & 0x5555_5555_5555_5555;
+    v
+}
+
+/// 区块的遍历顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkOrder {
+    /// 按 `(x, z)` 字典序
+    #[default]
+    Linear,
+    /// 按Morton（Z序）编码，空间局部性更好
+    Morton,
+}
+
+/// 方块六个面的集合
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FaceMask(u8);
+
+impl FaceMask {
+    pub const DOWN: Self = Self(0x01); // -Y
+    pub const UP: Self = Self(0x02); // +Y
+    pub const NORTH: Self = Self(0x04); // -Z
+    pub const SOUTH: Self = Self(0x08); // +Z
+    pub const WEST: Self = Self(0x10); // -X
+    pub const EAST: Self = Self(0x20); // +X
+
+    /// 全部六个面
+    pub const ALL: Self = Self(0x3F);
+
+    /// 每个面及其对应的相邻方块偏移
+    pub const DIRECTIONS: [(Self, (i32, i32, i32)); 6] = [
+        (Self::DOWN, (0, -1, 0)),
+        (Self::UP, (0, 1, 0)),
+        (Self::NORTH, (0, 0, -1)),
+        (Self::SOUTH, (0, 0, 1)),
+        (Self::WEST, (-1, 0, 0)),
+        (Self::EAST, (1, 0, 0)),
+    ];
+
+    /// 原始位
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// 是否包含 `other` 中的全部面
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// 是否为空集合
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// 加入 `other` 中的面
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
 }
 
 /// 区块内的局部方块坐标
@@ -61,22 +147,99 @@ impl LocalBlockPos {
     pub fn actual_y(&self) -> i32 {
         self.y as i32 - 64
     }
+
+    /// 所在的16格高的段（与原版一致，为实际Y坐标 `>> 4`，可以为负）
+    pub fn section_y(&self) -> i32 {
+        self.actual_y() >> 4
+    }
+
+    /// 根据所在区块坐标还原全局坐标
+    pub fn to_global(&self, chunk: ChunkPos) -> BlockPos {
+        BlockPos {
+            x: (chunk.x * 16) + self.x as i32,
+            y: self.actual_y(),
+            z: (chunk.z * 16) + self.z as i32,
+        }
+    }
 }
 
 /// 区块数据
+///
+/// 调色板元素默认为 `String`；共享存储的版本为 `ChunkData<Arc<str>>`（见 `PaletteInterner`）。
 #[derive(Debug, Clone)]
-pub struct ChunkData {
+pub struct ChunkData<S = String> {
     pub pos: ChunkPos,
-    pub palette: Vec<String>, // 方块ID列表
-    pub blocks: Vec<Block>,   // 非空气方块列表
+    pub palette: Palette<S>, // 方块ID列表
+    pub blocks: Vec<Block>,  // 非空气方块列表
 }
 
 /// 方块数据
 #[derive(Debug, Clone)]
 pub struct Block {
-    pub palette_index: u16,   // 调色板索引
-    pub pos: LocalBlockPos,   // 局部坐标
-    pub nbt: Option<Vec<u8>>, // NBT数据（如果有）
+    pub palette_index: u32,                       // 调色板索引
+    pub pos: LocalBlockPos,                       // 局部坐标
+    pub nbt: Option<Vec<u8>>,                     // NBT数据（如果有）
+    pub nbt_compression: Option<CompressionType>, // NBT数据单独压缩时使用的算法
+    pub overlay: Option<u32>,                     // 覆盖层（如含水方块中的水）的调色板索引
+    pub tags: u16, // 编辑器标签位（如标记点、不粘贴），含义由工具约定，0表示无标签
+}
+
+impl Block {
+    /// 获取解压后的NBT数据
+    ///
+    /// NBT未单独压缩时直接返回其副本。
+    pub fn nbt_decompressed(&self) -> Result<Option<Vec<u8>>, McStreamError> {
+        match (&self.nbt, self.nbt_compression) {
+            (Some(nbt), Some(compression)) => Ok(Some(decompress_data(nbt, compression)?)),
+            (Some(nbt), None) => Ok(Some(nbt.clone())),
+            (None, _) => Ok(None),
+        }
+    }
+
+    /// 按内容比较两个方块，`palette` 与 `other_palette` 分别为两者所在区块的调色板
+    ///
+    /// 比较方块ID、覆盖层ID、标签位与解压后的NBT字节，不比较坐标；NBT按字节比较，
+    /// 复合标签顺序不同等语义相同的NBT视为不相等。调色板索引无效或NBT无法解压时视为不相等。
+    pub fn content_eq<S: PaletteId>(
+        &self,
+        palette: &Palette<S>,
+        other: &Block,
+        other_palette: &Palette<S>,
+    ) -> bool {
+        let ids_eq = |a: u32, b: u32| match (palette.get(a), other_palette.get(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        };
+        let overlays_eq = match (self.overlay, other.overlay) {
+            (Some(a), Some(b)) => ids_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        if !ids_eq(self.palette_index, other.palette_index)
+            || !overlays_eq
+            || self.tags != other.tags
+        {
+            return false;
+        }
+
+        // 压缩方式相同时先比较原始字节，省去解压
+        if self.nbt_compression == other.nbt_compression && self.nbt == other.nbt {
+            return true;
+        }
+        match (self.nbt_decompressed(), other.nbt_decompressed()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// 将单独压缩的NBT数据就地解压
+    pub fn decompress_nbt_in_place(&mut self) -> Result<(), McStreamError> {
+        if let (Some(nbt), Some(compression)) = (&self.nbt, self.nbt_compression) {
+            self.nbt = Some(decompress_data(nbt, compression)?);
+            self.nbt_compression = None;
+        }
+        Ok(())
+    }
 }
 
 /// 区块索引条目
@@ -86,6 +249,7 @@ pub struct ChunkIndexEntry {
     pub chunk_z: i32,
     pub data_offset: u32,
     pub compressed_size: u32,
+    pub block_count: Option<u32>, // 1.0版本的文件不含方块数
 }
 
 /// MCS格式头部
@@ -95,11 +259,653 @@ pub struct McsHeader {
     pub compression: u8,
     pub flags: u8,
     pub index_table_offset: u32,
+    pub dictionary_id: u32, // 压缩字典ID，未使用字典时为0
 }
 
+/// 调色板统计，用于评估改用全局调色板能节省多少空间
+///
+/// 字节数按定长编码、压缩前计算：调色板大小2字节，每个条目为2字节长度加ID本身。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaletteReport {
+    pub chunk_palette_sizes: Vec<(ChunkPos, usize)>, // 按 (x, z) 排序
+    pub distinct_ids: usize,                         // 所有区块中不同的方块ID数
+    pub chunk_palette_bytes: u64,                    // 各区块调色板的总字节数
+    pub global_palette_bytes: u64,                   // 单个全局调色板的字节数
+    pub estimated_bytes_saved: u64,                  // 改用全局调色板预计节省的字节数
+}
+
+impl PaletteReport {
+    /// 统计一组区块的调色板
+    pub fn from_chunks<'a, I: IntoIterator<Item = &'a ChunkData>>(chunks: I) -> Self {
+        let mut chunk_palette_sizes = Vec::new();
+        let mut chunk_palette_bytes = 0;
+        let mut distinct = HashSet::new();
+        for chunk in chunks {
+            chunk_palette_sizes.push((chunk.pos, chunk.palette.len()));
+            chunk_palette_bytes += 2 + palette_bytes(&chunk.palette);
+            distinct.extend(chunk.palette.iter());
+        }
+        chunk_palette_sizes.sort_unstable_by_key(|(pos, _)| (pos.x, pos.z));
+
+        let global_palette_bytes = 2 + palette_bytes(distinct.iter().copied());
+
+        Self {
+            chunk_palette_sizes,
+            distinct_ids: distinct.len(),
+            chunk_palette_bytes,
+            global_palette_bytes,
+            estimated_bytes_saved: chunk_palette_bytes.saturating_sub(global_palette_bytes),
+        }
+    }
+}
+
+// 调色板条目的总字节数（不含调色板大小字段）
+fn palette_bytes<'a, I: IntoIterator<Item = &'a String>>(ids: I) -> u64 {
+    ids.into_iter().map(|id| 2 + id.len() as u64).sum()
+}
+
+/// 建筑的占用统计
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Occupancy {
+    pub bounds: Option<(BlockPos, BlockPos)>, // 包围盒的最小与最大角（含端点），没有方块时为 None
+    pub volume: u64,                          // 包围盒体积，溢出时取 u64::MAX
+    pub block_count: u64,                     // 非空气方块数
+    pub fill_ratio: f64,                      // block_count / volume，没有方块时为0
+}
+
+impl Occupancy {
+    /// 扫描一组区块的方块
+    pub fn from_chunks<'a, I: IntoIterator<Item = &'a ChunkData>>(chunks: I) -> Self {
+        let mut bounds: Option<(BlockPos, BlockPos)> = None;
+        let mut block_count = 0u64;
+        for chunk in chunks {
+            for block in &chunk.blocks {
+                let pos = block.pos.to_global(chunk.pos);
+                bounds = Some(match bounds {
+                    Some((min, max)) => (
+                        BlockPos::new(min.x.min(pos.x), min.y.min(pos.y), min.z.min(pos.z)),
+                        BlockPos::new(max.x.max(pos.x), max.y.max(pos.y), max.z.max(pos.z)),
+                    ),
+                    None => (pos, pos),
+                });
+                block_count += 1;
+            }
+        }
+
+        let (size_x, size_y, size_z) = box_size(bounds);
+        let volume = size_x.saturating_mul(size_y).saturating_mul(size_z);
+        let fill_ratio = if volume == 0 {
+            0.0
+        } else {
+            block_count as f64 / volume as f64
+        };
+
+        Self {
+            bounds,
+            volume,
+            block_count,
+            fill_ratio,
+        }
+    }
+
+    /// 包围盒在 `(x, y, z)` 方向的尺寸
+    pub fn dimensions(&self) -> (u64, u64, u64) {
+        box_size(self.bounds)
+    }
+}
+
+// 包围盒各方向的尺寸（含端点）
+fn box_size(bounds: Option<(BlockPos, BlockPos)>) -> (u64, u64, u64) {
+    bounds.map_or((0, 0, 0), |(min, max)| {
+        let size = |lo: i32, hi: i32| (hi as i64 - lo as i64 + 1) as u64;
+        (size(min.x, max.x), size(min.y, max.y), size(min.z, max.z))
+    })
+}
+
+// 方块ID、覆盖层ID、解压后的NBT与标签位
+type BlockContent<'a> = (&'a str, Option<&'a str>, Option<Vec<u8>>, u16);
+
 /// 完整的MCS数据
 #[derive(Debug, Clone)]
 pub struct McsData {
     pub header: McsHeader,
     pub chunks: HashMap<ChunkPos, ChunkData>,
 }
+
+impl McsData {
+    /// 统计各区块的调色板，评估全局调色板的收益
+    pub fn palette_report(&self) -> PaletteReport {
+        PaletteReport::from_chunks(self.chunks.values())
+    }
+
+    /// 统计包围盒、体积与填充率
+    pub fn occupancy(&self) -> Occupancy {
+        Occupancy::from_chunks(self.chunks.values())
+    }
+
+    /// 按内容比较两个建筑，与区块和方块的顺序、调色板索引的分配及NBT的压缩方式无关
+    ///
+    /// 逐个全局坐标比较方块ID、覆盖层ID、解压后的NBT与标签位；调色板索引无效或NBT无法解压时视为不相等。
+    pub fn content_eq(&self, other: &McsData) -> bool {
+        match (self.content_map(), other.content_map()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    // 全局坐标 → 方块内容
+    fn content_map(&self) -> Option<HashMap<BlockPos, BlockContent<'_>>> {
+        let mut map = HashMap::new();
+        for chunk in self.chunks.values() {
+            for block in &chunk.blocks {
+                let id = chunk.palette.get(block.palette_index)?;
+                let overlay = match block.overlay {
+                    Some(index) => Some(chunk.palette.get(index)?),
+                    None => None,
+                };
+                let nbt = block.nbt_decompressed().ok()?;
+                map.insert(
+                    block.pos.to_global(chunk.pos),
+                    (id, overlay, nbt, block.tags),
+                );
+            }
+        }
+        Some(map)
+    }
+
+    /// 将区域内ID为 `from_id` 的方块替换为 `to_id`
+    ///
+    /// 区域包含 `min` 与 `max` 两个端点，主方块与覆盖层都会被替换。`to_id` 为空气时删除匹配的方块
+    /// （覆盖层则被去掉），方块与覆盖层都不再引用的 `from_id` 调色板条目会被移除。
+    /// 返回替换的次数，主方块与覆盖层分别计数。
+    pub fn replace_in_region(
+        &mut self,
+        min: BlockPos,
+        max: BlockPos,
+        from_id: &str,
+        to_id: &str,
+    ) -> Result<usize, McStreamError> {
+        if from_id.contains("minecraft:air") {
+            return Err(McStreamError::ValidationError(
+                "不能替换空气方块（空气不被存储）".to_string(),
+            ));
+        }
+        if from_id == to_id {
+            return Ok(0);
+        }
+
+        let lo = BlockPos::new(min.x.min(max.x), min.y.min(max.y), min.z.min(max.z));
+        let hi = BlockPos::new(min.x.max(max.x), min.y.max(max.y), min.z.max(max.z));
+        let (lo_chunk, hi_chunk) = (lo.chunk_pos(), hi.chunk_pos());
+        let remove = to_id.contains("minecraft:air");
+
+        let mut replaced = 0;
+        let mut emptied = Vec::new();
+
+        for (pos, chunk) in self.chunks.iter_mut() {
+            if pos.x < lo_chunk.x || pos.x > hi_chunk.x || pos.z < lo_chunk.z || pos.z > hi_chunk.z
+            {
+                continue;
+            }
+
+            let Some(from_index) = chunk.palette.index_of(from_id) else {
+                continue;
+            };
+
+            let in_region = |block: &Block| {
+                let global = block.pos.to_global(*pos);
+                (lo.x..=hi.x).contains(&global.x)
+                    && (lo.y..=hi.y).contains(&global.y)
+                    && (lo.z..=hi.z).contains(&global.z)
+            };
+
+            let matches = |block: &Block| {
+                (block.palette_index == from_index || block.overlay == Some(from_index))
+                    && in_region(block)
+            };
+            if !chunk.blocks.iter().any(matches) {
+                continue;
+            }
+
+            let to_index = if remove {
+                None
+            } else {
+                Some(chunk.palette.get_or_insert(to_id.to_string())?)
+            };
+            let mut kept = Vec::with_capacity(chunk.blocks.len());
+            for mut block in std::mem::take(&mut chunk.blocks) {
+                if in_region(&block) {
+                    if block.overlay == Some(from_index) {
+                        block.overlay = to_index;
+                        replaced += 1;
+                    }
+                    if block.palette_index == from_index {
+                        replaced += 1;
+                        match to_index {
+                            Some(index) => block.palette_index = index,
+                            None => continue,
+                        }
+                    }
+                }
+                kept.push(block);
+            }
+            chunk.blocks = kept;
+
+            chunk.remove_palette_entry_if_unused(from_index);
+            if chunk.blocks.is_empty() {
+                emptied.push(*pos);
+            }
+        }
+
+        for pos in emptied {
+            self.chunks.remove(&pos);
+        }
+
+        Ok(replaced)
+    }
+
+    /// 按解析后的方块状态改写所有调色板条目，适合跨版本迁移（如改名的同时保留朝向等属性）
+    ///
+    /// 每个条目都按 [`BlockState`] 的规范形式重新写出，即使 `f` 没有改动它；改写后相同的条目合并为一个。
+    /// 改写为空气的方块被删除（覆盖层则被去掉），变空的区块随之移除。
+    pub fn remap_palette_states<F>(&mut self, f: F) -> Result<(), McStreamError>
+    where
+        F: Fn(&BlockState) -> BlockState,
+    {
+        let mut emptied = Vec::new();
+        for (pos, chunk) in self.chunks.iter_mut() {
+            chunk.remap_palette_states(&f)?;
+            if chunk.blocks.is_empty() {
+                emptied.push(*pos);
+            }
+        }
+
+        for pos in emptied {
+            self.chunks.remove(&pos);
+        }
+        Ok(())
+    }
+}
+
+/// 区块内方块的编码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkEncoding {
+    /// 逐个记录非空气方块（MCS区块数据目前使用的方式）
+    Sparse,
+    /// 按位打包整个区块的调色板索引数组
+    Dense,
+}
+
+impl<S> ChunkData<S> {
+    /// 方块数占区块体积（16×384×16）的比例
+    pub fn density(&self) -> f32 {
+        self.blocks.len() as f32 / CHUNK_VOLUME as f32
+    }
+
+    /// 稠密编码开始比稀疏编码更小的密度
+    ///
+    /// 稠密数组每格需要 ⌈log2(调色板大小 + 1)⌉ 位（含空气），稀疏编码每个方块至少7字节。
+    pub fn dense_threshold(&self) -> f32 {
+        let states = self.palette.len() + 1;
+        let bits = (usize::BITS - (states - 1).leading_zeros()).max(1);
+        bits as f32 / (BLOCK_RECORD_SIZE * 8) as f32
+    }
+
+    /// 按密度建议更紧凑的编码方式（不计NBT与压缩），可用于分析文件体积的来源
+    pub fn recommended_encoding(&self) -> ChunkEncoding {
+        if self.density() >= self.dense_threshold() {
+            ChunkEncoding::Dense
+        } else {
+            ChunkEncoding::Sparse
+        }
+    }
+
+    /// 按16格高的段对方块分组，每次调用时由方块列表计算
+    pub fn sections(&self) -> ChunkSections<'_> {
+        let mut sections: BTreeMap<i32, Vec<&Block>> = BTreeMap::new();
+        for block in &self.blocks {
+            sections
+                .entry(block.pos.section_y())
+                .or_default()
+                .push(block);
+        }
+        ChunkSections { sections }
+    }
+}
+
+/// 区块内按段分组的方块，段内保持方块列表中的顺序
+#[derive(Debug, Clone, Default)]
+pub struct ChunkSections<'a> {
+    sections: BTreeMap<i32, Vec<&'a Block>>,
+}
+
+impl<'a> ChunkSections<'a> {
+    /// 段 `sy` 中的方块，空段返回空切片
+    pub fn section(&self, sy: i32) -> &[&'a Block] {
+        self.sections.get(&sy).map_or(&[], Vec::as_slice)
+    }
+
+    /// 按Y从低到高遍历非空的段
+    pub fn iter(&self) -> impl Iterator<Item = (i32, &[&'a Block])> {
+        self.sections
+            .iter()
+            .map(|(sy, blocks)| (*sy, blocks.as_slice()))
+    }
+
+    /// 非空段的数量
+    pub fn len(&self) -> usize {
+        self.sections.len()
+    }
+
+    /// 是否没有任何方块
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+}
+
+impl<S: PaletteId> ChunkData<S> {
+    /// 将同一坐标的另一部分区块数据并入，调色板索引会重新映射
+    ///
+    /// 同一位置的方块以并入的数据为准；合并后的调色板超过上限时返回错误。
+    pub fn merge(&mut self, other: ChunkData<S>) -> Result<(), McStreamError> {
+        let mapping: Vec<u32> = other
+            .palette
+            .into_iter()
+            .map(|id| self.palette.get_or_insert(id))
+            .collect::<Result<_, _>>()?;
+        let remap = |index: u32| mapping.get(index as usize).copied().unwrap_or(index);
+
+        let mut positions: HashMap<LocalBlockPos, usize> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (b.pos, i))
+            .collect();
+
+        for mut block in other.blocks {
+            block.palette_index = remap(block.palette_index);
+            block.overlay = block.overlay.map(remap);
+
+            match positions.get(&block.pos) {
+                Some(&i) => self.blocks[i] = block,
+                None => {
+                    positions.insert(block.pos, self.blocks.len());
+                    self.blocks.push(block);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ChunkData {
+    /// 按解析后的方块状态改写调色板，见 [`McsData::remap_palette_states`]；调色板索引无效时返回错误
+    pub fn remap_palette_states<F>(&mut self, f: F) -> Result<(), McStreamError>
+    where
+        F: Fn(&BlockState) -> BlockState,
+    {
+        let len = self.palette.len();
+        let invalid = self.blocks.iter().any(|block| {
+            block.palette_index as usize >= len
+                || block.overlay.is_some_and(|index| index as usize >= len)
+        });
+        if invalid {
+            return Err(McStreamError::PaletteError("无效的调色板索引".to_string()));
+        }
+
+        let mut palette = Palette::new();
+        let mut remap = Vec::with_capacity(len);
+        for block_id in &self.palette {
+            let block_id = f(&BlockState::parse(block_id)).to_string();
+            remap.push(if block_id.contains("minecraft:air") {
+                None
+            } else {
+                Some(palette.get_or_insert(block_id)?)
+            });
+        }
+
+        let blocks = std::mem::take(&mut self.blocks)
+            .into_iter()
+            .filter_map(|mut block| {
+                block.palette_index = remap[block.palette_index as usize]?;
+                block.overlay = block.overlay.and_then(|index| remap[index as usize]);
+                Some(block)
+            })
+            .collect();
+
+        self.palette = palette;
+        self.blocks = blocks;
+        Ok(())
+    }
+
+    /// 整理为规范形式：同一坐标只保留最后一个方块，方块按 Y → Z → X 排序，
+    /// 调色板只保留用到的方块ID并按字符串排序
+    ///
+    /// 内容相同的区块整理后完全一致，与添加方块的顺序无关；调色板索引无效时返回错误。
+    pub fn canonicalize(&mut self) -> Result<(), McStreamError> {
+        let mut seen = HashSet::new();
+        let mut blocks: Vec<Block> = std::mem::take(&mut self.blocks)
+            .into_iter()
+            .rev()
+            .filter(|block| seen.insert(block.pos))
+            .collect();
+        blocks.sort_unstable_by_key(|block| (block.pos.y, block.pos.z, block.pos.x));
+
+        let mut used: Vec<u32> = blocks
+            .iter()
+            .flat_map(|block| std::iter::once(block.palette_index).chain(block.overlay))
+            .collect();
+        used.sort_unstable();
+        used.dedup();
+        if used
+            .last()
+            .is_some_and(|&index| index as usize >= self.palette.len())
+        {
+            return Err(McStreamError::PaletteError("无效的调色板索引".to_string()));
+        }
+        used.sort_by(|&a, &b| self.palette[a].cmp(&self.palette[b]));
+
+        // 调色板中重复的ID合并为一个条目
+        let mut palette = Palette::new();
+        let mut remap = vec![0u32; self.palette.len()];
+        for index in used {
+            remap[index as usize] = palette.get_or_insert(self.palette[index].clone())?;
+        }
+        for block in &mut blocks {
+            block.palette_index = remap[block.palette_index as usize];
+            block.overlay = block.overlay.map(|index| remap[index as usize]);
+        }
+
+        self.palette = palette;
+        self.blocks = blocks;
+        Ok(())
+    }
+
+    // 移除未被方块或覆盖层引用的调色板条目，并调整其后的索引
+    fn remove_palette_entry_if_unused(&mut self, index: u32) {
+        let used = self
+            .blocks
+            .iter()
+            .any(|block| block.palette_index == index || block.overlay == Some(index));
+        if used || index as usize >= self.palette.len() {
+            return;
+        }
+
+        self.palette.remove(index);
+        for block in &mut self.blocks {
+            if block.palette_index > index {
+                block.palette_index -= 1;
+            }
+            if let Some(overlay) = block.overlay.as_mut() {
+                if *overlay > index {
+                    *overlay -= 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packer::McsEncoder;
+
+    const EXTREME_XZ: [i32; 10] = [
+        -17,
+        -16,
+        -1,
+        0,
+        15,
+        16,
+        -30_000_000,
+        30_000_000,
+        i32::MIN,
+        i32::MAX,
+    ];
+
+    #[test]
+    fn local_global_round_trip() {
+        for x in EXTREME_XZ {
+            for z in EXTREME_XZ {
+                for y in [-64, 0, 319] {
+                    let pos = BlockPos::new(x, y, z);
+                    let local = pos.local_pos();
+                    assert!(local.x < 16 && local.z < 16);
+                    assert_eq!(local.to_global(pos.chunk_pos()), pos);
+                }
+            }
+        }
+
+        let pos = BlockPos::new(-17, 0, -1);
+        assert_eq!(pos.chunk_pos(), ChunkPos::new(-2, -1));
+        assert_eq!((pos.local_pos().x, pos.local_pos().z), (15, 15));
+    }
+
+    #[test]
+    fn out_of_range_y_does_not_wrap() {
+        for y in [i32::MIN, -65, i32::MAX - 10, i32::MAX] {
+            assert_eq!(BlockPos::new(0, y, 0).local_pos().y, u16::MAX);
+        }
+    }
+
+    // 全局坐标 → (方块ID, 覆盖层ID)
+    fn block_ids(data: &McsData) -> HashMap<BlockPos, (String, Option<String>)> {
+        let mut ids = HashMap::new();
+        for chunk in data.chunks.values() {
+            for block in &chunk.blocks {
+                let id = chunk.palette[block.palette_index].clone();
+                let overlay = block.overlay.map(|i| chunk.palette[i].clone());
+                ids.insert(block.pos.to_global(chunk.pos), (id, overlay));
+            }
+        }
+        ids
+    }
+
+    #[test]
+    fn replace_in_region_remaps_overlays() {
+        let mut encoder = McsEncoder::new(CompressionType::None);
+        for x in 0..4 {
+            encoder
+                .add_block_with_overlay(
+                    "minecraft:oak_stairs".to_string(),
+                    "minecraft:water".to_string(),
+                    x,
+                    0,
+                    0,
+                    None,
+                )
+                .unwrap();
+        }
+        let mut data = encoder.to_mcs_data();
+
+        let min = BlockPos::new(0, 0, 0);
+        let max = BlockPos::new(3, 0, 0);
+        let replaced = data
+            .replace_in_region(min, max, "minecraft:water", "minecraft:lava")
+            .unwrap();
+        assert_eq!(replaced, 4);
+        let chunk = &data.chunks[&ChunkPos::new(0, 0)];
+        assert!(chunk.palette.index_of("minecraft:water").is_none());
+        for (_, overlay) in block_ids(&data).into_values() {
+            assert_eq!(overlay.as_deref(), Some("minecraft:lava"));
+        }
+
+        // 覆盖层替换为空气时去掉覆盖层，主方块保留
+        let max = BlockPos::new(1, 0, 0);
+        data.replace_in_region(min, max, "minecraft:lava", "minecraft:air")
+            .unwrap();
+        let ids = block_ids(&data);
+        assert_eq!(ids.len(), 4);
+        assert_eq!(ids[&BlockPos::new(1, 0, 0)].1, None);
+        assert_eq!(
+            ids[&BlockPos::new(2, 0, 0)].1.as_deref(),
+            Some("minecraft:lava")
+        );
+    }
+
+    #[test]
+    fn replace_in_region_boundaries() {
+        let mut encoder = McsEncoder::new(CompressionType::None);
+        for x in -17..=17 {
+            for y in [63, 64] {
+                let id = if (x, y) == (0, 64) {
+                    "minecraft:stone"
+                } else {
+                    "minecraft:dirt"
+                };
+                encoder.add_block(id.to_string(), x, y, -1, None).unwrap();
+            }
+        }
+        let mut data = encoder.to_mcs_data();
+
+        // 两端点都包含在内，端点顺序不影响；区域跨过区块 -1/0 与 0/1 的边界
+        let replaced = data
+            .replace_in_region(
+                BlockPos::new(16, 64, -1),
+                BlockPos::new(-16, 64, -1),
+                "minecraft:dirt",
+                "minecraft:grass_block",
+            )
+            .unwrap();
+        assert_eq!(replaced, 32); // x = 0 处是石头
+        let ids = block_ids(&data);
+        for x in -17..=17 {
+            let top = ids[&BlockPos::new(x, 64, -1)].0.as_str();
+            let expected = match x {
+                0 => "minecraft:stone",
+                -16..=16 => "minecraft:grass_block",
+                _ => "minecraft:dirt",
+            };
+            assert_eq!(top, expected, "x = {}", x);
+            assert_eq!(ids[&BlockPos::new(x, 63, -1)].0, "minecraft:dirt");
+        }
+
+        // 替换为空气时删除方块，只剩空气的区块被移除
+        let removed = data
+            .replace_in_region(
+                BlockPos::new(-17, 63, -1),
+                BlockPos::new(-17, 64, -1),
+                "minecraft:dirt",
+                "minecraft:air",
+            )
+            .unwrap();
+        assert_eq!(removed, 2);
+        assert!(!data.chunks.contains_key(&ChunkPos::new(-2, -1)));
+        let chunk = &data.chunks[&ChunkPos::new(-1, -1)];
+        assert!(chunk.palette.index_of("minecraft:dirt").is_some());
+
+        // 区块 1 中仍有区域外的泥土，调色板条目保留
+        let chunk = &data.chunks[&ChunkPos::new(1, -1)];
+        assert!(chunk.palette.index_of("minecraft:dirt").is_some());
+
+        // 空气不被存储，不能作为被替换的方块
+        assert!(data
+            .replace_in_region(
+                BlockPos::new(0, 0, 0),
+                BlockPos::new(0, 0, 0),
+                "minecraft:air",
+                "minecraft:dirt",
+            )
+            .is_err());
+    }
+}
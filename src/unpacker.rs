@@ -1,119 +1,678 @@
 use crate::{
-    chunk::{decompress_chunk, read_chunk_index},
+    bloom::{read_palette_blooms, PaletteBloom},
+    chunk::{deserialize_chunk_with, read_chunk_index, DecodedChunk, SpatialIndex},
+    compression::{compression_type_from_u8, decompress_data_limited_with, dictionary_id},
     error::McStreamError,
-    header::read_header,
-    types::{ChunkData, ChunkIndexEntry, ChunkPos, McsData, McsHeader},
+    format::{index_entry_size, HEADER_SIZE, INDEX_COUNT_SIZE},
+    header::{read_header, McsFlags},
+    integrity::{leaf_hash, merkle_root, read_tree_hash, Hash, TreeHash},
+    journal::{apply_event, read_events, JournalEvent},
+    palette::{IdTable, PaletteId, PaletteInterner},
+    types::{
+        Block, BlockPos, ChunkData, ChunkIndexEntry, ChunkOrder, ChunkPos, FaceMask, LocalBlockPos,
+        McsData, McsHeader, Occupancy,
+    },
     utils::validate_file_size,
-    CompressionType,
+    CompressionType, MCS_MAGIC,
 };
+use byteorder::{LittleEndian, ReadBytesExt};
+use flate2::read::GzDecoder;
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufRead, BufReader};
 use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
+use std::rc::Rc;
+use std::sync::{Arc, OnceLock};
+use tempfile::NamedTempFile;
+
+// gzip数据的魔数
+pub(crate) const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// 稠密数组中表示空气的特殊索引
+pub const GRID_AIR: u16 = u16::MAX;
+
+/// 稠密数组允许的最大单元数，防止过大的内存分配
+pub const MAX_GRID_VOLUME: u64 = 64 * 1024 * 1024;
+
+/// 解码选项
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    /// 保持单独压缩的NBT数据不解压，通过 `Block::nbt_decompressed` 按需解压
+    pub keep_nbt_compressed: bool,
+    /// 跳过方块数少于此值的区块，拆分为多个索引条目的区块按总数计；仅对索引中记录了方块数的文件（1.1及以上）生效
+    pub min_block_count: u32,
+    /// 单个区块解压后的最大字节数，超过时视为该区块解码失败
+    pub max_decompressed_size: Option<u64>,
+    /// 不读取NBT数据，所有方块的 `nbt` 均为 `None`；适用于只关心方块分布的场景
+    pub skip_nbt: bool,
+    /// Zstandard压缩字典，文件头部记录了字典ID时必须提供且ID一致
+    pub dictionary: Option<Vec<u8>>,
+    /// 方块ID表，区块按ID表编码时必须提供且表ID一致
+    pub id_table: Option<IdTable>,
+    /// 在调用线程中按顺序解码区块；未启用 `parallel` 特性时总是按顺序解码
+    pub sequential: bool,
+    /// 并行解码使用的rayon线程池，未设置时使用全局线程池
+    #[cfg(feature = "parallel")]
+    pub thread_pool: Option<Arc<rayon::ThreadPool>>,
+}
+
+/// 解码时发现的非致命问题，文件本身仍可正常读取
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeWarning {
+    /// 头部含有本版本不认识的标志位，对应的数据被忽略
+    UnknownHeaderFlags(u8),
+    /// 尾部各段之后还有 `len` 字节未被使用的数据
+    TrailingBytes { offset: u64, len: u64 },
+    /// 调色板中同一方块ID出现了多次
+    DuplicatePaletteEntry { chunk: ChunkPos, block_id: String },
+    /// 调色板条目没有被任何方块引用
+    UnusedPaletteEntry { chunk: ChunkPos, block_id: String },
+    /// 方块ID不是 `命名空间:路径` 的形式
+    MalformedBlockId { chunk: ChunkPos, block_id: String },
+}
+
+impl fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeWarning::UnknownHeaderFlags(bits) => {
+                write!(f, "未知的头部标志位 0x{:02X}，对应的数据被忽略", bits)
+            }
+            DecodeWarning::TrailingBytes { offset, len } => {
+                write!(f, "偏移 {} 处有 {} 字节未使用的尾随数据", offset, len)
+            }
+            DecodeWarning::DuplicatePaletteEntry { chunk, block_id } => write!(
+                f,
+                "区块 ({}, {}) 的调色板重复包含 {}",
+                chunk.x, chunk.z, block_id
+            ),
+            DecodeWarning::UnusedPaletteEntry { chunk, block_id } => write!(
+                f,
+                "区块 ({}, {}) 的调色板条目 {} 未被使用",
+                chunk.x, chunk.z, block_id
+            ),
+            DecodeWarning::MalformedBlockId { chunk, block_id } => write!(
+                f,
+                "区块 ({}, {}) 的方块ID {:?} 缺少命名空间或路径",
+                chunk.x, chunk.z, block_id
+            ),
+        }
+    }
+}
+
+/// 解码报告，列出解码过程中发现的非致命问题（见 [`McsDecoder::from_file_with_report`]）
+#[derive(Debug, Clone, Default)]
+pub struct DecodeReport {
+    pub warnings: Vec<DecodeWarning>,
+}
+
+impl DecodeReport {
+    /// 没有任何警告
+    pub fn is_clean(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    fn warn(&mut self, warning: DecodeWarning) {
+        self.warnings.push(warning);
+    }
+}
+
+/// 区块过滤器，返回 `false` 的区块不会被读取
+pub type ChunkFilter = Box<dyn Fn(ChunkPos) -> bool + Send + Sync>;
 
 /// MCS解码器，用于将MCS格式解包成建筑数据
 pub struct McsDecoder {
     header: McsHeader,
     chunks: HashMap<ChunkPos, ChunkData>,
+    spatial_indexes: HashMap<ChunkPos, OnceLock<SpatialIndex>>, // 未随文件存储的索引在首次查询时建立
+    chunk_format: u8,                                           // 各区块格式字节的并集
+    compression_level: Option<i32>,
+    id_table: Option<IdTable>,
+    index_positions: Vec<ChunkPos>, // 索引条目对应的区块坐标，与尾部的布隆过滤器一一对应
+    footer: Footer,
+    failed_chunks: Vec<(ChunkPos, McStreamError)>,
 }
 
-impl McsDecoder {
-    /// 从MCS文件读取数据
-    pub fn from_file<P: AsRef<Path> + std::marker::Sync + std::marker::Copy>(
-        path: P,
-    ) -> Result<Self, McStreamError> {
-        let file = File::open(path)?;
-        let file_size = file.metadata()?.len();
+/// 解码器构建器，集中配置各项解码选项
+#[derive(Default)]
+pub struct McsDecoderBuilder {
+    options: DecodeOptions,
+    verify_integrity: bool,
+    lenient: bool,
+    chunk_filter: Option<ChunkFilter>,
+}
 
-        if file_size < 20 {
-            // 最小文件头大小
-            return Err(McStreamError::ValidationError(format!(
-                "文件过小，大小为 {} 字节",
-                file_size
-            )));
+impl McsDecoderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 以已有的解码选项为基础
+    pub fn from_options(options: DecodeOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
         }
+    }
 
-        let mut reader = BufReader::new(file);
+    /// 保持单独压缩的NBT数据不解压
+    pub fn keep_nbt_compressed(mut self, keep: bool) -> Self {
+        self.options.keep_nbt_compressed = keep;
+        self
+    }
 
-        // 验证文件大小
-        validate_file_size(&mut reader)?;
+    /// 跳过方块数少于此值的区块
+    pub fn min_block_count(mut self, count: u32) -> Self {
+        self.options.min_block_count = count;
+        self
+    }
 
-        // 读取头部
-        let header = read_header(&mut reader)?;
+    /// 单个区块解压后的最大字节数
+    pub fn max_decompressed_size(mut self, size: u64) -> Self {
+        self.options.max_decompressed_size = Some(size);
+        self
+    }
 
-        // 跳转到索引表位置
-        if header.index_table_offset as u64 >= file_size {
-            return Err(McStreamError::ValidationError(format!(
-                "索引表偏移 ({}) 超出文件大小 ({})",
-                header.index_table_offset, file_size
-            )));
-        }
+    /// 跳过所有NBT数据
+    pub fn skip_nbt(mut self, skip: bool) -> Self {
+        self.options.skip_nbt = skip;
+        self
+    }
+
+    /// 使用Zstandard压缩字典解码（文件以该字典编码时必须提供）
+    pub fn dictionary(mut self, dictionary: Vec<u8>) -> Self {
+        self.options.dictionary = Some(dictionary);
+        self
+    }
 
-        reader.seek(SeekFrom::Start(header.index_table_offset as u64))?;
+    /// 使用方块ID表还原按编号存储的调色板（文件以该表编码时必须提供）
+    pub fn id_table(mut self, table: IdTable) -> Self {
+        self.options.id_table = Some(table);
+        self
+    }
 
-        // 读取区块索引表
-        let index_entries = read_chunk_index(&mut reader)?;
+    /// 是否并行解码区块，默认并行；关闭后在调用线程中按顺序解码，适合同时处理大量文件的服务
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.options.sequential = !parallel;
+        self
+    }
 
-        // 检查是否有区块
-        if index_entries.is_empty() {
-            return Err(McStreamError::ChunkIndexError);
-        }
+    /// 在指定的线程池中并行解码区块，避免占用rayon全局线程池
+    #[cfg(feature = "parallel")]
+    pub fn thread_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+        self.options.thread_pool = Some(pool);
+        self
+    }
+
+    /// 读取区块数据的同时校验其树形哈希叶子，文件不含树形哈希时打开失败
+    ///
+    /// 只校验实际解码的区块，被过滤掉的区块不会读取。
+    pub fn verify_integrity(mut self, verify: bool) -> Self {
+        self.verify_integrity = verify;
+        self
+    }
+
+    /// 单个区块解码（或校验）失败时跳过该区块，而不是整体失败
+    ///
+    /// 失败的区块可通过 [`McsDecoder::failed_chunks`] 获取。
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// 只读取过滤器返回 `true` 的区块
+    pub fn chunk_filter(mut self, filter: ChunkFilter) -> Self {
+        self.chunk_filter = Some(filter);
+        self
+    }
+
+    /// 按当前配置打开MCS文件
+    ///
+    /// 整个文件被gzip包裹（如用于网络传输）时会自动识别，先解压到临时文件再读取。
+    pub fn open<P: AsRef<Path> + Sync + Copy>(self, path: P) -> Result<McsDecoder, McStreamError> {
+        self.open_reporting(path.as_ref(), &mut DecodeReport::default(), false)
+    }
+
+    /// 按当前配置打开MCS文件，同时返回解码过程中发现的非致命问题
+    ///
+    /// 除 `open` 也会检查的未知标志位与尾随数据外，还会检查各区块调色板中
+    /// 重复、未使用或格式不正确的方块ID。
+    pub fn open_with_report<P: AsRef<Path>>(
+        self,
+        path: P,
+    ) -> Result<(McsDecoder, DecodeReport), McStreamError> {
+        let mut report = DecodeReport::default();
+        let decoder = self.open_reporting(path.as_ref(), &mut report, true)?;
+        Ok((decoder, report))
+    }
 
-        // 检查所有区块的偏移是否在文件范围内
-        for entry in &index_entries {
-            let chunk_end = (entry.data_offset + entry.compressed_size) as u64;
-            if chunk_end > file_size {
-                return Err(McStreamError::ValidationError(format!(
-                    "区块数据超出文件范围，结束位置 {} 超出文件大小 {}",
-                    chunk_end, file_size
-                )));
+    fn open_reporting(
+        self,
+        path: &Path,
+        report: &mut DecodeReport,
+        check_palettes: bool,
+    ) -> Result<McsDecoder, McStreamError> {
+        let decoder = match gunzip_if_wrapped(path)? {
+            Some(unwrapped) => self.open_unwrapped(unwrapped.path(), report)?,
+            None => self.open_unwrapped(path, report)?,
+        };
+        if check_palettes {
+            for (pos, chunk) in decoder.chunks_sorted() {
+                check_palette(*pos, chunk, report);
             }
         }
+        Ok(decoder)
+    }
 
-        // 并行读取和解压所有区块
-        let compression_type = header.compression;
-        let chunks: Result<HashMap<ChunkPos, ChunkData>, McStreamError> = index_entries
-            .par_iter()
-            .map(|entry| {
-                // 跳转到区块数据位置
-                let mut chunk_reader = std::fs::File::open(path)?;
-                chunk_reader.seek(SeekFrom::Start(entry.data_offset as u64))?;
+    /// 异步打开MCS文件，读取与解压在tokio的阻塞线程池中进行，不阻塞异步运行时
+    #[cfg(feature = "async")]
+    pub async fn open_async<P: AsRef<Path>>(self, path: P) -> Result<McsDecoder, McStreamError> {
+        let path = path.as_ref().to_path_buf();
+        tokio::task::spawn_blocking(move || self.open(path.as_path()))
+            .await
+            .map_err(|e| McStreamError::Io(std::io::Error::other(e)))?
+    }
 
-                // 读取压缩数据
-                let mut compressed_data = vec![0u8; entry.compressed_size as usize];
-                chunk_reader.read_exact(&mut compressed_data)?;
+    /// 按当前配置打开MCS文件，调色板中相同的方块ID在所有区块间共享同一份存储
+    ///
+    /// 只返回区块数据；适用于区块多而方块种类少、需要长期驻留内存的场景。
+    /// 宽松模式下解码失败的区块记录在返回值的 `failed_chunks` 中。
+    pub fn open_interned<P: AsRef<Path>>(self, path: P) -> Result<InternedChunks, McStreamError> {
+        let unwrapped = gunzip_if_wrapped(path.as_ref())?;
+        let path = unwrapped.as_ref().map_or(path.as_ref(), |u| u.path());
 
-                // 创建区块坐标
-                let pos = ChunkPos::new(entry.chunk_x, entry.chunk_z);
+        let interner = PaletteInterner::new();
+        let loaded = self.load_chunks(
+            path,
+            &|id| interner.intern(id),
+            &mut DecodeReport::default(),
+        )?;
 
-                // 解压并解析区块数据
-                let chunk = decompress_chunk(&compressed_data, compression_type, pos)?;
+        Ok(InternedChunks {
+            chunks: loaded.chunks,
+            failed_chunks: loaded.failed_chunks,
+        })
+    }
+
+    fn open_unwrapped(
+        self,
+        path: &Path,
+        report: &mut DecodeReport,
+    ) -> Result<McsDecoder, McStreamError> {
+        let id_table = self.options.id_table.clone();
+        let loaded = self.load_chunks(path, &str::to_owned, report)?;
+        let footer = read_footer_reporting(path, &loaded.header, loaded.footer_offset, report)?;
 
-                Ok((pos, chunk))
+        let mut indexes = loaded.spatial_indexes;
+        let spatial_indexes = loaded
+            .chunks
+            .keys()
+            .map(|pos| {
+                (
+                    *pos,
+                    indexes
+                        .remove(pos)
+                        .map_or_else(OnceLock::new, OnceLock::from),
+                )
             })
             .collect();
 
-        // 处理区块结果
-        let chunks = chunks?;
+        Ok(McsDecoder {
+            header: loaded.header,
+            chunks: loaded.chunks,
+            spatial_indexes,
+            chunk_format: loaded.chunk_format,
+            compression_level: loaded.compression_level,
+            id_table,
+            index_positions: loaded.index_positions,
+            footer,
+            failed_chunks: loaded.failed_chunks,
+        })
+    }
 
-        // 计算最后一个区块数据的结束位置，用于读取尾部
-        let last_entry = index_entries
+    // 读取尾部保存的叶子哈希，与索引条目一一对应；根哈希须与叶子一致
+    fn expected_leaves(
+        &self,
+        path: &Path,
+        header: &McsHeader,
+        footer_offset: u64,
+        entry_count: usize,
+    ) -> Result<Vec<Option<Hash>>, McStreamError> {
+        if !header.has_tree_hash() {
+            return Err(McStreamError::ValidationError(
+                "文件不包含树形哈希".to_string(),
+            ));
+        }
+
+        // 树形哈希段位于尾部最前面
+        let mut reader = BufReader::new(File::open(path)?);
+        reader.seek(SeekFrom::Start(footer_offset))?;
+        let stored = read_tree_hash(&mut reader)?;
+
+        if merkle_root(&stored.leaves) != stored.root || stored.leaves.len() != entry_count {
+            if !self.lenient {
+                return Err(McStreamError::ValidationError(
+                    "树形哈希校验失败：根哈希与叶子哈希不一致".to_string(),
+                ));
+            }
+            log::warn!("{} 的根哈希与叶子哈希不一致", path.display());
+        }
+
+        let mut leaves = stored.leaves.into_iter();
+        Ok((0..entry_count).map(|_| leaves.next()).collect())
+    }
+
+    // 读取索引并按配置解码区块，调色板条目经 `intern` 转换
+    fn load_chunks<S: PaletteId + Send>(
+        &self,
+        path: &Path,
+        intern: &(impl Fn(&str) -> S + Sync),
+        report: &mut DecodeReport,
+    ) -> Result<LoadedChunks<S>, McStreamError> {
+        let (header, mut index_entries, _) = read_validated_index(path)?;
+        log::debug!(
+            "打开 {}：版本 {}.{}，{} 个索引条目",
+            path.display(),
+            header.version >> 8,
+            header.version & 0xFF,
+            index_entries.len()
+        );
+
+        let unknown = header.flags().unknown();
+        if unknown != McsFlags::empty() {
+            log::warn!(
+                "{} 含有未知的头部标志位 0x{:02X}，对应的数据将被忽略",
+                path.display(),
+                unknown.bits()
+            );
+            report.warn(DecodeWarning::UnknownHeaderFlags(unknown.bits()));
+        }
+
+        validate_dictionary(&header, &self.options)?;
+
+        // 区块数据与索引表之后的位置，用于读取尾部（已校验不超出文件）
+        let footer_offset = footer_offset(&header, &index_entries)?;
+        let index_positions = index_entries
             .iter()
-            .max_by_key(|e| e.data_offset + e.compressed_size)
-            .ok_or(McStreamError::ChunkIndexError)?;
-        let footer_offset = (last_entry.data_offset + last_entry.compressed_size) as u64;
+            .map(|e| ChunkPos::new(e.chunk_x, e.chunk_z))
+            .collect();
 
-        // 确保签名在文件范围内
-        if footer_offset > file_size {
-            return Err(McStreamError::ValidationError(format!(
-                "文件格式错误：区块数据结束位置 ({}) 超出文件大小 ({})",
-                footer_offset, file_size
-            )));
+        // 校验树形哈希时，每个区块的叶子哈希在解码读取数据的同时计算，无需再读一遍文件
+        let mut expected_leaves = if self.verify_integrity {
+            Some(self.expected_leaves(path, &header, footer_offset, index_entries.len())?)
+        } else {
+            None
+        };
+
+        if let Some(filter) = &self.chunk_filter {
+            let keep: Vec<bool> = index_entries
+                .iter()
+                .map(|e| filter(ChunkPos::new(e.chunk_x, e.chunk_z)))
+                .collect();
+            let mut keep_iter = keep.iter();
+            index_entries.retain(|_| *keep_iter.next().unwrap_or(&false));
+            if let Some(leaves) = expected_leaves.as_mut() {
+                let mut keep_iter = keep.iter();
+                leaves.retain(|_| *keep_iter.next().unwrap_or(&false));
+            }
+        }
+
+        // 并行读取和解压区块，宽松模式下哈希不匹配的区块与解码失败一样被跳过
+        let mut chunks = HashMap::new();
+        let mut spatial_indexes = HashMap::new();
+        let mut chunk_format = 0;
+        let mut compression_level = None;
+        let mut failed_chunks = Vec::new();
+        for (pos, chunk) in decode_chunks(
+            path,
+            &header,
+            &index_entries,
+            expected_leaves.as_deref(),
+            &self.options,
+            intern,
+        ) {
+            match chunk {
+                Ok((chunk, spatial_index, prefix)) => {
+                    chunk_format |= prefix.format;
+                    compression_level = compression_level.or(prefix.compression_level);
+                    // 拆分存储的区块合并后，各部分的索引不再适用，查询时重新建立
+                    if chunks.contains_key(&chunk.pos) {
+                        spatial_indexes.remove(&chunk.pos);
+                    } else if let Some(index) = spatial_index {
+                        spatial_indexes.insert(chunk.pos, index);
+                    }
+                    insert_chunk(&mut chunks, chunk)?
+                }
+                Err(e) if self.lenient => {
+                    log::warn!("区块 ({}, {}) 解码失败，已跳过: {}", pos.x, pos.z, e);
+                    failed_chunks.push((pos, e))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(LoadedChunks {
+            header,
+            chunks,
+            spatial_indexes,
+            chunk_format,
+            compression_level,
+            index_positions,
+            failed_chunks,
+            footer_offset,
+        })
+    }
+}
+
+/// 调色板共享存储的区块数据（见 [`McsDecoderBuilder::open_interned`]）
+#[derive(Debug)]
+pub struct InternedChunks {
+    pub chunks: HashMap<ChunkPos, ChunkData<Arc<str>>>,
+    pub failed_chunks: Vec<(ChunkPos, McStreamError)>,
+}
+
+// 解码得到的区块以及读取尾部所需的信息
+struct LoadedChunks<S> {
+    header: McsHeader,
+    chunks: HashMap<ChunkPos, ChunkData<S>>,
+    spatial_indexes: HashMap<ChunkPos, SpatialIndex>,
+    chunk_format: u8, // 各区块格式字节的并集
+    compression_level: Option<i32>,
+    index_positions: Vec<ChunkPos>, // 过滤前全部索引条目的坐标
+    failed_chunks: Vec<(ChunkPos, McStreamError)>,
+    footer_offset: u64,
+}
+
+/// 区块数据之后的尾部内容
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Footer {
+    pub tree_hash: Option<TreeHash>,
+    pub palette_blooms: Vec<PaletteBloom>, // 与区块索引表顺序一致
+    pub origin: Option<BlockPos>,
+    pub journal: Vec<JournalEvent>,
+    pub signature: Option<Vec<u8>>,
+    pub extension: Option<Vec<u8>>, // 仅分帧的尾部含扩展数据
+}
+
+impl McsDecoder {
+    /// 只检查文件结构能否打开：读取头部、索引表与尾部并校验偏移范围，不解压任何区块
+    ///
+    /// 返回发现的第一个结构问题，比 `from_file` 快得多，适合对文件库做快速检查。
+    pub fn can_open<P: AsRef<Path>>(path: P) -> Result<(), McStreamError> {
+        let path = path.as_ref();
+        let (header, entries, _) = read_validated_index(path)?;
+        read_footer(path, &header, footer_offset(&header, &entries)?)?;
+        Ok(())
+    }
+
+    /// 只读取索引表，估计完整解码后方块与区块结构占用的内存（字节），不解压任何区块
+    ///
+    /// 按索引中记录的方块数计算，格式中没有记录解压后的大小，NBT数据与调色板字符串不计入，结果应视为下限。
+    /// 1.0版本的文件索引不含方块数，返回错误。
+    pub fn estimate_memory<P: AsRef<Path>>(path: P) -> Result<u64, McStreamError> {
+        let (_, entries, _) = read_validated_index(path)?;
+
+        let mut chunks = HashSet::new();
+        let mut blocks = 0u64;
+        for entry in &entries {
+            let count = entry.block_count.ok_or_else(|| {
+                McStreamError::ValidationError("1.0版本的索引不含方块数，无法估计内存".to_string())
+            })?;
+            blocks += count as u64;
+            chunks.insert((entry.chunk_x, entry.chunk_z));
+        }
+
+        // 每个区块还有区块结构、两个按坐标索引的哈希表条目与空间索引的占位
+        let per_chunk = size_of::<ChunkData>()
+            + 2 * size_of::<ChunkPos>()
+            + size_of::<OnceLock<SpatialIndex>>();
+        Ok(blocks * size_of::<Block>() as u64 + chunks.len() as u64 * per_chunk as u64)
+    }
+
+    /// 从MCS文件读取数据
+    pub fn from_file<P: AsRef<Path> + std::marker::Sync + std::marker::Copy>(
+        path: P,
+    ) -> Result<Self, McStreamError> {
+        Self::from_file_with_options(path, &DecodeOptions::default())
+    }
+
+    /// 使用指定的解码选项从MCS文件读取数据
+    pub fn from_file_with_options<P: AsRef<Path> + std::marker::Sync + std::marker::Copy>(
+        path: P,
+        options: &DecodeOptions,
+    ) -> Result<Self, McStreamError> {
+        McsDecoderBuilder::from_options(options.clone()).open(path)
+    }
+
+    /// 从MCS文件读取数据，同时返回非致命问题的报告，见 [`McsDecoderBuilder::open_with_report`]
+    pub fn from_file_with_report<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<(Self, DecodeReport), McStreamError> {
+        McsDecoderBuilder::new().open_with_report(path)
+    }
+
+    /// 异步读取MCS文件，见 [`McsDecoderBuilder::open_async`]
+    #[cfg(feature = "async")]
+    pub async fn from_file_async<P: AsRef<Path>>(path: P) -> Result<Self, McStreamError> {
+        McsDecoderBuilder::new().open_async(path).await
+    }
+
+    /// 创建解码器构建器
+    pub fn builder() -> McsDecoderBuilder {
+        McsDecoderBuilder::new()
+    }
+
+    /// 尽可能读取文件中可解码的区块，单个区块的错误不会导致整体失败
+    ///
+    /// 头部与索引表仍须有效。返回的数据不含尾部（编辑日志不会被重放），
+    /// 解码失败的区块与对应错误一并返回。
+    pub fn from_file_lenient<P: AsRef<Path> + std::marker::Sync + std::marker::Copy>(
+        path: P,
+    ) -> Result<(McsData, Vec<(ChunkPos, McStreamError)>), McStreamError> {
+        let (header, index_entries, _) = read_validated_index(path)?;
+
+        let mut chunks = HashMap::new();
+        let mut failures = Vec::new();
+        for (pos, chunk) in decode_chunks(
+            path,
+            &header,
+            &index_entries,
+            None,
+            &DecodeOptions::default(),
+            &str::to_owned,
+        ) {
+            match chunk {
+                Ok((chunk, _, _)) => insert_chunk(&mut chunks, chunk)?,
+                Err(e) => {
+                    log::warn!("区块 ({}, {}) 解码失败，已跳过: {}", pos.x, pos.z, e);
+                    failures.push((pos, e))
+                }
+            }
         }
 
-        Ok(Self { header, chunks })
+        Ok((McsData { header, chunks }, failures))
+    }
+
+    /// 不解压区块，列出可能含有指定方块ID的区块
+    ///
+    /// 依据尾部的调色板布隆过滤器判断，可能有误报但不会漏报；
+    /// 文件不含过滤器时无法排除任何区块，返回全部区块。
+    pub fn chunks_may_contain<P: AsRef<Path>>(
+        path: P,
+        block_id: &str,
+    ) -> Result<Vec<ChunkPos>, McStreamError> {
+        let (header, entries, _) = read_validated_index(&path)?;
+
+        let blooms = if header.has_palette_bloom() {
+            let footer = read_footer(&path, &header, footer_offset(&header, &entries)?)?;
+            if footer.palette_blooms.len() != entries.len() {
+                return Err(McStreamError::ValidationError(
+                    "布隆过滤器数量与区块索引不一致".to_string(),
+                ));
+            }
+            Some(footer.palette_blooms)
+        } else {
+            None
+        };
+
+        let mut seen = HashSet::new();
+        let mut chunks = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            let pos = ChunkPos::new(entry.chunk_x, entry.chunk_z);
+            let possible = blooms
+                .as_ref()
+                .is_none_or(|blooms| blooms[i].may_contain(block_id));
+            if possible && seen.insert(pos) {
+                chunks.push(pos);
+            }
+        }
+
+        Ok(chunks)
+    }
+
+    /// 不解压区块，判断文件是否可能含有指定方块ID
+    pub fn may_contain<P: AsRef<Path>>(path: P, block_id: &str) -> Result<bool, McStreamError> {
+        Ok(!Self::chunks_may_contain(path, block_id)?.is_empty())
+    }
+
+    /// 列出已加载的区块中调色板含有指定方块ID的区块，按坐标排序
+    ///
+    /// 文件带有布隆过滤器时先用打开时读取的过滤器排除区块，其余区块再查调色板确认，
+    /// 不重新读取文件，结果没有误报。
+    pub fn chunks_containing(&self, block_id: &str) -> Vec<ChunkPos> {
+        // 拆分存储的各部分共用整个区块的过滤器，任一部分排除即可排除整个区块
+        let excluded: HashSet<ChunkPos> =
+            if self.footer.palette_blooms.len() == self.index_positions.len() {
+                self.index_positions
+                    .iter()
+                    .zip(&self.footer.palette_blooms)
+                    .filter(|(_, bloom)| !bloom.may_contain(block_id))
+                    .map(|(pos, _)| *pos)
+                    .collect()
+            } else {
+                HashSet::new()
+            };
+
+        let mut chunks: Vec<ChunkPos> = self
+            .chunks
+            .iter()
+            .filter(|(pos, chunk)| {
+                !excluded.contains(pos) && chunk.palette.index_of(block_id).is_some()
+            })
+            .map(|(pos, _)| *pos)
+            .collect();
+        chunks.sort_unstable_by_key(|pos| (pos.x, pos.z));
+        chunks
+    }
+
+    /// 宽松模式下未能读取的区块及对应错误
+    pub fn failed_chunks(&self) -> &[(ChunkPos, McStreamError)] {
+        &self.failed_chunks
     }
 
     /// 获取区块数据
@@ -121,11 +680,423 @@ impl McsDecoder {
         &self.chunks
     }
 
+    /// 按 `(x, z)` 排序返回全部区块
+    pub fn chunks_sorted(&self) -> Vec<(&ChunkPos, &ChunkData)> {
+        self.chunks_sorted_by(ChunkOrder::Linear)
+    }
+
+    /// 按指定顺序返回全部区块
+    pub fn chunks_sorted_by(&self, order: ChunkOrder) -> Vec<(&ChunkPos, &ChunkData)> {
+        let mut chunks: Vec<_> = self.chunks.iter().collect();
+        match order {
+            ChunkOrder::Linear => chunks.sort_unstable_by_key(|(pos, _)| (pos.x, pos.z)),
+            ChunkOrder::Morton => chunks.sort_unstable_by_key(|(pos, _)| pos.morton_code()),
+        }
+        chunks
+    }
+
     /// 获取指定坐标的区块
     pub fn get_chunk(&self, x: i32, z: i32) -> Option<&ChunkData> {
         self.chunks.get(&ChunkPos::new(x, z))
     }
 
+    /// 获取全局坐标处的方块及其ID，坐标处没有方块时返回 `None`
+    ///
+    /// 文件中未存储空间索引的区块在首次查询时建立索引，之后的查询为对数时间。
+    /// 同一坐标有多个方块时返回列表中最后一个。
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> Option<(&str, &Block)> {
+        let pos = BlockPos::new(x, y, z);
+        let chunk = self.chunks.get(&pos.chunk_pos())?;
+        let index = self.spatial_indexes.get(&chunk.pos)?;
+        let index = index.get_or_init(|| SpatialIndex::build(&chunk.blocks));
+        let block = &chunk.blocks[index.find(&chunk.blocks, pos.local_pos())?];
+        Some((chunk.palette.get(block.palette_index)?, block))
+    }
+
+    /// 遍历水平坐标满足掩码的所有方块，`mask` 接收全局 `(x, z)`
+    ///
+    /// 会检查每个区块；掩码范围已知时使用 [`blocks_in_mask_within`](Self::blocks_in_mask_within)
+    /// 可跳过范围外的区块。调色板索引无效的方块会被跳过。
+    pub fn blocks_in_mask<'a, F>(&'a self, mask: F) -> impl Iterator<Item = (BlockPos, &'a str)>
+    where
+        F: Fn(i32, i32) -> bool + 'a,
+    {
+        self.masked_blocks(mask, None)
+    }
+
+    /// 同 [`blocks_in_mask`](Self::blocks_in_mask)，但只检查与 `min`..=`max`（`(x, z)`
+    /// 全局坐标，含两端）相交的区块，掩码在范围外的结果应为 `false`
+    pub fn blocks_in_mask_within<'a, F>(
+        &'a self,
+        mask: F,
+        min: (i32, i32),
+        max: (i32, i32),
+    ) -> impl Iterator<Item = (BlockPos, &'a str)>
+    where
+        F: Fn(i32, i32) -> bool + 'a,
+    {
+        let lo = (min.0.min(max.0) >> 4, min.1.min(max.1) >> 4);
+        let hi = (min.0.max(max.0) >> 4, min.1.max(max.1) >> 4);
+        self.masked_blocks(mask, Some((lo, hi)))
+    }
+
+    fn masked_blocks<'a, F>(
+        &'a self,
+        mask: F,
+        chunk_bounds: Option<((i32, i32), (i32, i32))>,
+    ) -> impl Iterator<Item = (BlockPos, &'a str)>
+    where
+        F: Fn(i32, i32) -> bool + 'a,
+    {
+        let mask = Rc::new(mask);
+        self.chunks
+            .values()
+            .filter(move |chunk| {
+                chunk_bounds.is_none_or(|(lo, hi)| {
+                    (lo.0..=hi.0).contains(&chunk.pos.x) && (lo.1..=hi.1).contains(&chunk.pos.z)
+                })
+            })
+            .flat_map(move |chunk| {
+                let mask = Rc::clone(&mask);
+                chunk.blocks.iter().filter_map(move |block| {
+                    let pos = block.pos.to_global(chunk.pos);
+                    if !mask(pos.x, pos.z) {
+                        return None;
+                    }
+                    let block_id = chunk.palette.get(block.palette_index)?;
+                    Some((pos, block_id))
+                })
+            })
+    }
+
+    /// 遍历带有NBT数据的方块（箱子、告示牌等方块实体），给出坐标、方块ID与NBT数据
+    ///
+    /// NBT为文件中存储的形式：以 [`McsDecoderBuilder::keep_nbt_compressed`] 打开时可能仍是单独压缩的，
+    /// 可用 [`Block::nbt_decompressed`] 解压。调色板索引无效的方块会被跳过。
+    pub fn iter_nbt_blocks(&self) -> impl Iterator<Item = (BlockPos, &str, &[u8])> + '_ {
+        self.chunks.values().flat_map(|chunk| {
+            chunk.blocks.iter().filter_map(move |block| {
+                let nbt = block.nbt.as_deref()?;
+                let block_id = chunk.palette.get(block.palette_index)?;
+                Some((block.pos.to_global(chunk.pos), block_id, nbt))
+            })
+        })
+    }
+
+    /// 所有带NBT数据的方块按全局坐标建立的映射，便于按坐标反复查找方块实体
+    ///
+    /// NBT的形式同 [`iter_nbt_blocks`](Self::iter_nbt_blocks)。同一坐标有多个方块时以最后一个为准，
+    /// 它不带NBT时该坐标不在映射中。
+    pub fn nbt_index(&self) -> HashMap<BlockPos, &[u8]> {
+        let mut index = HashMap::new();
+        for chunk in self.chunks.values() {
+            for block in &chunk.blocks {
+                let pos = block.pos.to_global(chunk.pos);
+                match block.nbt.as_deref() {
+                    Some(nbt) => index.insert(pos, nbt),
+                    None => index.remove(&pos),
+                };
+            }
+        }
+        index
+    }
+
+    /// 遍历所有有外露面的方块及其外露面
+    ///
+    /// 某个面的相邻位置没有存储方块（包括跨区块的相邻位置）时即视为外露，
+    /// 不区分透明方块；六个面都被遮挡的方块不会出现在结果中。
+    pub fn exposed_faces(&self) -> impl Iterator<Item = (BlockPos, FaceMask)> + '_ {
+        self.exposed_blocks().map(|(pos, _, faces)| (pos, faces))
+    }
+
+    /// 与 `exposed_faces` 相同，同时给出方块ID
+    pub fn exposed_blocks(&self) -> impl Iterator<Item = (BlockPos, &str, FaceMask)> + '_ {
+        let occupied: Rc<HashMap<ChunkPos, HashSet<LocalBlockPos>>> = Rc::new(
+            self.chunks
+                .iter()
+                .map(|(pos, chunk)| (*pos, chunk.blocks.iter().map(|b| b.pos).collect()))
+                .collect(),
+        );
+
+        self.chunks.values().flat_map(move |chunk| {
+            let occupied = Rc::clone(&occupied);
+            chunk.blocks.iter().filter_map(move |block| {
+                let pos = block.pos.to_global(chunk.pos);
+                let mut faces = FaceMask::default();
+                for (face, (dx, dy, dz)) in FaceMask::DIRECTIONS {
+                    let neighbor = BlockPos::new(pos.x + dx, pos.y + dy, pos.z + dz);
+                    let present = occupied
+                        .get(&neighbor.chunk_pos())
+                        .is_some_and(|set| set.contains(&neighbor.local_pos()));
+                    if !present {
+                        faces.insert(face);
+                    }
+                }
+                if faces.is_empty() {
+                    return None;
+                }
+                let block_id = chunk.palette.get(block.palette_index)?;
+                Some((pos, block_id, faces))
+            })
+        })
+    }
+
+    /// 按固定大小分批遍历全部方块，便于增量写出而无需一次收集所有方块
+    ///
+    /// 区块按 `(x, z)` 顺序遍历；回调参数为全局坐标、方块ID与解压后的NBT数据，
+    /// 最后一批可能不足 `batch_size` 个。
+    pub fn for_each_block_batch<F>(&self, batch_size: usize, mut f: F) -> Result<(), McStreamError>
+    where
+        F: FnMut(&[(BlockPos, &str, Option<&[u8]>)]),
+    {
+        if batch_size == 0 {
+            return Err(McStreamError::ValidationError(
+                "批大小必须大于0".to_string(),
+            ));
+        }
+
+        let blocks = self
+            .chunks_sorted()
+            .into_iter()
+            .flat_map(|(_, chunk)| chunk.blocks.iter().map(move |block| (chunk, block)));
+
+        let mut pending = Vec::with_capacity(batch_size);
+        for item in blocks {
+            pending.push(item);
+            if pending.len() == batch_size {
+                emit_block_batch(&pending, &mut f)?;
+                pending.clear();
+            }
+        }
+        if !pending.is_empty() {
+            emit_block_batch(&pending, &mut f)?;
+        }
+
+        Ok(())
+    }
+
+    /// 统计包围盒、体积与填充率（扫描全部已加载的方块）
+    pub fn occupancy(&self) -> Occupancy {
+        Occupancy::from_chunks(self.chunks.values())
+    }
+
+    /// 检查所有方块的Y坐标是否都在 `min_y`..=`max_y` 内（含两端），用于粘贴到目标世界前的检查
+    ///
+    /// 有方块超出范围时返回这些方块的全局坐标，按 `(y, z, x)` 排序。覆盖层与方块同位置，不单独检查。
+    pub fn fits_in_height(&self, min_y: i32, max_y: i32) -> Result<(), Vec<BlockPos>> {
+        let mut outside: Vec<BlockPos> = self
+            .chunks
+            .values()
+            .flat_map(|chunk| {
+                chunk
+                    .blocks
+                    .iter()
+                    .map(|block| block.pos.to_global(chunk.pos))
+            })
+            .filter(|pos| pos.y < min_y || pos.y > max_y)
+            .collect();
+        if outside.is_empty() {
+            return Ok(());
+        }
+        outside.sort_unstable_by_key(|pos| (pos.y, pos.z, pos.x));
+        Err(outside)
+    }
+
+    /// 已加载区块坐标的最小值与最大值（各轴独立取值），没有区块时返回 `None`
+    ///
+    /// 只看区块坐标、不扫描方块，适合在裁剪或导出前估算所需的缓冲区大小。
+    pub fn chunk_bounds(&self) -> Option<(ChunkPos, ChunkPos)> {
+        let mut positions = self.chunks.keys();
+        let first = *positions.next()?;
+        Some(positions.fold((first, first), |(min, max), pos| {
+            (
+                ChunkPos::new(min.x.min(pos.x), min.z.min(pos.z)),
+                ChunkPos::new(max.x.max(pos.x), max.z.max(pos.z)),
+            )
+        }))
+    }
+
+    /// 统计各方块ID的方块数量，覆盖层不计入
+    ///
+    /// 每个区块先按调色板索引计数，启用 `parallel` 特性时各区块并行统计后两两合并。
+    pub fn block_counts(&self) -> HashMap<String, u64> {
+        #[cfg(feature = "parallel")]
+        let counts = self
+            .chunks
+            .par_iter()
+            .map(|(_, chunk)| chunk_block_counts(chunk))
+            .reduce(HashMap::new, merge_block_counts);
+        #[cfg(not(feature = "parallel"))]
+        let counts = self
+            .chunks
+            .values()
+            .map(chunk_block_counts)
+            .fold(HashMap::new(), merge_block_counts);
+
+        counts
+            .into_iter()
+            .map(|(block_id, count)| (block_id.to_string(), count))
+            .collect()
+    }
+
+    /// 将指定区域展开为稠密的三维数组
+    ///
+    /// 区域包含 `min` 与 `max` 两个端点，数组按 y → z → x 排列（x 变化最快，y 最慢），
+    /// 与 [`crate::packer::McsEncoder::from_voxel_grid`] 的布局相同；
+    /// 空气位置为 [`GRID_AIR`]，其余值为返回调色板中的索引。
+    pub fn to_grid(
+        &self,
+        min: BlockPos,
+        max: BlockPos,
+    ) -> Result<(Vec<u16>, Vec<String>), McStreamError> {
+        if min.x > max.x || min.y > max.y || min.z > max.z {
+            return Err(McStreamError::ValidationError(
+                "区域最小坐标大于最大坐标".to_string(),
+            ));
+        }
+
+        let size_x = (max.x as i64 - min.x as i64 + 1) as u64;
+        let size_y = (max.y as i64 - min.y as i64 + 1) as u64;
+        let size_z = (max.z as i64 - min.z as i64 + 1) as u64;
+
+        let volume = size_x
+            .checked_mul(size_y)
+            .and_then(|v| v.checked_mul(size_z))
+            .filter(|v| *v <= MAX_GRID_VOLUME)
+            .ok_or_else(|| {
+                McStreamError::ValidationError(format!(
+                    "区域体积过大，最多允许 {} 个单元",
+                    MAX_GRID_VOLUME
+                ))
+            })?;
+
+        let mut grid = vec![GRID_AIR; volume as usize];
+        let mut palette: Vec<String> = Vec::new();
+        let mut id_to_index: HashMap<&str, u16> = HashMap::new();
+
+        let min_chunk = min.chunk_pos();
+        let max_chunk = max.chunk_pos();
+
+        for chunk in self.chunks.values() {
+            if chunk.pos.x < min_chunk.x
+                || chunk.pos.x > max_chunk.x
+                || chunk.pos.z < min_chunk.z
+                || chunk.pos.z > max_chunk.z
+            {
+                continue;
+            }
+
+            for block in &chunk.blocks {
+                let pos = block.pos.to_global(chunk.pos);
+                if pos.x < min.x
+                    || pos.x > max.x
+                    || pos.y < min.y
+                    || pos.y > max.y
+                    || pos.z < min.z
+                    || pos.z > max.z
+                {
+                    continue;
+                }
+
+                let block_id = chunk
+                    .palette
+                    .get(block.palette_index)
+                    .ok_or_else(|| McStreamError::PaletteError("无效的调色板索引".to_string()))?;
+
+                let index = match id_to_index.get(block_id) {
+                    Some(index) => *index,
+                    None => {
+                        // GRID_AIR 保留给空气，调色板最多使用其余的索引
+                        if palette.len() >= GRID_AIR as usize {
+                            return Err(McStreamError::PaletteError(
+                                "区域调色板条目数超过上限".to_string(),
+                            ));
+                        }
+                        let index = palette.len() as u16;
+                        palette.push(block_id.to_string());
+                        id_to_index.insert(block_id, index);
+                        index
+                    }
+                };
+
+                let offset = ((pos.y - min.y) as u64 * size_z + (pos.z - min.z) as u64) * size_x
+                    + (pos.x - min.x) as u64;
+                grid[offset as usize] = index;
+            }
+        }
+
+        Ok((grid, palette))
+    }
+
+    /// 获取文件中的编辑日志
+    pub fn journal(&self) -> &[JournalEvent] {
+        &self.footer.journal
+    }
+
+    /// 获取文件中的树形哈希
+    pub fn tree_hash(&self) -> Option<&TreeHash> {
+        self.footer.tree_hash.as_ref()
+    }
+
+    // 各区块格式字节的并集，文件不含区块格式字节时为0
+    pub(crate) fn chunk_format(&self) -> u8 {
+        self.chunk_format
+    }
+
+    // 解码时使用的方块ID表
+    pub(crate) fn id_table(&self) -> Option<&IdTable> {
+        self.id_table.as_ref()
+    }
+
+    /// 写入时记录的压缩级别（见 `McsEncoder::with_compression_level`），未记录时为 `None`
+    ///
+    /// 只供查看，解码不需要它；同一文件中各区块记录的级别通常相同，返回最先解码的区块的记录。
+    pub fn compression_level(&self) -> Option<i32> {
+        self.compression_level
+    }
+
+    /// 获取文件尾部的签名数据
+    pub fn signature(&self) -> Option<&[u8]> {
+        self.footer.signature.as_deref()
+    }
+
+    /// 尾部中应用自定义的扩展数据，尾部未分帧时为 `None`
+    pub fn footer_extension(&self) -> Option<&[u8]> {
+        self.footer.extension.as_deref()
+    }
+
+    /// 采集建筑时记录的世界坐标原点
+    pub fn origin(&self) -> Option<BlockPos> {
+        self.footer.origin
+    }
+
+    /// 把建筑粘贴到 `target` 时需要加到每个方块坐标上的平移量，使原点落在 `target`
+    ///
+    /// 文件未记录原点时视为 `(0, 0, 0)`。
+    pub fn paste_offset(&self, target: BlockPos) -> BlockPos {
+        let origin = self.origin().unwrap_or(BlockPos::new(0, 0, 0));
+        BlockPos::new(
+            target.x.wrapping_sub(origin.x),
+            target.y.wrapping_sub(origin.y),
+            target.z.wrapping_sub(origin.z),
+        )
+    }
+
+    /// 在区块数据上按顺序重放编辑日志，得到当前状态
+    pub fn replay(&self) -> Result<McsData, McStreamError> {
+        let mut chunks = self.chunks.clone();
+        for event in &self.footer.journal {
+            apply_event(&mut chunks, event)?;
+        }
+
+        let mut header = self.header.clone();
+        let mut flags = header.flags();
+        flags.remove(McsFlags::JOURNAL);
+        header.flags = flags.bits();
+
+        Ok(McsData { header, chunks })
+    }
+
     /// 转换为McsData结构
     pub fn to_mcs_data(&self) -> McsData {
         McsData {
@@ -134,6 +1105,14 @@ impl McsDecoder {
         }
     }
 
+    /// 消耗解码器并转换为McsData结构，避免复制区块数据
+    pub fn into_mcs_data(self) -> McsData {
+        McsData {
+            header: self.header,
+            chunks: self.chunks,
+        }
+    }
+
     /// 获取文件头
     pub fn header(&self) -> &McsHeader {
         &self.header
@@ -141,14 +1120,632 @@ impl McsDecoder {
 
     /// 获取压缩算法类型
     pub fn compression_type(&self) -> CompressionType {
-        match self.header.compression {
-            0 => CompressionType::None,
-            1 => CompressionType::Zstandard,
-            2 => CompressionType::LZ4,
-            3 => CompressionType::Brotli,
-            _ => CompressionType::None, // 不应该发生，因为在read_header时已验证
+        // 已在读取头部时验证
+        compression_type_from_u8(self.header.compression).unwrap_or(CompressionType::None)
+    }
+}
+
+/// 读取头部与区块索引表，并校验所有偏移都在文件范围内（见 [`McsDecoder::can_open`]）
+///
+/// 返回头部、索引条目与文件大小。
+pub(crate) fn read_validated_index<P: AsRef<Path>>(
+    path: P,
+) -> Result<(McsHeader, Vec<ChunkIndexEntry>, u64), McStreamError> {
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let (header, entries) = read_validated_index_from(BufReader::new(file), file_size)?;
+    Ok((header, entries, file_size))
+}
+
+// 从任意可定位的数据源读取并校验头部与索引表，`file_size` 为数据源的总长度
+pub(crate) fn read_validated_index_from<R: Read + Seek>(
+    mut reader: R,
+    file_size: u64,
+) -> Result<(McsHeader, Vec<ChunkIndexEntry>), McStreamError> {
+    if file_size < HEADER_SIZE as u64 {
+        // 最小文件头大小
+        return Err(McStreamError::ValidationError(format!(
+            "文件过小，大小为 {} 字节",
+            file_size
+        )));
+    }
+
+    // 验证文件大小
+    validate_file_size(&mut reader)?;
+
+    // 读取头部
+    let header = read_header(&mut reader)?;
+
+    // 索引表只能紧跟头部或位于区块数据之后，落在头部内部的偏移说明文件已损坏
+    if (header.index_table_offset as u64) < HEADER_SIZE as u64 {
+        return Err(McStreamError::ValidationError(format!(
+            "索引表偏移 ({}) 位于头部 ({} 字节) 内",
+            header.index_table_offset, HEADER_SIZE
+        )));
+    }
+
+    // 跳转到索引表位置
+    if header.index_table_offset as u64 >= file_size {
+        return Err(McStreamError::ValidationError(format!(
+            "索引表偏移 ({}) 超出文件大小 ({})",
+            header.index_table_offset, file_size
+        )));
+    }
+
+    reader.seek(SeekFrom::Start(header.index_table_offset as u64))?;
+
+    // 读取区块索引表
+    let index_entries = read_chunk_index(&mut reader, header.version)?;
+
+    // 检查是否有区块
+    if index_entries.is_empty() {
+        return Err(McStreamError::ChunkIndexError);
+    }
+
+    // 索引表必须完整位于文件内
+    let index_start = header.index_table_offset as u64;
+    let index_end = index_table_end(&header, &index_entries);
+    if index_end > file_size {
+        return Err(McStreamError::ValidationError(format!(
+            "索引表范围 [{}, {}) 超出文件范围",
+            index_start, index_end
+        )));
+    }
+
+    // 检查所有区块的偏移是否在文件范围内，且不与头部或索引表重叠
+    for entry in &index_entries {
+        // 区块坐标乘以16后必须仍能表示为 i32 的全局坐标
+        let chunk_range = (i32::MIN >> 4)..=(i32::MAX >> 4);
+        if !chunk_range.contains(&entry.chunk_x) || !chunk_range.contains(&entry.chunk_z) {
+            return Err(McStreamError::ValidationError(format!(
+                "区块坐标 ({}, {}) 超出范围",
+                entry.chunk_x, entry.chunk_z
+            )));
+        }
+
+        let chunk_start = entry.data_offset as u64;
+        let chunk_end = chunk_start + entry.compressed_size as u64;
+        if chunk_end > file_size {
+            return Err(McStreamError::ValidationError(format!(
+                "区块数据超出文件范围，结束位置 {} 超出文件大小 {}",
+                chunk_end, file_size
+            )));
+        }
+        if chunk_start < HEADER_SIZE as u64 || (chunk_start < index_end && chunk_end > index_start)
+        {
+            return Err(McStreamError::ValidationError(format!(
+                "区块 ({}, {}) 的数据与头部或索引表重叠",
+                entry.chunk_x, entry.chunk_z
+            )));
+        }
+    }
+
+    // 不紧跟头部的索引表必须位于全部区块数据之后
+    let data_end = index_entries
+        .iter()
+        .map(|e| e.data_offset as u64 + e.compressed_size as u64)
+        .max()
+        .unwrap_or(0);
+    if index_start != HEADER_SIZE as u64 && index_start < data_end {
+        return Err(McStreamError::ValidationError(format!(
+            "索引表偏移 ({}) 既不紧跟头部也不在区块数据 (结束于 {}) 之后",
+            index_start, data_end
+        )));
+    }
+
+    // 拆分的区块条目相邻排列，不相邻的重复坐标说明索引表已损坏
+    let mut seen = HashSet::new();
+    for (i, entry) in index_entries.iter().enumerate() {
+        let pos = ChunkPos::new(entry.chunk_x, entry.chunk_z);
+        let continues_previous = i > 0 && {
+            let prev = &index_entries[i - 1];
+            (prev.chunk_x, prev.chunk_z) == (pos.x, pos.z)
+        };
+        if !seen.insert(pos) && !continues_previous {
+            log::warn!("索引表中区块 ({}, {}) 重复出现", pos.x, pos.z);
+            return Err(McStreamError::ChunkIndexError);
+        }
+    }
+
+    // 尾部（签名、编辑日志等）必须从文件范围内开始
+    let footer_offset = footer_offset(&header, &index_entries)?;
+    if footer_offset > file_size {
+        return Err(McStreamError::ValidationError(format!(
+            "文件格式错误：区块数据结束位置 ({}) 超出文件大小 ({})",
+            footer_offset, file_size
+        )));
+    }
+
+    Ok((header, index_entries))
+}
+
+// 索引表的结束位置：条目数 + 各条目
+fn index_table_end(header: &McsHeader, entries: &[ChunkIndexEntry]) -> u64 {
+    let entry_size = index_entry_size(header.version) as u64;
+    header.index_table_offset as u64 + INDEX_COUNT_SIZE as u64 + entries.len() as u64 * entry_size
+}
+
+/// 计算尾部的起始偏移，即最后一个区块数据与索引表二者中靠后的结束位置
+pub(crate) fn footer_offset(
+    header: &McsHeader,
+    entries: &[ChunkIndexEntry],
+) -> Result<u64, McStreamError> {
+    entries
+        .iter()
+        .map(|e| e.data_offset as u64 + e.compressed_size as u64)
+        .max()
+        .map(|end| end.max(index_table_end(header, entries)))
+        .ok_or(McStreamError::ChunkIndexError)
+}
+
+/// 从尾部起始偏移读取树形哈希、编辑日志与签名
+///
+/// 顺序为：树形哈希段（如有）→ 调色板布隆过滤器段（如有）→ 采集原点（如有）→
+/// 编辑日志或签名（二者互斥，均延续到文件末尾）。分帧的签名文件在尾部之后还有数据时返回错误。
+pub(crate) fn read_footer<P: AsRef<Path>>(
+    path: P,
+    header: &McsHeader,
+    footer_offset: u64,
+) -> Result<Footer, McStreamError> {
+    read_footer_reporting(path, header, footer_offset, &mut DecodeReport::default())
+}
+
+// 读取尾部，各段之后的尾随数据记录到报告中
+fn read_footer_reporting<P: AsRef<Path>>(
+    path: P,
+    header: &McsHeader,
+    footer_offset: u64,
+    report: &mut DecodeReport,
+) -> Result<Footer, McStreamError> {
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    read_footer_from(
+        BufReader::new(file),
+        file_size,
+        header,
+        footer_offset,
+        report,
+    )
+}
+
+// 从任意可定位的数据源读取尾部，`file_size` 为数据源的总长度
+pub(crate) fn read_footer_from<R: Read + Seek>(
+    mut reader: R,
+    file_size: u64,
+    header: &McsHeader,
+    footer_offset: u64,
+    report: &mut DecodeReport,
+) -> Result<Footer, McStreamError> {
+    reader.seek(SeekFrom::Start(footer_offset))?;
+
+    let tree_hash = if header.has_tree_hash() {
+        Some(read_tree_hash(&mut reader)?)
+    } else {
+        None
+    };
+
+    let palette_blooms = if header.has_palette_bloom() {
+        read_palette_blooms(&mut reader)?
+    } else {
+        Vec::new()
+    };
+
+    let origin = if header.has_origin() {
+        let x = reader.read_i32::<LittleEndian>()?;
+        let y = reader.read_i32::<LittleEndian>()?;
+        let z = reader.read_i32::<LittleEndian>()?;
+        Some(BlockPos::new(x, y, z))
+    } else {
+        None
+    };
+
+    // 分帧的尾部先记录签名与扩展数据的长度，再依次存放二者
+    let (framed_signature, extension) = if header.has_framed_footer() {
+        let signature_len = reader.read_u32::<LittleEndian>()?;
+        let extension_len = reader.read_u32::<LittleEndian>()?;
+        let signature = read_framed(&mut reader, signature_len, "签名")?;
+        let extension = read_framed(&mut reader, extension_len, "扩展数据")?;
+        (header.has_signature().then_some(signature), Some(extension))
+    } else {
+        (None, None)
+    };
+
+    let journal = if header.has_journal() {
+        read_events(&mut reader)?
+    } else {
+        Vec::new()
+    };
+
+    let signature = if header.has_framed_footer() {
+        framed_signature
+    } else if header.has_signature() {
+        let mut signature = Vec::new();
+        reader.read_to_end(&mut signature)?;
+        Some(signature)
+    } else {
+        None
+    };
+
+    // 编辑日志与未分帧的签名延续到文件末尾，其余情况下尾部之后的数据被忽略
+    let end = reader.stream_position()?;
+    if end < file_size {
+        // 分帧的签名须恰好用完尾部，多余的数据说明签名长度有误或写入方出错
+        if header.has_framed_footer() && header.has_signature() {
+            return Err(McStreamError::ValidationError(format!(
+                "签名文件的分帧尾部之后还有 {} 字节多余数据（偏移 {}）",
+                file_size - end,
+                end
+            )));
+        }
+        report.warn(DecodeWarning::TrailingBytes {
+            offset: end,
+            len: file_size - end,
+        });
+    }
+
+    Ok(Footer {
+        tree_hash,
+        palette_blooms,
+        origin,
+        journal,
+        signature,
+        extension,
+    })
+}
+
+// 读取尾部中带长度前缀的一段，长度超出文件时返回错误
+fn read_framed<R: Read>(reader: &mut R, len: u32, what: &str) -> Result<Vec<u8>, McStreamError> {
+    let mut data = Vec::new();
+    reader.take(len as u64).read_to_end(&mut data)?;
+    if data.len() != len as usize {
+        return Err(McStreamError::ValidationError(format!(
+            "尾部{}长度 ({}) 超出文件范围",
+            what, len
+        )));
+    }
+    Ok(data)
+}
+
+/// 读取索引条目指向的区块压缩数据（不解压）
+pub fn read_raw_chunk<P: AsRef<Path>>(
+    path: P,
+    entry: &ChunkIndexEntry,
+) -> Result<Vec<u8>, McStreamError> {
+    // 跳转到区块数据位置
+    let mut chunk_reader = File::open(path)?;
+    chunk_reader.seek(SeekFrom::Start(entry.data_offset as u64))?;
+
+    // 读取压缩数据
+    let mut compressed_data = vec![0u8; entry.compressed_size as usize];
+    chunk_reader.read_exact(&mut compressed_data)?;
+
+    Ok(compressed_data)
+}
+
+/// 读取并解压索引条目指向的单个区块
+pub(crate) fn read_chunk_at<P: AsRef<Path>>(
+    path: P,
+    entry: &ChunkIndexEntry,
+    header: &McsHeader,
+    options: &DecodeOptions,
+) -> Result<ChunkData, McStreamError> {
+    read_chunk_at_with(path, entry, header, options, None, &mut str::to_owned)
+        .map(|(chunk, _, _)| chunk)
+}
+
+// 读取并解压单个区块，调色板条目经 `intern` 转换
+//
+// `expected_leaf` 存在时先校验压缩数据的叶子哈希，`Some(None)` 表示树形哈希中缺少该条目。
+fn read_chunk_at_with<P: AsRef<Path>, S: PaletteId>(
+    path: P,
+    entry: &ChunkIndexEntry,
+    header: &McsHeader,
+    options: &DecodeOptions,
+    expected_leaf: Option<Option<&Hash>>,
+    intern: &mut impl FnMut(&str) -> S,
+) -> Result<DecodedChunk<S>, McStreamError> {
+    let compressed_data = read_raw_chunk(path, entry)?;
+    decode_raw_chunk_with(
+        &compressed_data,
+        entry,
+        header,
+        options,
+        expected_leaf,
+        intern,
+    )
+}
+
+// 解压并解析一个区块的压缩数据，参数含义同 `read_chunk_at_with`
+pub(crate) fn decode_raw_chunk_with<S: PaletteId>(
+    compressed_data: &[u8],
+    entry: &ChunkIndexEntry,
+    header: &McsHeader,
+    options: &DecodeOptions,
+    expected_leaf: Option<Option<&Hash>>,
+    intern: &mut impl FnMut(&str) -> S,
+) -> Result<DecodedChunk<S>, McStreamError> {
+    // 创建区块坐标
+    let pos = ChunkPos::new(entry.chunk_x, entry.chunk_z);
+
+    if let Some(expected) = expected_leaf {
+        if expected != Some(&leaf_hash(compressed_data)) {
+            return Err(McStreamError::ValidationError(format!(
+                "树形哈希校验失败：区块 ({}, {}) 的哈希不匹配",
+                pos.x, pos.z
+            )));
+        }
+    }
+
+    // 解压并解析区块数据
+    let compression = compression_type_from_u8(header.compression)?;
+    let dictionary = if header.has_dictionary() {
+        options.dictionary.as_deref()
+    } else {
+        None
+    };
+    let data = decompress_data_limited_with(
+        compressed_data,
+        compression,
+        options.max_decompressed_size,
+        dictionary,
+    )?;
+    let prefixed = header.has_chunk_format();
+    deserialize_chunk_with(
+        &data,
+        pos,
+        prefixed,
+        options.skip_nbt,
+        options.id_table.as_ref(),
+        intern,
+    )
+}
+
+// 解压一批方块的NBT并调用回调
+fn emit_block_batch<F>(blocks: &[(&ChunkData, &Block)], f: &mut F) -> Result<(), McStreamError>
+where
+    F: FnMut(&[(BlockPos, &str, Option<&[u8]>)]),
+{
+    let nbt: Vec<Option<Cow<[u8]>>> = blocks
+        .iter()
+        .map(|(_, block)| match (&block.nbt, block.nbt_compression) {
+            (Some(_), Some(_)) => Ok(block.nbt_decompressed()?.map(Cow::Owned)),
+            (nbt, _) => Ok(nbt.as_deref().map(Cow::Borrowed)),
+        })
+        .collect::<Result<_, McStreamError>>()?;
+
+    let batch = blocks
+        .iter()
+        .zip(&nbt)
+        .map(|((chunk, block), nbt)| {
+            let block_id = chunk
+                .palette
+                .get(block.palette_index)
+                .ok_or_else(|| McStreamError::ValidationError("无效的调色板索引".to_string()))?;
+            Ok((block.pos.to_global(chunk.pos), block_id, nbt.as_deref()))
+        })
+        .collect::<Result<Vec<_>, McStreamError>>()?;
+
+    f(&batch);
+    Ok(())
+}
+
+// 文件使用压缩字典时，选项中的字典ID必须与头部一致
+pub(crate) fn validate_dictionary(
+    header: &McsHeader,
+    options: &DecodeOptions,
+) -> Result<(), McStreamError> {
+    if !header.has_dictionary() {
+        return Ok(());
+    }
+    match options.dictionary.as_deref().map(dictionary_id) {
+        Some(id) if id == header.dictionary_id => Ok(()),
+        Some(id) => Err(McStreamError::ValidationError(format!(
+            "压缩字典不匹配：文件需要ID为 {:08x} 的字典，提供的字典ID为 {:08x}",
+            header.dictionary_id, id
+        ))),
+        None => Err(McStreamError::ValidationError(format!(
+            "文件使用了ID为 {:08x} 的压缩字典，解码时需要提供该字典",
+            header.dictionary_id
+        ))),
+    }
+}
+
+// 检查调色板中重复、未被引用或缺少命名空间的方块ID
+fn check_palette(pos: ChunkPos, chunk: &ChunkData, report: &mut DecodeReport) {
+    let mut used = vec![false; chunk.palette.len()];
+    for block in &chunk.blocks {
+        for index in std::iter::once(block.palette_index).chain(block.overlay) {
+            if let Some(used) = used.get_mut(index as usize) {
+                *used = true;
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for (block_id, used) in chunk.palette.iter().zip(used) {
+        // 方块状态（`[...]`）之前须为 `命名空间:路径`
+        let name = block_id.split('[').next().unwrap_or_default();
+        let well_formed = name
+            .split_once(':')
+            .is_some_and(|(namespace, path)| !namespace.is_empty() && !path.is_empty());
+        if !well_formed {
+            report.warn(DecodeWarning::MalformedBlockId {
+                chunk: pos,
+                block_id: block_id.clone(),
+            });
+        }
+        if !seen.insert(block_id) {
+            report.warn(DecodeWarning::DuplicatePaletteEntry {
+                chunk: pos,
+                block_id: block_id.clone(),
+            });
+        }
+        if !used {
+            report.warn(DecodeWarning::UnusedPaletteEntry {
+                chunk: pos,
+                block_id: block_id.clone(),
+            });
+        }
+    }
+}
+
+// 统计单个区块中各方块ID的数量，调色板索引无效的方块不计入
+fn chunk_block_counts(chunk: &ChunkData) -> HashMap<&str, u64> {
+    let mut by_index = vec![0u64; chunk.palette.len()];
+    for block in &chunk.blocks {
+        if let Some(count) = by_index.get_mut(block.palette_index as usize) {
+            *count += 1;
+        }
+    }
+
+    let mut counts = HashMap::new();
+    for (block_id, count) in chunk.palette.iter().zip(by_index) {
+        if count > 0 {
+            *counts.entry(block_id.as_str()).or_default() += count;
         }
     }
+    counts
+}
+
+// 合并两份计数，把较小的一份并入较大的一份以减少插入次数
+fn merge_block_counts<'a>(
+    a: HashMap<&'a str, u64>,
+    b: HashMap<&'a str, u64>,
+) -> HashMap<&'a str, u64> {
+    let (mut large, small) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    for (block_id, count) in small {
+        *large.entry(block_id).or_default() += count;
+    }
+    large
+}
+
+/// 放入区块，同一坐标已存在时合并（编码器可能把一个区块拆分为多个索引条目）
+pub(crate) fn insert_chunk<S: PaletteId>(
+    chunks: &mut HashMap<ChunkPos, ChunkData<S>>,
+    chunk: ChunkData<S>,
+) -> Result<(), McStreamError> {
+    match chunks.entry(chunk.pos) {
+        Entry::Occupied(existing) => existing.into_mut().merge(chunk)?,
+        Entry::Vacant(slot) => {
+            slot.insert(chunk);
+        }
+    }
+    Ok(())
+}
+
+// 读取并解压索引中的区块（启用 `parallel` 特性且未要求顺序解码时并行），每个区块的结果单独返回
+fn decode_chunks<P: AsRef<Path> + Sync + Copy, S: PaletteId + Send>(
+    path: P,
+    header: &McsHeader,
+    entries: &[ChunkIndexEntry],
+    expected_leaves: Option<&[Option<Hash>]>,
+    options: &DecodeOptions,
+    intern: &(impl Fn(&str) -> S + Sync),
+) -> Vec<(ChunkPos, Result<DecodedChunk<S>, McStreamError>)> {
+    // 拆分的区块按各部分方块数之和整体保留或跳过
+    let mut totals: HashMap<ChunkPos, Option<u64>> = HashMap::new();
+    for entry in entries {
+        let total = totals
+            .entry(ChunkPos::new(entry.chunk_x, entry.chunk_z))
+            .or_insert(Some(0));
+        *total = total.zip(entry.block_count).map(|(a, b)| a + b as u64);
+    }
+    let wanted = |(_, entry): &(usize, &ChunkIndexEntry)| {
+        totals[&ChunkPos::new(entry.chunk_x, entry.chunk_z)]
+            .is_none_or(|count| count >= options.min_block_count as u64)
+    };
+    let decode = |(i, entry): (usize, &ChunkIndexEntry)| {
+        let pos = ChunkPos::new(entry.chunk_x, entry.chunk_z);
+        let expected_leaf = expected_leaves.map(|leaves| leaves.get(i).and_then(Option::as_ref));
+        let chunk = read_chunk_at_with(path, entry, header, options, expected_leaf, &mut |id| {
+            intern(id)
+        });
+        let chunk = chunk.and_then(|(mut chunk, spatial_index, prefix)| {
+            if !options.keep_nbt_compressed {
+                for block in &mut chunk.blocks {
+                    block.decompress_nbt_in_place()?;
+                }
+            }
+            log::debug!(
+                "解码区块 ({}, {})：{} 个方块",
+                pos.x,
+                pos.z,
+                chunk.blocks.len()
+            );
+            Ok((chunk, spatial_index, prefix))
+        });
+        (pos, chunk)
+    };
+
+    #[cfg(feature = "parallel")]
+    if !options.sequential {
+        let run = || {
+            entries
+                .par_iter()
+                .enumerate()
+                .filter(wanted)
+                .map(decode)
+                .collect()
+        };
+        return match &options.thread_pool {
+            Some(pool) => pool.install(run),
+            None => run(),
+        };
+    }
+    entries
+        .iter()
+        .enumerate()
+        .filter(wanted)
+        .map(decode)
+        .collect()
+}
+
+// 外层为gzip时解压到临时文件，解压后的大小不超过MCS文件的上限
+pub(crate) fn gunzip_if_wrapped(path: &Path) -> Result<Option<NamedTempFile>, McStreamError> {
+    let mut file = File::open(path)?;
+    let mut magic = [0u8; 2];
+    if file.read_exact(&mut magic).is_err() || magic != GZIP_MAGIC {
+        return Ok(None);
+    }
+    file.seek(SeekFrom::Start(0))?;
+    log::debug!("{} 被gzip包裹，解压到临时文件", path.display());
+
+    let mut unwrapped = NamedTempFile::new()?;
+    let limit = u32::MAX as u64 + 1;
+    std::io::copy(
+        &mut GzDecoder::new(BufReader::new(file)).take(limit),
+        unwrapped.as_file_mut(),
+    )?;
+    Ok(Some(unwrapped))
+}
+
+/// 只读取MCS文件的头部，文件被gzip包裹时只解压头部所需的字节
+pub fn read_mcs_header<P: AsRef<Path>>(path: P) -> Result<McsHeader, McStreamError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        read_header(&mut GzDecoder::new(reader))
+    } else {
+        read_header(&mut reader)
+    }
+}
+
+/// 文件是否为MCS格式（含gzip包裹的MCS），只检查魔数，读取失败时返回 `false`
+pub fn sniff<P: AsRef<Path>>(path: P) -> bool {
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    let mut reader = BufReader::new(file);
+    let gzipped = reader
+        .fill_buf()
+        .is_ok_and(|buf| buf.starts_with(&GZIP_MAGIC));
+
+    let mut magic = [0u8; 8];
+    let read = if gzipped {
+        GzDecoder::new(reader).read_exact(&mut magic)
+    } else {
+        reader.read_exact(&mut magic)
+    };
+    read.is_ok() && magic == *MCS_MAGIC
 }
 
 /// 从MCS文件读取区块索引（不加载区块数据）
@@ -163,5 +1760,282 @@ pub fn read_mcs_index<P: AsRef<Path>>(path: P) -> Result<Vec<ChunkIndexEntry>, M
     reader.seek(SeekFrom::Start(header.index_table_offset as u64))?;
 
     // 读取区块索引表
-    read_chunk_index(&mut reader)
+    read_chunk_index(&mut reader, header.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lazy::LazyMcsDecoder, packer::McsEncoder};
+    use tempfile::NamedTempFile;
+
+    fn write_temp(encoder: &McsEncoder) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        encoder.write_to_writer(file.as_file_mut()).unwrap();
+        file
+    }
+
+    #[test]
+    fn min_block_count_sums_split_chunks() {
+        let mut encoder = McsEncoder::new(CompressionType::None).with_max_chunk_bytes(64);
+        for y in 0..10 {
+            encoder
+                .add_block(format!("minecraft:block_{}", y), 0, y, 0, None)
+                .unwrap();
+        }
+        for x in 0..3 {
+            encoder
+                .add_block("minecraft:stone".to_string(), 16 + x, 0, 0, None)
+                .unwrap();
+        }
+        let file = write_temp(&encoder);
+        let entries = read_mcs_index(file.path()).unwrap();
+        assert!(entries.iter().filter(|e| e.chunk_x == 0).count() > 1);
+
+        let decoder = McsDecoder::builder()
+            .min_block_count(8)
+            .open(file.path())
+            .unwrap();
+        assert_eq!(decoder.get_chunks().len(), 1);
+        assert_eq!(decoder.get_chunk(0, 0).unwrap().blocks.len(), 10);
+    }
+
+    fn sample_encoder() -> McsEncoder {
+        let mut encoder = McsEncoder::new(CompressionType::None);
+        for i in 0..40 {
+            encoder
+                .add_block(
+                    format!("minecraft:block_{}", i % 7),
+                    i * 3 - 50,
+                    i,
+                    -i,
+                    None,
+                )
+                .unwrap();
+        }
+        encoder
+    }
+
+    #[test]
+    fn chunks_containing_uses_loaded_blooms() {
+        let plain = write_temp(&sample_encoder());
+        let bloomed = write_temp(&sample_encoder().with_palette_bloom());
+        let plain = McsDecoder::from_file(plain.path()).unwrap();
+        let decoder = McsDecoder::from_file(bloomed.path()).unwrap();
+
+        let mut expected: Vec<ChunkPos> = decoder
+            .get_chunks()
+            .iter()
+            .filter(|(_, chunk)| {
+                chunk
+                    .blocks
+                    .iter()
+                    .any(|b| chunk.palette.get(b.palette_index) == Some("minecraft:block_3"))
+            })
+            .map(|(pos, _)| *pos)
+            .collect();
+        expected.sort_unstable_by_key(|pos| (pos.x, pos.z));
+        assert!(!expected.is_empty());
+        assert_eq!(decoder.chunks_containing("minecraft:block_3"), expected);
+        assert_eq!(plain.chunks_containing("minecraft:block_3"), expected);
+        assert!(decoder.chunks_containing("minecraft:missing").is_empty());
+
+        let maybe = McsDecoder::chunks_may_contain(bloomed.path(), "minecraft:block_3").unwrap();
+        let unique: HashSet<ChunkPos> = maybe.iter().copied().collect();
+        assert_eq!(unique.len(), maybe.len());
+        assert!(expected.iter().all(|pos| unique.contains(pos)));
+    }
+
+    #[test]
+    fn lazy_opens_gzip_wrapped() {
+        let encoder = sample_encoder();
+        let file = NamedTempFile::new().unwrap();
+        encoder.write_to_file_gzip(file.path()).unwrap();
+
+        let lazy = LazyMcsDecoder::open(file.path()).unwrap();
+        let decoder = McsDecoder::from_file(file.path()).unwrap();
+        assert_eq!(lazy.chunk_positions().count(), decoder.get_chunks().len());
+        for (pos, chunk) in decoder.get_chunks() {
+            let lazy_chunk = lazy.get_chunk(pos.x, pos.z).unwrap().unwrap();
+            assert_eq!(lazy_chunk.blocks.len(), chunk.blocks.len());
+        }
+    }
+
+    #[test]
+    fn tail_index_round_trip() {
+        let head = write_temp(&sample_encoder().with_tree_hash());
+        let tail = write_temp(&sample_encoder().with_tree_hash().with_tail_index());
+
+        let head_header = read_mcs_header(head.path()).unwrap();
+        let tail_header = read_mcs_header(tail.path()).unwrap();
+        assert_eq!(head_header.index_table_offset as usize, HEADER_SIZE);
+        assert!(tail_header.index_table_offset as usize > HEADER_SIZE);
+        assert_eq!(
+            read_mcs_index(head.path()).unwrap().len(),
+            read_mcs_index(tail.path()).unwrap().len()
+        );
+
+        let lazy = LazyMcsDecoder::open(tail.path()).unwrap();
+        let head = McsDecoder::from_file(head.path()).unwrap();
+        let tail = McsDecoder::builder()
+            .verify_integrity(true)
+            .open(tail.path())
+            .unwrap();
+        assert!(head.to_mcs_data().content_eq(&tail.to_mcs_data()));
+        assert!(tail.tree_hash().is_some());
+        for (pos, chunk) in head.get_chunks() {
+            let lazy_chunk = lazy.get_chunk(pos.x, pos.z).unwrap().unwrap();
+            assert_eq!(lazy_chunk.blocks.len(), chunk.blocks.len());
+            for (a, b) in lazy_chunk.blocks.iter().zip(&chunk.blocks) {
+                assert_eq!(a.pos, b.pos);
+                assert!(a.content_eq(&lazy_chunk.palette, b, &chunk.palette));
+            }
+        }
+    }
+
+    #[test]
+    fn extreme_coordinates_round_trip() {
+        let coords = [-17, -16, -1, 0, 15, 16, -30_000_000, i32::MIN, i32::MAX];
+        let mut encoder = McsEncoder::new(CompressionType::None);
+        let mut expected = HashSet::new();
+        for (i, x) in coords.into_iter().enumerate() {
+            for (y, z) in [(-64, x), (319, -x.saturating_add(1))] {
+                encoder
+                    .add_block(format!("minecraft:block_{}", i), x, y, z, None)
+                    .unwrap();
+                expected.insert(BlockPos::new(x, y, z));
+            }
+        }
+        for y in [-65, 320, i32::MIN, i32::MAX - 10] {
+            assert!(encoder
+                .add_block("minecraft:stone".to_string(), 0, y, 0, None)
+                .is_err());
+        }
+
+        let file = write_temp(&encoder);
+        let decoder = McsDecoder::from_file(file.path()).unwrap();
+        let decoded: HashSet<BlockPos> = decoder
+            .get_chunks()
+            .values()
+            .flat_map(|chunk| chunk.blocks.iter().map(|b| b.pos.to_global(chunk.pos)))
+            .collect();
+        assert_eq!(decoded, expected);
+        assert_eq!(
+            decoder.get_block(-17, -64, -17).map(|(id, _)| id),
+            Some("minecraft:block_0")
+        );
+    }
+
+    fn write_bytes(bytes: &[u8]) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        std::io::Write::write_all(file.as_file_mut(), bytes).unwrap();
+        file
+    }
+
+    #[test]
+    fn non_adjacent_duplicate_chunks_rejected() {
+        let mut encoder = McsEncoder::new(CompressionType::None);
+        for x in 0..3 {
+            encoder
+                .add_block("minecraft:stone".to_string(), x * 16, 0, 0, None)
+                .unwrap();
+        }
+        let mut bytes = Vec::new();
+        encoder.write_to_writer(&mut bytes).unwrap();
+        let header = read_mcs_header(write_bytes(&bytes).path()).unwrap();
+        let entry_size = index_entry_size(header.version);
+        let first = header.index_table_offset as usize + INDEX_COUNT_SIZE;
+        let third = first + 2 * entry_size;
+        let coords = bytes[first..first + 8].to_vec();
+        bytes[third..third + 8].copy_from_slice(&coords);
+
+        let file = write_bytes(&bytes);
+        assert!(matches!(
+            read_validated_index(file.path()),
+            Err(McStreamError::ChunkIndexError)
+        ));
+    }
+
+    #[test]
+    fn signed_framed_footer_length() {
+        let encoder = sample_encoder()
+            .with_signature(vec![0xAB; 16])
+            .with_footer_extension(vec![1, 2, 3]);
+        let mut bytes = Vec::new();
+        encoder.write_to_writer(&mut bytes).unwrap();
+
+        // 恰好结束
+        let exact = write_bytes(&bytes);
+        let decoder = McsDecoder::from_file(exact.path()).unwrap();
+        assert_eq!(decoder.signature(), Some(&[0xAB; 16][..]));
+        assert_eq!(decoder.footer_extension(), Some(&[1, 2, 3][..]));
+
+        // 截断
+        let short = write_bytes(&bytes[..bytes.len() - 2]);
+        assert!(McsDecoder::from_file(short.path()).is_err());
+
+        // 多余数据
+        let mut overlong = bytes.clone();
+        overlong.extend_from_slice(&[0; 5]);
+        let overlong = write_bytes(&overlong);
+        assert!(matches!(
+            McsDecoder::from_file(overlong.path()),
+            Err(McStreamError::ValidationError(_))
+        ));
+
+        // 未签名的分帧尾部之后的数据只产生警告
+        let mut unsigned = Vec::new();
+        sample_encoder()
+            .with_footer_extension(vec![1, 2, 3])
+            .write_to_writer(&mut unsigned)
+            .unwrap();
+        unsigned.extend_from_slice(&[0; 5]);
+        let unsigned = write_bytes(&unsigned);
+        let (_, report) = McsDecoder::builder()
+            .open_with_report(unsigned.path())
+            .unwrap();
+        assert!(report
+            .warnings
+            .iter()
+            .any(|w| matches!(w, DecodeWarning::TrailingBytes { len: 5, .. })));
+    }
+
+    #[test]
+    fn signature_round_trip() {
+        let signature: Vec<u8> = (0..=255).collect();
+
+        // 未分帧的签名延续到文件末尾
+        let unframed = write_temp(&sample_encoder().with_signature(signature.clone()));
+        let decoder = McsDecoder::from_file(unframed.path()).unwrap();
+        assert!(decoder.header().has_signature());
+        assert_eq!(decoder.signature(), Some(&signature[..]));
+
+        // 分帧的签名带长度前缀，与采集原点等其他尾部内容共存
+        let framed = write_temp(
+            &sample_encoder()
+                .with_signature(signature.clone())
+                .with_framed_footer()
+                .with_origin(BlockPos::new(1, 2, 3)),
+        );
+        let decoder = McsDecoder::from_file(framed.path()).unwrap();
+        assert_eq!(decoder.signature(), Some(&signature[..]));
+        assert_eq!(decoder.footer_extension(), Some(&[][..]));
+        assert_eq!(decoder.origin(), Some(BlockPos::new(1, 2, 3)));
+
+        // 签名函数对树形哈希的根签名
+        let signed = write_temp(&sample_encoder().with_signer(Box::new(|root| root.to_vec())));
+        let decoder = McsDecoder::builder()
+            .verify_integrity(true)
+            .open(signed.path())
+            .unwrap();
+        let root = decoder.tree_hash().unwrap().root;
+        assert_eq!(decoder.signature(), Some(&root[..]));
+
+        // 未签名的文件没有签名
+        let unsigned = write_temp(&sample_encoder());
+        assert_eq!(
+            McsDecoder::from_file(unsigned.path()).unwrap().signature(),
+            None
+        );
+    }
 }
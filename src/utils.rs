@@ -1,5 +1,6 @@
 use crate::error::McStreamError;
-use std::io::Seek;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::io::{Read, Seek, Write};
 
 /// 验证文件大小是否在4GB限制内
 pub fn validate_file_size<S: Seek>(seeker: &mut S) -> Result<(), McStreamError> {
@@ -13,3 +14,90 @@ pub fn validate_file_size<S: Seek>(seeker: &mut S) -> Result<(), McStreamError>
 
     Ok(())
 }
+
+/// 写入无符号LEB128变长整数
+pub fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> Result<(), McStreamError> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            writer.write_all(&[byte])?;
+            return Ok(());
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+/// 读取无符号LEB128变长整数，结果超过 `max` 时返回错误
+pub fn read_varint<R: Read>(reader: &mut R, max: u64) -> Result<u64, McStreamError> {
+    let mut value = 0u64;
+    let mut shift = 0;
+
+    loop {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte)?;
+
+        let bits = (byte[0] & 0x7F) as u64;
+        if shift >= 64 || (shift > 0 && bits >> (64 - shift) != 0) {
+            return Err(McStreamError::ValidationError("变长整数溢出".to_string()));
+        }
+        value |= bits << shift;
+
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+
+    if value > max {
+        return Err(McStreamError::ValidationError(
+            "变长整数超出范围".to_string(),
+        ));
+    }
+
+    Ok(value)
+}
+
+/// 写入u16字段，`varint` 为真时使用变长整数
+pub(crate) fn write_u16_field<W: Write>(
+    writer: &mut W,
+    value: u16,
+    varint: bool,
+) -> Result<(), McStreamError> {
+    if varint {
+        write_varint(writer, value as u64)
+    } else {
+        Ok(writer.write_u16::<LittleEndian>(value)?)
+    }
+}
+
+/// 读取u16字段，`varint` 为真时使用变长整数
+pub(crate) fn read_u16_field<R: Read>(reader: &mut R, varint: bool) -> Result<u16, McStreamError> {
+    if varint {
+        Ok(read_varint(reader, u16::MAX as u64)? as u16)
+    } else {
+        Ok(reader.read_u16::<LittleEndian>()?)
+    }
+}
+
+/// 写入u32字段，`varint` 为真时使用变长整数
+pub(crate) fn write_u32_field<W: Write>(
+    writer: &mut W,
+    value: u32,
+    varint: bool,
+) -> Result<(), McStreamError> {
+    if varint {
+        write_varint(writer, value as u64)
+    } else {
+        Ok(writer.write_u32::<LittleEndian>(value)?)
+    }
+}
+
+/// 读取u32字段，`varint` 为真时使用变长整数
+pub(crate) fn read_u32_field<R: Read>(reader: &mut R, varint: bool) -> Result<u32, McStreamError> {
+    if varint {
+        Ok(read_varint(reader, u32::MAX as u64)? as u32)
+    } else {
+        Ok(reader.read_u32::<LittleEndian>()?)
+    }
+}